@@ -0,0 +1,81 @@
+use axum::{extract::FromRequestParts, http::request::Parts};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use chrono::Utc;
+use db::{ApiKey, api_key};
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, sea_query::Expr};
+use sha2::{Digest, Sha256};
+
+use crate::{AppState, error::ApiError};
+
+/// Header merchants send their key in. It isn't `Authorization` so a key can never be
+/// mistaken for a session token.
+pub const API_KEY_HEADER: &str = "X-Api-Key";
+
+const KEY_PREFIX: &str = "kr_";
+/// How much of the key is stored in clear to tell keys apart.
+const SHOWN_PREFIX_LEN: usize = 10;
+
+/// A new random key, its displayable prefix and the hash stored instead of it.
+pub fn generate() -> (String, String, String) {
+    let key = format!("{}{}", KEY_PREFIX, URL_SAFE_NO_PAD.encode(rand::random::<[u8; 32]>()));
+    let prefix = key[..SHOWN_PREFIX_LEN].to_string();
+    let hash = hash(&key);
+    (key, prefix, hash)
+}
+
+/// Keys are 256 random bits, so a plain sha256 is enough to keep them useless if the table leaks.
+pub fn hash(key: &str) -> String {
+    hex::encode(Sha256::digest(key.as_bytes()))
+}
+
+/// The merchant behind `X-Api-Key`: a key that exists and isn't revoked.
+#[derive(Debug, Clone)]
+pub struct MerchantKey(pub api_key::Model);
+
+impl MerchantKey {
+    pub fn require(&self, scope: &'static str) -> Result<&api_key::Model, ApiError> {
+        if self.0.has_scope(scope) {
+            Ok(&self.0)
+        } else {
+            Err(ApiError::MissingScope(scope))
+        }
+    }
+}
+
+impl FromRequestParts<AppState> for MerchantKey {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        let key = parts
+            .headers
+            .get(API_KEY_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .ok_or(ApiError::MissingCredentials)?;
+        let api_key = ApiKey::find()
+            .filter(api_key::Column::KeyHash.eq(hash(key)))
+            .filter(api_key::Column::RevokedAt.is_null())
+            .one(&state.db)
+            .await?
+            .ok_or(ApiError::InvalidApiKey)?;
+        ApiKey::update_many()
+            .col_expr(api_key::Column::LastUsedAt, Expr::value(Utc::now()))
+            .filter(api_key::Column::Id.eq(api_key.id))
+            .exec(&state.db)
+            .await?;
+        Ok(MerchantKey(api_key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_key_matches_its_hash_and_prefix() {
+        let (key, prefix, stored) = generate();
+        assert!(key.starts_with(KEY_PREFIX));
+        assert!(key.starts_with(&prefix));
+        assert_eq!(hash(&key), stored);
+        assert_ne!(generate().0, key);
+    }
+}
@@ -0,0 +1,131 @@
+//! Who is calling and which transactions they may touch.
+//!
+//! Handlers addressing a transaction or a channel follow one policy: no or bad credentials
+//! is 401, an unknown id is 404, and something that belongs to another user is 403.
+
+use axum::{
+    extract::FromRequestParts,
+    http::{header::AUTHORIZATION, request::Parts},
+};
+use db::{Channel, Transaction, UserWallet, WebhookEndpoint, channel, transaction, user_wallet, webhook_endpoint};
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, prelude::Uuid};
+
+use crate::{AppState, error::ApiError, response, session::Session, telegram_auth::TelegramUser};
+
+/// The authenticated caller: a Mini App user (`Authorization: tma ...`) or
+/// a TON Proof session (`Authorization: Bearer ...`).
+#[derive(Debug, Clone)]
+pub enum Requester {
+    Telegram { telegram_id: i64 },
+    Wallet {
+        address: String,
+        /// Telegram account the wallet was linked to with /linkwallet.
+        telegram_id: Option<i64>,
+    },
+}
+
+impl Requester {
+    /// Telegram account of the caller; a wallet must have been linked with /linkwallet.
+    pub fn require_telegram_id(&self) -> Result<i64, ApiError> {
+        self.telegram_id().ok_or(ApiError::WalletNotLinked)
+    }
+
+    pub fn telegram_id(&self) -> Option<i64> {
+        match self {
+            Requester::Telegram { telegram_id } => Some(*telegram_id),
+            Requester::Wallet { telegram_id, .. } => *telegram_id,
+        }
+    }
+
+    pub fn wallet_address(&self) -> Option<&str> {
+        match self {
+            Requester::Telegram { .. } => None,
+            Requester::Wallet { address, .. } => Some(address),
+        }
+    }
+}
+
+impl FromRequestParts<AppState> for Requester {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        let is_mini_app = parts
+            .headers
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.starts_with("tma "));
+        if is_mini_app {
+            let user = TelegramUser::from_request_parts(parts, state).await?;
+            return Ok(Requester::Telegram {
+                telegram_id: user.telegram_id,
+            });
+        }
+
+        let session = Session::from_request_parts(parts, state).await?;
+        let linked = UserWallet::find()
+            .filter(user_wallet::Column::Address.eq(&session.address))
+            .one(&state.db)
+            .await?;
+        Ok(Requester::Wallet {
+            address: session.address,
+            telegram_id: linked.map(|wallet| wallet.telegram_id),
+        })
+    }
+}
+
+/// Loads the transaction behind `public_id` if `requester` owns it. Merchant invoices have
+/// no known payer and are open to anyone signed in with the link.
+pub async fn find_owned_transaction(
+    db: &DatabaseConnection,
+    public_id: Uuid,
+    requester: &Requester,
+) -> Result<transaction::Model, ApiError> {
+    let tx = Transaction::find()
+        .filter(transaction::Column::PublicId.eq(public_id))
+        .one(db)
+        .await?
+        .ok_or(ApiError::TransactionNotFound)?;
+    response::record_correlation_id(tx.correlation_id);
+
+    // Транзакцию видит только тот, кто её создал в боте, или его привязанный кошелёк.
+    // Плательщик счёта из API неизвестен, его может оплатить любой, у кого есть ссылка
+    if tx.telegram_id.is_some() && requester.telegram_id() != tx.telegram_id {
+        return Err(ApiError::TransactionForbidden);
+    }
+    Ok(tx)
+}
+
+/// Loads the channel `channel_id` if `requester` owns it.
+pub async fn find_owned_channel(
+    db: &DatabaseConnection,
+    channel_id: i64,
+    requester: &Requester,
+) -> Result<channel::Model, ApiError> {
+    let owner = requester.require_telegram_id()?;
+    let channel = Channel::find_by_id(channel_id)
+        .one(db)
+        .await?
+        .ok_or(ApiError::ChannelNotFound)?;
+    if channel.owner_telegram_id != owner {
+        return Err(ApiError::ChannelForbidden);
+    }
+    Ok(channel)
+}
+
+/// Loads the webhook endpoint `webhook_id` if `requester` owns its channel.
+pub async fn find_owned_webhook(
+    db: &DatabaseConnection,
+    webhook_id: i64,
+    requester: &Requester,
+) -> Result<webhook_endpoint::Model, ApiError> {
+    let owner = requester.require_telegram_id()?;
+    let endpoint = WebhookEndpoint::find_by_id(webhook_id)
+        .one(db)
+        .await?
+        .ok_or(ApiError::WebhookNotFound)?;
+    let channel = Channel::find_by_id(endpoint.channel_id).one(db).await?;
+    if channel.is_none_or(|channel| channel.owner_telegram_id != owner) {
+        return Err(ApiError::WebhookForbidden);
+    }
+    Ok(endpoint)
+}
@@ -0,0 +1,139 @@
+//! Aggregates behind the owner dashboard, one query per block of metrics.
+
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use sea_orm::{ConnectionTrait, DbBackend, DbErr, FromQueryResult, Statement};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Granularity {
+    #[default]
+    Day,
+    Month,
+}
+
+impl Granularity {
+    fn as_str(self) -> &'static str {
+        match self {
+            Granularity::Day => "day",
+            Granularity::Month => "month",
+        }
+    }
+}
+
+/// Subscriber counts of a channel. `active` is a snapshot at the time of the request,
+/// the rest are counted within the requested period.
+#[derive(Debug, FromQueryResult, Serialize, ToSchema)]
+pub struct SubscriberMetrics {
+    /// Users with a subscription running right now.
+    pub active: i64,
+    /// First subscriptions of users to the channel.
+    pub new: i64,
+    /// Subscriptions of users who had subscribed to the channel before.
+    pub renewing: i64,
+    /// Users whose last subscription ended and wasn't renewed since.
+    pub churned: i64,
+}
+
+/// Completed payments of one period and currency.
+#[derive(Debug, FromQueryResult, Serialize, ToSchema)]
+pub struct RevenueBucket {
+    /// Start of the day or month, UTC.
+    pub period: DateTime<Utc>,
+    pub currency: String,
+    pub payments: i64,
+    /// What subscribers paid, platform fee included.
+    pub gross: Decimal,
+    /// The owner's part of `gross`.
+    pub owner_amount: Decimal,
+}
+
+/// Payment links that are still open.
+#[derive(Debug, FromQueryResult, Serialize, ToSchema)]
+pub struct PendingPayments {
+    pub currency: String,
+    pub count: i64,
+    pub amount: Decimal,
+}
+
+// Подписки пользователя на канал упорядочены по времени покупки: у первой нет предыдущей,
+// у последней нет следующей. Истёкшие подписки тоже считаются, иначе не будет оттока
+const SUBSCRIBERS_SQL: &str = "WITH subs AS ( \
+         SELECT telegram_id, time_from, time_to, created_at, \
+                LAG(created_at) OVER w AS previous_created, \
+                LEAD(created_at) OVER w AS next_created \
+         FROM subscriptions \
+         WHERE channel_id = $1 AND status IN ('active', 'expired') \
+         WINDOW w AS (PARTITION BY telegram_id ORDER BY created_at) \
+     ) \
+     SELECT \
+         COUNT(DISTINCT telegram_id) FILTER (WHERE time_from <= now() AND time_to > now()) AS active, \
+         COUNT(*) FILTER (WHERE created_at >= $2 AND created_at < $3 AND previous_created IS NULL) AS new, \
+         COUNT(*) FILTER (WHERE created_at >= $2 AND created_at < $3 AND previous_created IS NOT NULL) AS renewing, \
+         COUNT(*) FILTER (WHERE time_to >= $2 AND time_to < LEAST($3, now()) AND next_created IS NULL) AS churned \
+     FROM subs";
+
+const REVENUE_SQL: &str = "SELECT date_trunc($2, completed_at, 'UTC') AS period, currency, \
+         COUNT(*) AS payments, SUM(price) AS gross, SUM(owner_amount) AS owner_amount \
+     FROM payment_transactions \
+     WHERE channel_id = $1 AND status = 'completed' AND completed_at >= $3 AND completed_at < $4 \
+     GROUP BY 1, 2 \
+     ORDER BY 1, 2";
+
+const PENDING_SQL: &str = "SELECT currency, COUNT(*) AS count, SUM(price) AS amount \
+     FROM payment_transactions \
+     WHERE channel_id = $1 AND status = 'active' AND (expires_at IS NULL OR expires_at > now()) \
+     GROUP BY currency \
+     ORDER BY currency";
+
+pub async fn subscribers<C: ConnectionTrait>(
+    db: &C,
+    channel_id: i64,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Result<SubscriberMetrics, DbErr> {
+    SubscriberMetrics::find_by_statement(Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        SUBSCRIBERS_SQL,
+        [channel_id.into(), from.into(), to.into()],
+    ))
+    .one(db)
+    .await?
+    .ok_or_else(|| DbErr::RecordNotFound("subscriber metrics".to_string()))
+}
+
+pub async fn revenue<C: ConnectionTrait>(
+    db: &C,
+    channel_id: i64,
+    granularity: Granularity,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Result<Vec<RevenueBucket>, DbErr> {
+    RevenueBucket::find_by_statement(Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        REVENUE_SQL,
+        [
+            channel_id.into(),
+            granularity.as_str().into(),
+            from.into(),
+            to.into(),
+        ],
+    ))
+    .all(db)
+    .await
+}
+
+pub async fn pending<C: ConnectionTrait>(
+    db: &C,
+    channel_id: i64,
+) -> Result<Vec<PendingPayments>, DbErr> {
+    PendingPayments::find_by_statement(Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        PENDING_SQL,
+        [channel_id.into()],
+    ))
+    .all(db)
+    .await
+}
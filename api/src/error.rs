@@ -0,0 +1,209 @@
+use axum::{
+    Json,
+    extract::rejection::{JsonRejection, PathRejection, QueryRejection},
+    http::{HeaderValue, StatusCode, header::RETRY_AFTER},
+    response::{IntoResponse, Response},
+};
+use redis::RedisError;
+use sea_orm::DbErr;
+use serde::Serialize;
+use thiserror::Error;
+use utoipa::ToSchema;
+
+use crate::{response, ton_proof::ProofError};
+
+/// Every error the API returns. `code()` is part of the contract with clients and must
+/// not change; the message is for humans and may.
+#[derive(Debug, Error)]
+pub enum ApiError {
+    #[error("{0}")]
+    BadRequest(String),
+
+    #[error("Missing credentials")]
+    MissingCredentials,
+
+    #[error("Invalid or expired session")]
+    InvalidSession,
+
+    #[error("Invalid initData: {0}")]
+    InvalidInitData(&'static str),
+
+    #[error("Challenge not found or already used")]
+    ChallengeNotFound,
+
+    #[error("Proof timestamp is out of range")]
+    ProofExpired,
+
+    #[error("Proof is for another domain")]
+    InvalidDomain,
+
+    #[error("{0}")]
+    InvalidProof(#[from] ProofError),
+
+    #[error("Transaction not found")]
+    TransactionNotFound,
+
+    #[error("Transaction belongs to another user")]
+    TransactionForbidden,
+
+    #[error("Channel not found")]
+    ChannelNotFound,
+
+    #[error("Channel belongs to another user")]
+    ChannelForbidden,
+
+    #[error("Wallet isn't linked to a Telegram account")]
+    WalletNotLinked,
+
+    #[error("Link not found or expired")]
+    WalletLinkNotFound,
+
+    #[error("Wallet is linked to another account")]
+    WalletLinkedElsewhere,
+
+    #[error("Webhook not found")]
+    WebhookNotFound,
+
+    #[error("Webhook belongs to another user")]
+    WebhookForbidden,
+
+    #[error("Delivery not found")]
+    DeliveryNotFound,
+
+    #[error("Invalid or revoked API key")]
+    InvalidApiKey,
+
+    #[error("API key lacks the `{0}` scope")]
+    MissingScope(&'static str),
+
+    #[error("API key not found")]
+    ApiKeyNotFound,
+
+    #[error("Too many requests, retry in {0} s")]
+    RateLimited(u64),
+
+    #[error("Database error")]
+    Database(#[from] DbErr),
+
+    #[error("Redis error")]
+    Redis(#[from] RedisError),
+}
+
+impl ApiError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::MissingCredentials
+            | ApiError::InvalidSession
+            | ApiError::InvalidInitData(_)
+            | ApiError::ChallengeNotFound
+            | ApiError::ProofExpired
+            | ApiError::InvalidDomain
+            | ApiError::InvalidProof(_)
+            | ApiError::InvalidApiKey => StatusCode::UNAUTHORIZED,
+            ApiError::TransactionForbidden
+            | ApiError::ChannelForbidden
+            | ApiError::WalletNotLinked
+            | ApiError::WebhookForbidden
+            | ApiError::MissingScope(_) => StatusCode::FORBIDDEN,
+            ApiError::TransactionNotFound
+            | ApiError::ChannelNotFound
+            | ApiError::WalletLinkNotFound
+            | ApiError::WebhookNotFound
+            | ApiError::DeliveryNotFound
+            | ApiError::ApiKeyNotFound => StatusCode::NOT_FOUND,
+            ApiError::WalletLinkedElsewhere => StatusCode::CONFLICT,
+            ApiError::RateLimited(_) => StatusCode::TOO_MANY_REQUESTS,
+            ApiError::Database(_) | ApiError::Redis(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::BadRequest(_) => "bad_request",
+            ApiError::MissingCredentials => "missing_credentials",
+            ApiError::InvalidSession => "invalid_session",
+            ApiError::InvalidInitData(_) => "invalid_init_data",
+            ApiError::ChallengeNotFound => "challenge_not_found",
+            ApiError::ProofExpired => "proof_expired",
+            ApiError::InvalidDomain => "invalid_domain",
+            ApiError::InvalidProof(_) => "invalid_proof",
+            ApiError::TransactionNotFound => "transaction_not_found",
+            ApiError::TransactionForbidden => "transaction_forbidden",
+            ApiError::ChannelNotFound => "channel_not_found",
+            ApiError::ChannelForbidden => "channel_forbidden",
+            ApiError::WalletNotLinked => "wallet_not_linked",
+            ApiError::WalletLinkNotFound => "wallet_link_not_found",
+            ApiError::WalletLinkedElsewhere => "wallet_linked_elsewhere",
+            ApiError::WebhookNotFound => "webhook_not_found",
+            ApiError::WebhookForbidden => "webhook_forbidden",
+            ApiError::DeliveryNotFound => "delivery_not_found",
+            ApiError::InvalidApiKey => "invalid_api_key",
+            ApiError::MissingScope(_) => "missing_scope",
+            ApiError::ApiKeyNotFound => "api_key_not_found",
+            ApiError::RateLimited(_) => "rate_limited",
+            ApiError::Database(_) => "database_error",
+            ApiError::Redis(_) => "redis_error",
+        }
+    }
+}
+
+/// Body of every error response.
+#[derive(Serialize, ToSchema)]
+pub struct ErrorEnvelope {
+    #[schema(value_type = String, example = "error")]
+    status: &'static str,
+    error: ErrorBody,
+    request_id: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ErrorBody {
+    /// Stable machine-readable code, e.g. `transaction_not_found`.
+    #[schema(value_type = String)]
+    code: &'static str,
+    message: String,
+}
+
+impl From<JsonRejection> for ApiError {
+    fn from(rejection: JsonRejection) -> Self {
+        ApiError::BadRequest(rejection.body_text())
+    }
+}
+
+impl From<PathRejection> for ApiError {
+    fn from(rejection: PathRejection) -> Self {
+        ApiError::BadRequest(rejection.body_text())
+    }
+}
+
+impl From<QueryRejection> for ApiError {
+    fn from(rejection: QueryRejection) -> Self {
+        ApiError::BadRequest(rejection.body_text())
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let request_id = response::request_id();
+        // Подробности ошибок БД и Redis клиенту не отдаём, только в лог; request_id там из span запроса
+        if let ApiError::Database(err) = &self {
+            tracing::error!("❌ Ошибка БД: {}", err);
+        } else if let ApiError::Redis(err) = &self {
+            tracing::error!("❌ Ошибка Redis: {}", err);
+        }
+        let body = ErrorEnvelope {
+            status: "error",
+            error: ErrorBody {
+                code: self.code(),
+                message: self.to_string(),
+            },
+            request_id,
+        };
+        let mut response = (self.status(), Json(body)).into_response();
+        if let ApiError::RateLimited(retry_after) = self {
+            response.headers_mut().insert(RETRY_AFTER, HeaderValue::from(retry_after));
+        }
+        response
+    }
+}
@@ -1,130 +1,195 @@
-use std::{collections::HashMap, env, sync::Arc};
+mod api_key;
+mod auth;
+mod dashboard;
+mod error;
+mod openapi;
+mod rate_limit;
+mod response;
+mod session;
+mod status_stream;
+mod telegram_auth;
+mod ton_proof;
+
+use std::{net::SocketAddr, sync::Arc};
 
 use axum::{
-    Json, Router,
-    extract::{Path, Query, State},
-    http::{HeaderValue, StatusCode},
-    response::IntoResponse,
-    routing::{get, post},
-    debug_handler
+    Router,
+    extract::State,
+    http::HeaderMap,
+    middleware,
+    response::sse::{Event, KeepAlive, Sse},
+    routing::{delete, get, post},
 };
-use axum_extra::headers::Authorization;
-use axum_extra::{TypedHeader, headers::authorization::Credentials};
-use chrono::{Utc, Duration};
-use db::{Transaction, TransactionModel};
-use dotenv;
-use events;
-use base64::{engine::{general_purpose}, Engine};
-use rand::{self, Rng };
+use auth::Requester;
+use chrono::{DateTime, Duration, Utc};
+use db::{Channel, Transaction, UserModel, UserWallet, UserWalletModel, channel, user, user_wallet};
 use redis::{Client, aio::MultiplexedConnection, AsyncCommands};
-use sea_orm::{ActiveModelTrait, Database, DatabaseConnection, EntityTrait, Iden};
-use serde_json::{Value, json};
-use ed25519_dalek::{Signature, Verifier, VerifyingKey};
-use tokio;
-
-#[derive(Debug, Clone)]
-struct Wallet(pub String);
-
-impl Credentials for Wallet {
-    const SCHEME: &'static str = "Wallet";
-
-    fn decode(value: &axum::http::HeaderValue) -> Option<Self> {
-        let str_val = value.to_str().ok()?;
-        let prefix = format!("{} ", Self::SCHEME);
-        if str_val.starts_with(&prefix) {
-            Some(Wallet(str_val[prefix.len()..].to_string()))
-        } else {
-            None
-        }
-    }
-
-    fn encode(&self) -> axum::http::HeaderValue {
-        let encoded = format!("{} {}", Self::SCHEME, self.0);
-        HeaderValue::from_str(&encoded).unwrap()
-    }
-}
-#[derive(serde::Serialize)]
-struct ErrorResponse {
-    error: String,
-}
-
-#[derive(serde::Serialize)]
-struct DataResponse {
-    status: String,
-    data: serde_json::Value,
-}
+use sea_orm::{
+    ActiveModelTrait, ActiveValue::Set, ColumnTrait, Condition, ConnectionTrait, Database,
+    DatabaseConnection, DbErr, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder, QuerySelect,
+    TransactionTrait,
+    prelude::Uuid,
+    sea_query::{Expr, LikeExpr, OnConflict, extension::postgres::PgExpr},
+};
+use error::{ApiError, ErrorEnvelope};
+use events::payment_status::{PaymentStatusEvent, last_payment_status_id};
+use futures_util::{Stream, StreamExt};
+use response::{Body, Data, Envelope, Param, QueryParams};
+use rust_decimal::Decimal;
+use session::Session;
+use ton_proof::{ProofError, TonAddress};
+use utoipa::{IntoParams, ToSchema};
 
-impl Default for DataResponse {
-    fn default() -> Self {
-        Self {
-            status: "ok".to_string(),
-            data: serde_json::Value::Null,
-        }
-    }
-}
 #[derive(Clone)]
 struct AppState {
     redis: MultiplexedConnection,
+    /// For connections that block, like the ones behind payment status streams.
+    redis_client: Client,
     db: DatabaseConnection,
+    /// Key for Mini App initData signatures.
+    bot_token: Arc<String>,
+    /// Payment page, invoice links are `{payment_gateway}/{public_id}`.
+    payment_gateway: Arc<String>,
+    /// Krypton gate address every payment goes to.
+    gate_crypto_address: Arc<String>,
+    /// Toncenter key, for wallets that log in without a StateInit.
+    ton_api_key: Arc<String>,
+    /// Domain TON Proof signatures must be made for.
+    domain: Arc<String>,
+    rate_limits: Arc<rate_limit::RateLimits>,
+    /// Ends payment status streams, so the server can stop without waiting for them.
+    shutdown: ops::Shutdown,
+}
+
+struct ApiConfig {
+    port: u16,
+    /// Domain of the frontend, TON Proof signatures are made for it.
+    domain: String,
+    database: config::DatabaseConfig,
+    redis: config::RedisConfig,
+    telegram: config::TelegramConfig,
+    payment_page: config::PaymentPageConfig,
+    ton: config::TonConfig,
+    rate_limits: rate_limit::RateLimits,
+    log: config::LogConfig,
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    dotenv::dotenv().ok();
-    let port = env::var("API_PORT").expect("PORT must be set");
-    let connection_string = env::var("API_DATABASE_URL").expect("DATABASE_URL must be set");
-    let dragonfly_password =
-        env::var("API_DRAGONFLY_PASSWORD").expect("API_DRAGONFLY_PASSWORD must be set");
-    let redis_host = env::var("API_HOST_URL").expect("API_HOST_URL must be set");
-    let redis_url = format!("redis://:{}@{}:6379", dragonfly_password, redis_host);
-    let redis_client = Client::open(redis_url)?;
+    let settings = config::load_or_exit("api", |r| ApiConfig {
+        port: r.required("port"),
+        domain: r.required("domain"),
+        database: config::DatabaseConfig::read(r),
+        redis: config::RedisConfig::read(r),
+        telegram: config::TelegramConfig::read(r),
+        payment_page: config::PaymentPageConfig::read(r),
+        ton: config::TonConfig::read(r),
+        rate_limits: rate_limit::RateLimits::read(r),
+        log: config::LogConfig::read(r),
+    });
+    ops::logging::init(settings.log.json);
+    let redis_client = Client::open(settings.redis.url())?;
     let redis_connection = redis_client.get_multiplexed_async_connection().await?;
-    let db = Database::connect(connection_string).await?;
+    let db = Database::connect(settings.database.url).await?;
+    if settings.database.migrate {
+        tracing::info!("Applying pending migrations");
+        migration::up_on_boot(&db).await?;
+    }
+    let shutdown = ops::Shutdown::listen();
+    ops::metrics::install();
+    let health = ops::Health::new(
+        vec![ops::Check::Database(db.clone()), ops::Check::Redis(redis_connection.clone())],
+        shutdown.clone(),
+    );
 
     let state = AppState {
         redis: redis_connection,
+        redis_client,
         db,
+        bot_token: Arc::new(settings.telegram.bot_token),
+        payment_gateway: Arc::new(settings.payment_page.gateway_url),
+        gate_crypto_address: Arc::new(settings.ton.gate_address),
+        ton_api_key: Arc::new(settings.ton.api_key),
+        domain: Arc::new(settings.domain),
+        rate_limits: Arc::new(settings.rate_limits),
+        shutdown: shutdown.clone(),
     };
 
     let app = Router::new()
-        .route("/api/payment/{id}", get(get_transaction))
-        .route("/api/payment/{id}/start", post(start_payment))
+        .route("/api/payment/{public_id}", get(get_transaction))
+        .route("/api/payment/{public_id}/start", post(start_payment))
+        .route("/api/payment/{public_id}/events", get(payment_events))
         .route("/api/auth/challenge", get(get_challenge))
         .route("/api/auth/tonproof", post(verify_proof))
-        .with_state(state.clone());
+        .route("/api/wallet/link", post(link_wallet))
+        .route("/api/channels", get(list_channels))
+        .route("/api/channels/{channel}", get(get_channel))
+        .route("/api/owner/channels", get(list_owned_channels))
+        .route("/api/owner/channels/{channel_id}/metrics", get(get_channel_metrics))
+        .route(
+            "/api/owner/channels/{channel_id}/webhooks",
+            get(list_webhooks).post(create_webhook),
+        )
+        .route("/api/owner/webhooks/{webhook_id}", delete(delete_webhook))
+        .route("/api/owner/webhooks/{webhook_id}/deliveries", get(list_webhook_deliveries))
+        .route(
+            "/api/owner/webhooks/{webhook_id}/deliveries/{delivery_id}/replay",
+            post(replay_webhook_delivery),
+        )
+        .route("/api/owner/api-keys", get(list_api_keys).post(create_api_key))
+        .route("/api/owner/api-keys/{api_key_id}", delete(revoke_api_key))
+        .route("/api/merchant/invoices", post(create_invoice))
+        .route("/api/merchant/invoices/{public_id}", get(get_invoice))
+        .route("/api/openapi.json", get(openapi::openapi_json))
+        .layer(middleware::from_fn_with_state(state.clone(), rate_limit::layer))
+        .layer(middleware::from_fn(response::request_id_layer))
+        .layer(middleware::from_fn(ops::metrics::http_layer))
+        .with_state(state.clone())
+        .merge(ops::health::router(health));
 
-    let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{}", port))
+    let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{}", settings.port))
         .await
         .unwrap();
 
-    axum::serve(listener, app).await?;
+    // Адрес клиента нужен для ограничения запросов по IP
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
+        .with_graceful_shutdown(shutdown.wait())
+        .await?;
     Ok(())
 }
+
+/// What the payment page shows before the wallet is connected.
+#[derive(serde::Serialize, ToSchema)]
+struct TransactionView {
+    id: Uuid,
+    price: Decimal,
+    created_at: DateTime<Utc>,
+    /// active, failed, completed, expired or cancelled
+    status: String,
+    currency: String,
+    /// What the payment is for, set on merchant invoices.
+    description: Option<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/payment/{public_id}",
+    tag = "payments",
+    params(("public_id" = Uuid, Path, description = "Public id from the bot's payment link")),
+    security(("init_data" = []), ("session" = [])),
+    responses(
+        (status = 200, body = Envelope<TransactionView>),
+        (status = 401, body = ErrorEnvelope),
+        (status = 403, body = ErrorEnvelope),
+        (status = 404, body = ErrorEnvelope),
+    )
+)]
 async fn get_transaction(
     State(state): State<AppState>,
-    Path(id): Path<i64>,
-) -> Result<impl IntoResponse, impl IntoResponse> {
-    let transaction = Transaction::find_by_id(id)
-        .one(&state.db)
-        .await
-        .map_err(|_| {
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorResponse {
-                    error: "DB error".to_string(),
-                }),
-            )
-        })?;
-
-    // Если есть — unwrap или else
-    let Some(tx) = transaction else {
-        return Err((
-            StatusCode::NOT_FOUND,
-            Json(ErrorResponse {
-                error: "Transaction not found".to_string(),
-            }),
-        ));
-    };
+    Param(public_id): Param<Uuid>,
+    requester: Requester,
+) -> Result<Data<TransactionView>, ApiError> {
+    let tx = auth::find_owned_transaction(&state.db, public_id, &requester).await?;
 
     /*
     let price = tx.price;
@@ -145,44 +210,68 @@ async fn get_transaction(
         .unwrap();
     let img = InputFile::memory(png_bytes).file_name("payment_qr.png");
     */
-    Ok(Json(DataResponse {
-        data: json!({
-            "id": tx.id,
-            "price": tx.price,
-            "created_at": tx.created_at,
-            "status": tx.status,
-            "currency": tx.currency
-        }),
-        ..Default::default()
+    Ok(Data(TransactionView {
+        id: tx.public_id,
+        price: tx.price,
+        created_at: tx.created_at,
+        status: tx.status,
+        currency: tx.currency,
+        description: tx.description,
     }))
 }
 
+/// Everything the wallet needs to send the payment.
+#[derive(serde::Serialize, ToSchema)]
+struct PaymentView {
+    id: Uuid,
+    price: Decimal,
+    created_at: DateTime<Utc>,
+    status: String,
+    currency: String,
+    description: Option<String>,
+    /// Krypton gate address the payment goes to.
+    wallet_address: String,
+    platform_fee: Decimal,
+    owner_amount: Decimal,
+    /// Wallet of the TON Proof session, if the request was made with one.
+    payer_address: Option<String>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/payment/{public_id}/start",
+    tag = "payments",
+    params(("public_id" = Uuid, Path, description = "Public id from the bot's payment link")),
+    security(("init_data" = []), ("session" = [])),
+    responses(
+        (status = 200, body = Envelope<PaymentView>),
+        (status = 401, body = ErrorEnvelope),
+        (status = 403, body = ErrorEnvelope),
+        (status = 404, body = ErrorEnvelope),
+    )
+)]
 async fn start_payment(
     State(state): State<AppState>,
-    Path(id): Path<i64>,
-    TypedHeader(Authorization(wallet)): TypedHeader<Authorization<Wallet>>,
-) -> Result<impl IntoResponse, impl IntoResponse> {
-    let transaction = Transaction::find_by_id(id)
-        .one(&state.db)
-        .await
-        .map_err(|_| {
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorResponse {
-                    error: "DB error".to_string(),
-                }),
-            )
-        })?;
-
-    // Если есть — unwrap или else
-    let Some(tx) = transaction else {
-        return Err((
-            StatusCode::NOT_FOUND,
-            Json(ErrorResponse {
-                error: "Transaction not found".to_string(),
-            }),
-        ));
-    };
+    Param(public_id): Param<Uuid>,
+    requester: Requester,
+) -> Result<Data<PaymentView>, ApiError> {
+    let tx = auth::find_owned_transaction(&state.db, public_id, &requester).await?;
+
+    // Ставим транзакцию в очередь ton-watcher, он подтвердит платёж и проведёт его в ledger.
+    // Повторное открытие страницы второй раз её не ставит
+    if tx.status == "active" {
+        let event = events::event::PaymentEvent {
+            transaction_id: tx.id,
+            telegram_id: tx.telegram_id,
+            channel_id: tx.channel_id,
+            chat_id: tx.chat_id,
+            price: tx.price,
+            wallet_address: tx.wallet_address.clone(),
+            correlation_id: Some(tx.correlation_id.to_string()),
+        };
+        let mut redis = state.redis.clone();
+        events::event::send_payment_event(&event, &mut redis).await?;
+    }
 
     /*
     let price = tx.price;
@@ -203,202 +292,1241 @@ async fn start_payment(
         .unwrap();
     let img = InputFile::memory(png_bytes).file_name("payment_qr.png");
     */
-    Ok(Json(DataResponse {
-        data: json!({
-            "id": tx.id,
-            "price": tx.price,
-            "created_at": tx.created_at,
-            "status": tx.status,
-            "currency": tx.currency
-        }),
-        ..Default::default()
+    Ok(Data(PaymentView {
+        id: tx.public_id,
+        price: tx.price,
+        created_at: tx.created_at,
+        status: tx.status,
+        currency: tx.currency,
+        description: tx.description,
+        // платёж идёт на адрес Krypton, владелец получает owner_amount при выплате
+        wallet_address: tx.wallet_address,
+        platform_fee: tx.platform_fee,
+        owner_amount: tx.owner_amount,
+        payer_address: requester.wallet_address().map(str::to_string),
     }))
 }
 
-#[derive(serde::Serialize)]
+/// Streams status changes of the transaction as server-sent `status` events. Browsers'
+/// EventSource can't send the Authorization header, so the page reads it with fetch.
+#[utoipa::path(
+    get,
+    path = "/api/payment/{public_id}/events",
+    tag = "payments",
+    params(
+        ("public_id" = Uuid, Path, description = "Public id from the bot's payment link"),
+        ("Last-Event-ID" = Option<String>, Header, description = "Id of the last event received before reconnecting"),
+    ),
+    security(("init_data" = []), ("session" = [])),
+    responses(
+        (
+            status = 200,
+            content_type = "text/event-stream",
+            body = String,
+            description = "`status` events with JSON data `{status, verdict, terminal}`; the stream ends after a terminal one"
+        ),
+        (status = 401, body = ErrorEnvelope),
+        (status = 403, body = ErrorEnvelope),
+        (status = 404, body = ErrorEnvelope),
+    )
+)]
+async fn payment_events(
+    State(state): State<AppState>,
+    Param(public_id): Param<Uuid>,
+    headers: HeaderMap,
+    requester: Requester,
+) -> Result<Sse<impl Stream<Item = Result<Event, ApiError>>>, ApiError> {
+    let tx = auth::find_owned_transaction(&state.db, public_id, &requester).await?;
+    let mut redis = state.redis_client.get_multiplexed_async_connection().await?;
+    let last_event_id = headers
+        .get("last-event-id")
+        .and_then(|value| value.to_str().ok())
+        .filter(|id| status_stream::is_event_id(id));
+    let after = match last_event_id {
+        Some(id) => id.to_string(),
+        None => last_payment_status_id(&mut redis, tx.id).await?,
+    };
+    // Статус читаем после id последнего события: всё, что опубликуют позже, придёт из потока
+    let status = Transaction::find_by_id(tx.id)
+        .one(&state.db)
+        .await?
+        .ok_or(ApiError::TransactionNotFound)?
+        .status;
+    let snapshot = PaymentStatusEvent {
+        terminal: status != "active",
+        status,
+        verdict: None,
+    };
+    // Клиент переподключится с Last-Event-ID к другому экземпляру
+    let stream = status_stream::status_stream(redis, tx.id, after, snapshot)
+        .take_until(state.shutdown.wait());
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+#[derive(serde::Serialize, ToSchema)]
 pub struct TonProofChallenge {
     domain: String,
     timestamp: i64,
     payload: String,
 }
 
+#[utoipa::path(
+    get,
+    path = "/api/auth/challenge",
+    tag = "auth",
+    params(
+        ("Wallet" = Option<String>, Header, description = "Address of the wallet about to sign in, limits challenges per wallet"),
+    ),
+    responses(
+        (status = 200, body = Envelope<TonProofChallenge>),
+        (status = 400, body = ErrorEnvelope),
+        (status = 429, body = ErrorEnvelope, headers(("Retry-After" = u64, description = "Seconds until a retry can succeed"))),
+    )
+)]
 #[axum::debug_handler]
 async fn get_challenge(
     State(state): State<AppState>,
-    TypedHeader(Authorization(wallet)): TypedHeader<Authorization<Wallet>>,
-) -> Result<impl IntoResponse, impl IntoResponse> {
+    headers: HeaderMap,
+) -> Result<Data<TonProofChallenge>, ApiError> {
     let mut redis  = state.redis;
+    // Адрес пока ничем не доказан и нужен только, чтобы один кошелёк не запрашивал nonce с разных IP без ограничений
+    if let Some(wallet) = headers.get("wallet").and_then(|value| value.to_str().ok()) {
+        rate_limit::check_wallet(&mut redis, "challenge_wallet", wallet, state.rate_limits.challenge_wallet).await?;
+    }
     let nonce: u64 = rand::random::<u64>();
     let payload = format!("nonce_{}", nonce);
 
-    // пример хранения в Redis
-    let key = format!("ton_proof:{}", wallet.0);
-
-    match redis.set_ex::<_, _, ()>(key, payload.clone(), 300).await {
-        Ok(_) => (),
-        Err(_) => {
-            return Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorResponse {
-                    error: "Redis error".to_string(),
-                }),
-            ));
-        }
-    }
+    // Кошелёк ещё не доказан, поэтому nonce хранится по самому payload, а не по адресу из заголовка
+    let key = format!("ton_proof:{}", payload);
 
-    Ok(Json(DataResponse {
-        data: json!(TonProofChallenge {
-            domain: "krypton.com".to_string(),
-            timestamp: Utc::now().timestamp(),
-            payload,
-        }),
-        ..Default::default()
+    redis.set_ex::<_, _, ()>(key, payload.clone(), 300).await?;
+
+    Ok(Data(TonProofChallenge {
+        domain: state.domain.to_string(),
+        timestamp: Utc::now().timestamp(),
+        payload,
     }))
 }
 
-#[derive(serde::Deserialize)]
+/// Body of a TON Connect `ton_proof` reply, as forwarded by the frontend.
+#[derive(serde::Deserialize, ToSchema)]
 struct TonProofRequest {
+    /// Raw wallet address, `0:<hex>`.
     address: String,
+    /// Public key the wallet reported, checked against the one from the wallet contract.
+    public_key: Option<String>,
     proof: ProofPayload,
 }
 
-#[derive(serde::Deserialize)]
+#[derive(serde::Deserialize, ToSchema)]
 struct ProofPayload {
     timestamp: i64,
-    domain: String,
+    domain: ProofDomain,
     payload: String,
     signature: String,
+    /// Base64 StateInit BOC of the wallet, `walletStateInit` in TON Connect.
+    state_init: Option<String>,
 }
 
-#[derive(serde::Serialize)]
+#[derive(serde::Deserialize, ToSchema)]
+struct ProofDomain {
+    #[serde(alias = "lengthBytes")]
+    length_bytes: u32,
+    value: String,
+}
+
+#[derive(serde::Serialize, ToSchema)]
 pub struct AuthResponse {
     valid: bool,
     address: Option<String>,
+    token: String,
+    expires_in: u64,
 }
 
+#[utoipa::path(
+    post,
+    path = "/api/auth/tonproof",
+    tag = "auth",
+    request_body = TonProofRequest,
+    responses(
+        (status = 200, body = Envelope<AuthResponse>),
+        (status = 400, body = ErrorEnvelope),
+        (status = 401, body = ErrorEnvelope),
+        (status = 429, body = ErrorEnvelope, headers(("Retry-After" = u64, description = "Seconds until a retry can succeed"))),
+    )
+)]
 async fn verify_proof(
     State(state): State<AppState>,
-    TypedHeader(Authorization(wallet)): TypedHeader<Authorization<Wallet>>,
-    Json(req): Json<TonProofRequest>,
-) -> Result<impl IntoResponse, impl IntoResponse> {
-    if wallet.0 != req.address {
-        return Err((
-            StatusCode::UNAUTHORIZED,
-            Json(ErrorResponse {
-                error: "Wallet header mismatch".to_string()
-            }),
-        ));
-    }
+    Body(req): Body<TonProofRequest>,
+) -> Result<Data<AuthResponse>, ApiError> {
     let now = Utc::now().timestamp();
     let mut redis = state.redis;
-    let key = format!("ton_proof:{}", wallet.0);
-    let stored_payload: Option<String> = redis.get(&key).await.ok();
-
-    let Some(payload) = stored_payload else {
-        return Err((
-            StatusCode::UNAUTHORIZED,
-            Json(ErrorResponse {
-                error: "Key not found".to_string(),
-            }),
-        ));
-    };
-
-    if payload != req.proof.payload {
-        return Err((
-            StatusCode::UNAUTHORIZED,
-            Json(ErrorResponse {
-                error: "Invalid payload".to_string()
-            }),
-        ));
+    // Подбор подписи для одного кошелька с разных IP упирается в этот лимит
+    rate_limit::check_wallet(&mut redis, "tonproof_wallet", &req.address, state.rate_limits.tonproof_wallet).await?;
+    // nonce одноразовый: удаляем сразу, даже если проверка дальше не пройдёт
+    let key = format!("ton_proof:{}", req.proof.payload);
+    let stored_payload: Option<String> = redis.get_del(&key).await?;
+    if stored_payload.as_deref() != Some(req.proof.payload.as_str()) {
+        return Err(ApiError::ChallengeNotFound);
     }
 
+    let address = check_proof(&req, &state.domain, &state.ton_api_key, now).await?;
+
+    let token = session::create(&mut redis, &address.to_string()).await?;
+    Ok(Data(AuthResponse {
+        valid: true,
+        address: Some(address.to_string()),
+        token,
+        expires_in: session::SESSION_TTL_SECONDS,
+    }))
+}
+
+/// Everything of a `ton_proof` but the one-time nonce: the proof is fresh, made for our
+/// domain, and signed by the key of the wallet it claims. Returns the proven address.
+async fn check_proof(
+    req: &TonProofRequest,
+    domain: &str,
+    ton_api_key: &str,
+    now: i64,
+) -> Result<TonAddress, ApiError> {
     if (req.proof.timestamp - now).abs() > Duration::minutes(5).num_seconds() {
-        return Err((
-            StatusCode::UNAUTHORIZED,
-            Json(ErrorResponse {
-                error: "Too late".to_string()
-            }),
-        ));
+        return Err(ApiError::ProofExpired);
     }
 
-    // Проверка домена (опционально)
-    let domain = env::var("API_DOMAIN").expect("API_DOMAIN should be set");
-    if req.proof.domain != domain {
-        return Err((
-            StatusCode::UNAUTHORIZED,
-            Json(ErrorResponse {
-                error: "Invalid domain".to_string()
-            }),
-        ));
+    if req.proof.domain.value != domain
+        || req.proof.domain.length_bytes as usize != req.proof.domain.value.len()
+    {
+        return Err(ApiError::InvalidDomain);
     }
 
-    // Верификация подписи
-    let signature_bytes = match general_purpose::STANDARD.decode(&req.proof.signature) {
-        Ok(bytes) => bytes,
-        Err(_) => return Err((
-            StatusCode::UNAUTHORIZED,
-            Json(ErrorResponse {
-                error: "Invalid signature size".to_string()
-            }),
-        ))
-    };
+    let address: TonAddress = req.address.parse()?;
+    // Ключ берём из StateInit (он же доказывает адрес), а у задеплоенного кошелька без StateInit — из get_public_key
+    let public_key = match &req.proof.state_init {
+        Some(state_init) => ton_proof::public_key_from_state_init(state_init, &address),
+        None => ton_proof::fetch_public_key(ton_api_key, &address).await,
+    }?;
+    if let Some(claimed) = &req.public_key
+        && !claimed.eq_ignore_ascii_case(&hex::encode(public_key))
+    {
+        return Err(ProofError::PublicKeyMismatch.into());
+    }
 
-    let signature_array: [u8; 64] = match signature_bytes.as_slice().try_into() {
-        Ok(arr) => arr,
-        Err(_) => return Err((
-            StatusCode::UNAUTHORIZED,
-            Json(ErrorResponse {
-                error: "Invalid signature_array".to_string()
-            }),
-        )),
-    };
+    ton_proof::verify_signature(
+        &public_key,
+        &address,
+        &req.proof.domain.value,
+        req.proof.timestamp as u64,
+        &req.proof.payload,
+        &req.proof.signature,
+    )?;
 
-    let sig = ed25519_dalek::Signature::from_bytes(&signature_array);
+    Ok(address)
+}
 
-    let pk_bytes = match general_purpose::STANDARD.decode(&req.address) {
-        Ok(bytes) => bytes,
-        Err(_) =>         return Err((
-            StatusCode::UNAUTHORIZED,
-            Json(ErrorResponse {
-                error: "Invalid pk bytes".to_string()
-            }),
-        ))
-    };
+#[derive(serde::Deserialize, ToSchema)]
+struct LinkWalletRequest {
+    /// One-time token from the bot's /linkwallet link.
+    token: String,
+}
 
-    let pk_array: [u8; 32] = match pk_bytes.as_slice().try_into() {
-        Ok(arr) => arr,
-        Err(error) => return Err((
-            StatusCode::UNAUTHORIZED,
-            Json(ErrorResponse {
-                error: error.to_string()
-            }),
-        ))
-    };
+#[derive(serde::Serialize, ToSchema)]
+struct LinkedWallet {
+    telegram_id: i64,
+    address: String,
+    verified_at: DateTime<Utc>,
+}
+
+/// Binds the wallet proven by the session to the Telegram account that requested the link.
+#[utoipa::path(
+    post,
+    path = "/api/wallet/link",
+    tag = "wallets",
+    request_body = LinkWalletRequest,
+    security(("session" = [])),
+    responses(
+        (status = 200, body = Envelope<LinkedWallet>),
+        (status = 400, body = ErrorEnvelope),
+        (status = 401, body = ErrorEnvelope),
+        (status = 404, body = ErrorEnvelope),
+        (status = 409, body = ErrorEnvelope),
+    )
+)]
+async fn link_wallet(
+    State(state): State<AppState>,
+    session: Session,
+    Body(req): Body<LinkWalletRequest>,
+) -> Result<Data<LinkedWallet>, ApiError> {
+    let mut redis = state.redis;
+    let telegram_id = events::wallet_link::take_wallet_link(&mut redis, &req.token)
+        .await?
+        .ok_or(ApiError::WalletLinkNotFound)?;
+
+    let now = Utc::now();
+    let txn = state.db.begin().await?;
+    ensure_user(&txn, telegram_id, now).await?;
+    // Чужую привязку проверяет сам ON CONFLICT: обновляется только своя строка, иначе ни одной
+    let saved = UserWallet::insert(UserWalletModel {
+        telegram_id: Set(telegram_id),
+        address: Set(session.address.clone()),
+        verified_at: Set(now),
+        created_at: Set(now),
+        ..Default::default()
+    })
+    .on_conflict(
+        OnConflict::column(user_wallet::Column::Address)
+            .update_column(user_wallet::Column::VerifiedAt)
+            .action_and_where(user_wallet::Column::TelegramId.eq(telegram_id))
+            .to_owned(),
+    )
+    .exec_without_returning(&txn)
+    .await?;
+    if saved == 0 {
+        return Err(ApiError::WalletLinkedElsewhere);
+    }
+    txn.commit().await?;
+
+    Ok(Data(LinkedWallet {
+        telegram_id,
+        address: session.address,
+        verified_at: now,
+    }))
+}
+
+/// Creates the users row for `telegram_id` unless it exists: a Mini App user may not have
+/// written to the bot yet.
+async fn ensure_user<C: ConnectionTrait>(db: &C, telegram_id: i64, now: DateTime<Utc>) -> Result<(), DbErr> {
+    user::Entity::insert(UserModel {
+        telegram_id: Set(telegram_id),
+        username: Set(String::new()),
+        created_at: Set(now),
+        last_active_at: Set(now),
+        ..Default::default()
+    })
+    .on_conflict(OnConflict::column(user::Column::TelegramId).do_nothing().to_owned())
+    .do_nothing()
+    .exec(db)
+    .await?;
+    Ok(())
+}
+
+const CATALOG_PAGE_SIZE: u64 = 20;
+const CATALOG_MAX_PAGE_SIZE: u64 = 100;
+/// Far beyond any real catalog, keeps `(page - 1) * per_page` well inside the offset range.
+const CATALOG_MAX_PAGE: u64 = 10_000;
+
+#[derive(serde::Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct CatalogQuery {
+    /// Case-insensitive part of the title or slug.
+    search: Option<String>,
+    /// Page number, from 1 to 10000.
+    page: Option<u64>,
+    /// Channels per page, 20 by default, from 1 to 100.
+    per_page: Option<u64>,
+}
+
+/// What a subscription buys.
+#[derive(serde::Serialize, ToSchema)]
+struct Plan {
+    name: String,
+    period_days: i64,
+    price: Decimal,
+    currency: String,
+}
 
-    let public_key = match VerifyingKey::from_bytes(&pk_array) {
-        Ok(pk) => pk,
-        Err(_) => return Err((
-            StatusCode::UNAUTHORIZED,
-            Json(ErrorResponse {
-                error: "Invalid public key".to_string()
-            }),
-        ))
+/// Public profile of a channel that sells subscriptions.
+#[derive(serde::Serialize, ToSchema)]
+struct ChannelProfile {
+    id: i64,
+    slug: Option<String>,
+    title: String,
+    description: Option<String>,
+    currency: String,
+    plans: Vec<Plan>,
+}
+
+impl From<channel::Model> for ChannelProfile {
+    fn from(channel: channel::Model) -> Self {
+        Self {
+            id: channel.channel_id,
+            slug: channel.slug,
+            title: channel.title,
+            description: channel.description,
+            currency: channel::SUBSCRIPTION_CURRENCY.to_string(),
+            plans: channel
+                .monthly_price
+                .into_iter()
+                .map(|price| Plan {
+                    name: "monthly".to_string(),
+                    period_days: channel::SUBSCRIPTION_PERIOD_DAYS,
+                    price,
+                    currency: channel::SUBSCRIPTION_CURRENCY.to_string(),
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(serde::Serialize, ToSchema)]
+struct ChannelPage {
+    items: Vec<ChannelProfile>,
+    page: u64,
+    per_page: u64,
+    total: u64,
+}
+
+/// Channels that can be paid for: active, with a price and a payout address, the same
+/// checks the bot's /pay makes before creating a transaction.
+fn for_sale() -> Condition {
+    Condition::all()
+        .add(channel::Column::IsActive.eq(true))
+        .add(channel::Column::MonthlyPrice.is_not_null())
+        .add(channel::Column::CryptoAddress.is_not_null())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/channels",
+    tag = "channels",
+    params(CatalogQuery),
+    responses(
+        (status = 200, body = Envelope<ChannelPage>),
+        (status = 400, body = ErrorEnvelope),
+    )
+)]
+async fn list_channels(
+    State(state): State<AppState>,
+    QueryParams(query): QueryParams<CatalogQuery>,
+) -> Result<Data<ChannelPage>, ApiError> {
+    let page = query.page.unwrap_or(1);
+    if !(1..=CATALOG_MAX_PAGE).contains(&page) {
+        return Err(ApiError::BadRequest(format!("`page` must be from 1 to {}", CATALOG_MAX_PAGE)));
+    }
+    let per_page = query.per_page.unwrap_or(CATALOG_PAGE_SIZE);
+    if !(1..=CATALOG_MAX_PAGE_SIZE).contains(&per_page) {
+        return Err(ApiError::BadRequest(format!(
+            "`per_page` must be from 1 to {}",
+            CATALOG_MAX_PAGE_SIZE
+        )));
+    }
+
+    let mut select = Channel::find().filter(for_sale());
+    if let Some(search) = query.search.as_deref().map(str::trim).filter(|s| !s.is_empty()) {
+        // % и _ в запросе ищем буквально
+        let pattern = format!(
+            "%{}%",
+            search.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+        );
+        select = select.filter(
+            Condition::any()
+                .add(Expr::col(channel::Column::Title).ilike(LikeExpr::new(&pattern).escape('\\')))
+                .add(Expr::col(channel::Column::Slug).ilike(LikeExpr::new(&pattern).escape('\\'))),
+        );
+    }
+    let paginator = select
+        .order_by_asc(channel::Column::Title)
+        .order_by_asc(channel::Column::ChannelId)
+        .paginate(&state.db, per_page);
+    let total = paginator.num_items().await?;
+    let items = paginator.fetch_page(page - 1).await?;
+
+    Ok(Data(ChannelPage {
+        items: items.into_iter().map(ChannelProfile::from).collect(),
+        page,
+        per_page,
+        total,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/channels/{channel}",
+    tag = "channels",
+    params(("channel" = String, Path, description = "Channel id or slug")),
+    responses(
+        (status = 200, body = Envelope<ChannelProfile>),
+        (status = 404, body = ErrorEnvelope),
+    )
+)]
+async fn get_channel(
+    State(state): State<AppState>,
+    Param(key): Param<String>,
+) -> Result<Data<ChannelProfile>, ApiError> {
+    // Слаг всегда начинается с буквы, поэтому число — это id канала
+    let by_key = match key.parse::<i64>() {
+        Ok(channel_id) => channel::Column::ChannelId.eq(channel_id),
+        Err(_) => channel::Column::Slug.eq(key.to_lowercase()),
     };
+    let channel = Channel::find()
+        .filter(by_key)
+        .filter(for_sale())
+        .one(&state.db)
+        .await?
+        .ok_or(ApiError::ChannelNotFound)?;
+    Ok(Data(channel.into()))
+}
 
-    if public_key.verify(req.proof.payload.as_bytes(), &sig).is_ok() {
-        redis.del(&key).await.unwrap_or(());
-        Ok(Json(
-            DataResponse {
-                data: json!(AuthResponse { valid: true, address: Some(req.address) }),
-                ..Default::default()
-            }
-        ))
+/// A channel as its owner sees it.
+#[derive(serde::Serialize, ToSchema)]
+struct OwnedChannel {
+    id: i64,
+    slug: Option<String>,
+    title: String,
+    is_active: bool,
+    monthly_price: Option<Decimal>,
+    crypto_address: Option<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/owner/channels",
+    tag = "owner",
+    security(("init_data" = []), ("session" = [])),
+    responses(
+        (status = 200, body = Envelope<Vec<OwnedChannel>>),
+        (status = 401, body = ErrorEnvelope),
+        (status = 403, body = ErrorEnvelope),
+    )
+)]
+async fn list_owned_channels(
+    State(state): State<AppState>,
+    requester: Requester,
+) -> Result<Data<Vec<OwnedChannel>>, ApiError> {
+    let owner = requester.require_telegram_id()?;
+    let channels = Channel::find()
+        .filter(channel::Column::OwnerTelegramId.eq(owner))
+        .order_by_asc(channel::Column::Title)
+        .all(&state.db)
+        .await?;
+    Ok(Data(
+        channels
+            .into_iter()
+            .map(|channel| OwnedChannel {
+                id: channel.channel_id,
+                slug: channel.slug,
+                title: channel.title,
+                is_active: channel.is_active,
+                monthly_price: channel.monthly_price,
+                crypto_address: channel.crypto_address,
+            })
+            .collect(),
+    ))
+}
+
+/// Metrics are counted for the last 30 days unless `from` is given.
+const METRICS_DEFAULT_DAYS: i64 = 30;
+
+#[derive(serde::Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct MetricsQuery {
+    /// Start of the period, inclusive. 30 days before `to` by default.
+    from: Option<DateTime<Utc>>,
+    /// End of the period, exclusive. Now by default.
+    to: Option<DateTime<Utc>>,
+    /// Revenue bucket size, `day` by default.
+    granularity: Option<dashboard::Granularity>,
+}
+
+#[derive(serde::Serialize, ToSchema)]
+struct ChannelMetrics {
+    channel_id: i64,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+    granularity: dashboard::Granularity,
+    subscribers: dashboard::SubscriberMetrics,
+    revenue: Vec<dashboard::RevenueBucket>,
+    pending: Vec<dashboard::PendingPayments>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/owner/channels/{channel_id}/metrics",
+    tag = "owner",
+    params(("channel_id" = i64, Path), MetricsQuery),
+    security(("init_data" = []), ("session" = [])),
+    responses(
+        (status = 200, body = Envelope<ChannelMetrics>),
+        (status = 400, body = ErrorEnvelope),
+        (status = 401, body = ErrorEnvelope),
+        (status = 403, body = ErrorEnvelope),
+        (status = 404, body = ErrorEnvelope),
+    )
+)]
+async fn get_channel_metrics(
+    State(state): State<AppState>,
+    Param(channel_id): Param<i64>,
+    QueryParams(query): QueryParams<MetricsQuery>,
+    requester: Requester,
+) -> Result<Data<ChannelMetrics>, ApiError> {
+    let channel = auth::find_owned_channel(&state.db, channel_id, &requester).await?;
+    let to = query.to.unwrap_or_else(Utc::now);
+    let from = query.from.unwrap_or(to - Duration::days(METRICS_DEFAULT_DAYS));
+    if from >= to {
+        return Err(ApiError::BadRequest("`from` must be before `to`".to_string()));
+    }
+    let granularity = query.granularity.unwrap_or_default();
+
+    let subscribers = dashboard::subscribers(&state.db, channel.channel_id, from, to).await?;
+    let revenue = dashboard::revenue(&state.db, channel.channel_id, granularity, from, to).await?;
+    let pending = dashboard::pending(&state.db, channel.channel_id).await?;
+    Ok(Data(ChannelMetrics {
+        channel_id: channel.channel_id,
+        from,
+        to,
+        granularity,
+        subscribers,
+        revenue,
+        pending,
+    }))
+}
+
+/// Deliveries listed per endpoint, newest first.
+const DELIVERIES_LIMIT: u64 = 50;
+
+#[derive(serde::Deserialize, ToSchema)]
+struct CreateWebhookRequest {
+    /// Where events are POSTed to: https, on a public address. Redirects aren't followed.
+    url: String,
+    /// Event types to send, e.g. `payment.confirmed`. All of them when empty or missing.
+    #[serde(default)]
+    events: Vec<String>,
+}
+
+#[derive(serde::Serialize, ToSchema)]
+struct WebhookView {
+    id: i64,
+    channel_id: i64,
+    url: String,
+    /// Subscribed event types, empty means all of them.
+    events: Vec<String>,
+    is_active: bool,
+    created_at: DateTime<Utc>,
+}
+
+impl From<db::webhook_endpoint::Model> for WebhookView {
+    fn from(endpoint: db::webhook_endpoint::Model) -> Self {
+        WebhookView {
+            id: endpoint.id,
+            channel_id: endpoint.channel_id,
+            url: endpoint.url,
+            events: serde_json::from_value(endpoint.events).unwrap_or_default(),
+            is_active: endpoint.is_active,
+            created_at: endpoint.created_at,
+        }
+    }
+}
+
+/// A new endpoint with the key its payloads are signed with. The secret is shown only here.
+#[derive(serde::Serialize, ToSchema)]
+struct CreatedWebhook {
+    webhook: WebhookView,
+    /// HMAC-SHA256 key for the `X-Krypton-Signature` header.
+    secret: String,
+}
+
+#[derive(serde::Serialize, ToSchema)]
+struct WebhookDeliveryView {
+    id: i64,
+    /// Same for every attempt and replay of the event.
+    event_id: Uuid,
+    event_type: String,
+    /// pending, delivered or failed
+    status: String,
+    attempts: i32,
+    next_attempt_at: DateTime<Utc>,
+    /// HTTP status of the last attempt, if the endpoint answered.
+    response_status: Option<i32>,
+    error: Option<String>,
+    created_at: DateTime<Utc>,
+    delivered_at: Option<DateTime<Utc>>,
+}
+
+impl From<db::webhook_delivery::Model> for WebhookDeliveryView {
+    fn from(delivery: db::webhook_delivery::Model) -> Self {
+        WebhookDeliveryView {
+            id: delivery.id,
+            event_id: delivery.event_id,
+            event_type: delivery.event_type,
+            status: delivery.status,
+            attempts: delivery.attempts,
+            next_attempt_at: delivery.next_attempt_at,
+            response_status: delivery.response_status,
+            error: delivery.error,
+            created_at: delivery.created_at,
+            delivered_at: delivery.delivered_at,
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/owner/channels/{channel_id}/webhooks",
+    tag = "owner",
+    params(("channel_id" = i64, Path)),
+    request_body = CreateWebhookRequest,
+    security(("init_data" = []), ("session" = [])),
+    responses(
+        (status = 200, body = Envelope<CreatedWebhook>),
+        (status = 400, body = ErrorEnvelope),
+        (status = 401, body = ErrorEnvelope),
+        (status = 403, body = ErrorEnvelope),
+        (status = 404, body = ErrorEnvelope),
+    )
+)]
+async fn create_webhook(
+    State(state): State<AppState>,
+    Param(channel_id): Param<i64>,
+    requester: Requester,
+    Body(req): Body<CreateWebhookRequest>,
+) -> Result<Data<CreatedWebhook>, ApiError> {
+    let channel = auth::find_owned_channel(&state.db, channel_id, &requester).await?;
+    let url = webhooks::target::check(&req.url)
+        .await
+        .map_err(|error| ApiError::BadRequest(format!("`url` {}", error)))?;
+    if let Some(unknown) = req
+        .events
+        .iter()
+        .find(|event| webhooks::WebhookEvent::parse(event).is_none())
+    {
+        return Err(ApiError::BadRequest(format!("Unknown event type `{}`", unknown)));
+    }
+
+    let secret = webhooks::signature::generate_secret();
+    let endpoint = db::WebhookEndpointModel {
+        channel_id: Set(channel.channel_id),
+        url: Set(url.to_string()),
+        secret: Set(secret.clone()),
+        events: Set(serde_json::json!(req.events)),
+        ..Default::default()
+    }
+    .insert(&state.db)
+    .await?;
+    Ok(Data(CreatedWebhook {
+        webhook: endpoint.into(),
+        secret,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/owner/channels/{channel_id}/webhooks",
+    tag = "owner",
+    params(("channel_id" = i64, Path)),
+    security(("init_data" = []), ("session" = [])),
+    responses(
+        (status = 200, body = Envelope<Vec<WebhookView>>),
+        (status = 401, body = ErrorEnvelope),
+        (status = 403, body = ErrorEnvelope),
+        (status = 404, body = ErrorEnvelope),
+    )
+)]
+async fn list_webhooks(
+    State(state): State<AppState>,
+    Param(channel_id): Param<i64>,
+    requester: Requester,
+) -> Result<Data<Vec<WebhookView>>, ApiError> {
+    let channel = auth::find_owned_channel(&state.db, channel_id, &requester).await?;
+    let endpoints = db::WebhookEndpoint::find()
+        .filter(db::webhook_endpoint::Column::ChannelId.eq(channel.channel_id))
+        .order_by_asc(db::webhook_endpoint::Column::Id)
+        .all(&state.db)
+        .await?;
+    Ok(Data(endpoints.into_iter().map(Into::into).collect()))
+}
+
+/// Turns the endpoint off. It stays in the list with its delivery log, and nothing is
+/// sent to it anymore, including deliveries already queued.
+#[utoipa::path(
+    delete,
+    path = "/api/owner/webhooks/{webhook_id}",
+    tag = "owner",
+    params(("webhook_id" = i64, Path)),
+    security(("init_data" = []), ("session" = [])),
+    responses(
+        (status = 200, body = Envelope<WebhookView>),
+        (status = 401, body = ErrorEnvelope),
+        (status = 403, body = ErrorEnvelope),
+        (status = 404, body = ErrorEnvelope),
+    )
+)]
+async fn delete_webhook(
+    State(state): State<AppState>,
+    Param(webhook_id): Param<i64>,
+    requester: Requester,
+) -> Result<Data<WebhookView>, ApiError> {
+    let endpoint = auth::find_owned_webhook(&state.db, webhook_id, &requester).await?;
+    let mut endpoint: db::WebhookEndpointModel = endpoint.into();
+    endpoint.is_active = Set(false);
+    let endpoint = endpoint.update(&state.db).await?;
+    Ok(Data(endpoint.into()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/owner/webhooks/{webhook_id}/deliveries",
+    tag = "owner",
+    params(("webhook_id" = i64, Path)),
+    security(("init_data" = []), ("session" = [])),
+    responses(
+        (status = 200, body = Envelope<Vec<WebhookDeliveryView>>),
+        (status = 401, body = ErrorEnvelope),
+        (status = 403, body = ErrorEnvelope),
+        (status = 404, body = ErrorEnvelope),
+    )
+)]
+async fn list_webhook_deliveries(
+    State(state): State<AppState>,
+    Param(webhook_id): Param<i64>,
+    requester: Requester,
+) -> Result<Data<Vec<WebhookDeliveryView>>, ApiError> {
+    let endpoint = auth::find_owned_webhook(&state.db, webhook_id, &requester).await?;
+    let deliveries = db::WebhookDelivery::find()
+        .filter(db::webhook_delivery::Column::EndpointId.eq(endpoint.id))
+        .order_by_desc(db::webhook_delivery::Column::Id)
+        .limit(DELIVERIES_LIMIT)
+        .all(&state.db)
+        .await?;
+    Ok(Data(deliveries.into_iter().map(Into::into).collect()))
+}
+
+/// Sends the event again from scratch: the delivery goes back to the queue with a fresh
+/// set of attempts and the same event id.
+#[utoipa::path(
+    post,
+    path = "/api/owner/webhooks/{webhook_id}/deliveries/{delivery_id}/replay",
+    tag = "owner",
+    params(("webhook_id" = i64, Path), ("delivery_id" = i64, Path)),
+    security(("init_data" = []), ("session" = [])),
+    responses(
+        (status = 200, body = Envelope<WebhookDeliveryView>),
+        (status = 401, body = ErrorEnvelope),
+        (status = 403, body = ErrorEnvelope),
+        (status = 404, body = ErrorEnvelope),
+    )
+)]
+async fn replay_webhook_delivery(
+    State(state): State<AppState>,
+    Param((webhook_id, delivery_id)): Param<(i64, i64)>,
+    requester: Requester,
+) -> Result<Data<WebhookDeliveryView>, ApiError> {
+    let endpoint = auth::find_owned_webhook(&state.db, webhook_id, &requester).await?;
+    let delivery = db::WebhookDelivery::find_by_id(delivery_id)
+        .filter(db::webhook_delivery::Column::EndpointId.eq(endpoint.id))
+        .one(&state.db)
+        .await?
+        .ok_or(ApiError::DeliveryNotFound)?;
+    let delivery = webhooks::delivery::replay(&state.db, delivery.id)
+        .await?
+        .ok_or(ApiError::DeliveryNotFound)?;
+    Ok(Data(delivery.into()))
+}
+
+#[derive(serde::Deserialize, ToSchema)]
+struct CreateApiKeyRequest {
+    /// Label to tell keys apart, e.g. the name of the shop.
+    name: String,
+    /// `invoices:write` and/or `invoices:read`. Both when empty or missing.
+    #[serde(default)]
+    scopes: Vec<String>,
+}
+
+#[derive(serde::Serialize, ToSchema)]
+struct ApiKeyView {
+    id: i64,
+    name: String,
+    /// First characters of the key.
+    prefix: String,
+    scopes: Vec<String>,
+    created_at: DateTime<Utc>,
+    last_used_at: Option<DateTime<Utc>>,
+    revoked_at: Option<DateTime<Utc>>,
+}
+
+impl From<db::api_key::Model> for ApiKeyView {
+    fn from(key: db::api_key::Model) -> Self {
+        ApiKeyView {
+            id: key.id,
+            name: key.name,
+            prefix: key.prefix,
+            scopes: serde_json::from_value(key.scopes).unwrap_or_default(),
+            created_at: key.created_at,
+            last_used_at: key.last_used_at,
+            revoked_at: key.revoked_at,
+        }
+    }
+}
+
+/// A new key. Only its hash is stored, so it's shown only here.
+#[derive(serde::Serialize, ToSchema)]
+struct CreatedApiKey {
+    api_key: ApiKeyView,
+    /// Goes to the `X-Api-Key` header of merchant requests.
+    key: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/owner/api-keys",
+    tag = "owner",
+    request_body = CreateApiKeyRequest,
+    security(("init_data" = []), ("session" = [])),
+    responses(
+        (status = 200, body = Envelope<CreatedApiKey>),
+        (status = 400, body = ErrorEnvelope),
+        (status = 401, body = ErrorEnvelope),
+        (status = 403, body = ErrorEnvelope),
+    )
+)]
+async fn create_api_key(
+    State(state): State<AppState>,
+    requester: Requester,
+    Body(req): Body<CreateApiKeyRequest>,
+) -> Result<Data<CreatedApiKey>, ApiError> {
+    let owner = requester.require_telegram_id()?;
+    let name = req.name.trim();
+    if name.is_empty() || name.len() > 64 {
+        return Err(ApiError::BadRequest("`name` must be 1-64 characters".to_string()));
+    }
+    if let Some(unknown) = req
+        .scopes
+        .iter()
+        .find(|scope| !db::api_key::SCOPES.contains(&scope.as_str()))
+    {
+        return Err(ApiError::BadRequest(format!("Unknown scope `{}`", unknown)));
+    }
+    let scopes = if req.scopes.is_empty() {
+        db::api_key::SCOPES.map(str::to_string).to_vec()
     } else {
-        Err((
-            StatusCode::UNAUTHORIZED,
-            Json(ErrorResponse {
-                error: "Invalid signature".to_string()
-            }),
-        ))
-    }
-}
\ No newline at end of file
+        req.scopes
+    };
+
+    let (key, prefix, key_hash) = api_key::generate();
+    let now = Utc::now();
+    let txn = state.db.begin().await?;
+    ensure_user(&txn, owner, now).await?;
+    let api_key = db::ApiKeyModel {
+        owner_telegram_id: Set(owner),
+        name: Set(name.to_string()),
+        prefix: Set(prefix),
+        key_hash: Set(key_hash),
+        scopes: Set(serde_json::json!(scopes)),
+        created_at: Set(now),
+        ..Default::default()
+    }
+    .insert(&txn)
+    .await?;
+    txn.commit().await?;
+    Ok(Data(CreatedApiKey {
+        api_key: api_key.into(),
+        key,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/owner/api-keys",
+    tag = "owner",
+    security(("init_data" = []), ("session" = [])),
+    responses(
+        (status = 200, body = Envelope<Vec<ApiKeyView>>),
+        (status = 401, body = ErrorEnvelope),
+        (status = 403, body = ErrorEnvelope),
+    )
+)]
+async fn list_api_keys(
+    State(state): State<AppState>,
+    requester: Requester,
+) -> Result<Data<Vec<ApiKeyView>>, ApiError> {
+    let owner = requester.require_telegram_id()?;
+    let keys = db::ApiKey::find()
+        .filter(db::api_key::Column::OwnerTelegramId.eq(owner))
+        .order_by_asc(db::api_key::Column::Id)
+        .all(&state.db)
+        .await?;
+    Ok(Data(keys.into_iter().map(Into::into).collect()))
+}
+
+/// Revokes the key for good. Invoices created with it stay payable.
+#[utoipa::path(
+    delete,
+    path = "/api/owner/api-keys/{api_key_id}",
+    tag = "owner",
+    params(("api_key_id" = i64, Path)),
+    security(("init_data" = []), ("session" = [])),
+    responses(
+        (status = 200, body = Envelope<ApiKeyView>),
+        (status = 401, body = ErrorEnvelope),
+        (status = 403, body = ErrorEnvelope),
+        (status = 404, body = ErrorEnvelope),
+    )
+)]
+async fn revoke_api_key(
+    State(state): State<AppState>,
+    Param(api_key_id): Param<i64>,
+    requester: Requester,
+) -> Result<Data<ApiKeyView>, ApiError> {
+    let owner = requester.require_telegram_id()?;
+    let key = db::ApiKey::find_by_id(api_key_id)
+        .filter(db::api_key::Column::OwnerTelegramId.eq(owner))
+        .one(&state.db)
+        .await?
+        .ok_or(ApiError::ApiKeyNotFound)?;
+    if key.revoked_at.is_some() {
+        return Ok(Data(key.into()));
+    }
+    let mut key: db::ApiKeyModel = key.into();
+    key.revoked_at = Set(Some(Utc::now()));
+    let key = key.update(&state.db).await?;
+    Ok(Data(key.into()))
+}
+
+/// Invoices can be paid for an hour unless asked otherwise.
+const INVOICE_DEFAULT_TTL_MINUTES: i64 = 60;
+const INVOICE_MAX_TTL_MINUTES: i64 = 7 * 24 * 60;
+
+#[derive(serde::Deserialize, ToSchema)]
+struct CreateInvoiceRequest {
+    /// Amount to pay, at most 2 decimal places.
+    amount: Decimal,
+    /// Only `USDT` is accepted for now, which is also the default.
+    currency: Option<String>,
+    /// What the payer is paying for, shown on the payment page.
+    description: Option<String>,
+    /// One of the owner's channels to attribute the payment to: it then counts in the channel
+    /// metrics and triggers its webhooks. It doesn't grant a subscription.
+    channel_id: Option<i64>,
+    /// How long the invoice can be paid, 60 minutes by default and a week at most.
+    expires_in_minutes: Option<i64>,
+}
+
+#[derive(serde::Serialize, ToSchema)]
+struct InvoiceView {
+    id: Uuid,
+    /// active, failed, completed, expired or cancelled
+    status: String,
+    amount: Decimal,
+    currency: String,
+    description: Option<String>,
+    channel_id: Option<i64>,
+    /// Payment page to send the payer to.
+    payment_url: String,
+    /// Krypton gate address the payment goes to.
+    wallet_address: String,
+    /// Krypton's part of the amount, the rest is paid out to the owner.
+    platform_fee: Decimal,
+    owner_amount: Decimal,
+    created_at: DateTime<Utc>,
+    expires_at: Option<DateTime<Utc>>,
+    completed_at: Option<DateTime<Utc>>,
+}
+
+impl InvoiceView {
+    fn new(tx: db::transaction::Model, payment_gateway: &str) -> Self {
+        InvoiceView {
+            id: tx.public_id,
+            status: tx.status,
+            amount: tx.price,
+            currency: tx.currency,
+            description: tx.description,
+            channel_id: tx.channel_id,
+            payment_url: format!("{}/{}", payment_gateway, tx.public_id),
+            wallet_address: tx.wallet_address,
+            platform_fee: tx.platform_fee,
+            owner_amount: tx.owner_amount,
+            created_at: tx.created_at,
+            expires_at: tx.expires_at,
+            completed_at: tx.completed_at,
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/merchant/invoices",
+    tag = "merchant",
+    request_body = CreateInvoiceRequest,
+    security(("api_key" = [])),
+    responses(
+        (status = 200, body = Envelope<InvoiceView>),
+        (status = 400, body = ErrorEnvelope),
+        (status = 401, body = ErrorEnvelope),
+        (status = 403, body = ErrorEnvelope),
+        (status = 404, body = ErrorEnvelope),
+    )
+)]
+async fn create_invoice(
+    State(state): State<AppState>,
+    merchant: api_key::MerchantKey,
+    Body(req): Body<CreateInvoiceRequest>,
+) -> Result<Data<InvoiceView>, ApiError> {
+    let key = merchant.require(db::api_key::SCOPE_INVOICES_WRITE)?;
+    if req.amount <= Decimal::ZERO || req.amount.scale() > 2 {
+        return Err(ApiError::BadRequest(
+            "`amount` must be positive with at most 2 decimal places".to_string(),
+        ));
+    }
+    let currency = req.currency.as_deref().unwrap_or(channel::SUBSCRIPTION_CURRENCY);
+    if currency != channel::SUBSCRIPTION_CURRENCY {
+        return Err(ApiError::BadRequest(format!("Unsupported currency `{}`", currency)));
+    }
+    let description = req
+        .description
+        .map(|description| description.trim().to_string())
+        .filter(|description| !description.is_empty());
+    if description.as_ref().is_some_and(|description| description.len() > 255) {
+        return Err(ApiError::BadRequest("`description` must be at most 255 characters".to_string()));
+    }
+    let ttl = req.expires_in_minutes.unwrap_or(INVOICE_DEFAULT_TTL_MINUTES);
+    if !(1..=INVOICE_MAX_TTL_MINUTES).contains(&ttl) {
+        return Err(ApiError::BadRequest(format!(
+            "`expires_in_minutes` must be between 1 and {}",
+            INVOICE_MAX_TTL_MINUTES
+        )));
+    }
+    if let Some(channel_id) = req.channel_id {
+        let channel = Channel::find_by_id(channel_id)
+            .one(&state.db)
+            .await?
+            .ok_or(ApiError::ChannelNotFound)?;
+        if channel.owner_telegram_id != key.owner_telegram_id {
+            return Err(ApiError::ChannelForbidden);
+        }
+    }
+
+    let fee = db::settings::PlatformFee::load(&state.db).await?;
+    let split = fee.split(req.amount);
+    let now = Utc::now();
+    let txn = state.db.begin().await?;
+    let tx = db::TransactionModel {
+        channel_id: Set(req.channel_id),
+        price: Set(req.amount),
+        currency: Set(currency.to_string()),
+        description: Set(description),
+        api_key_id: Set(Some(key.id)),
+        status: Set("active".to_string()),
+        created_at: Set(now),
+        expires_at: Set(Some(now + Duration::minutes(ttl))),
+        platform_fee: Set(split.fee),
+        owner_amount: Set(split.owner_amount),
+        wallet_address: Set(state.gate_crypto_address.to_string()),
+        ..Default::default()
+    }
+    .insert(&txn)
+    .await?;
+    db::FeeLedgerModel {
+        transaction_id: Set(tx.id),
+        channel_id: Set(req.channel_id),
+        owner_telegram_id: Set(key.owner_telegram_id),
+        currency: Set(tx.currency.clone()),
+        gross_amount: Set(tx.price),
+        fee_amount: Set(split.fee),
+        owner_amount: Set(split.owner_amount),
+        fee_percent: Set(fee.percent),
+        fee_minimum: Set(fee.minimum),
+        created_at: Set(now),
+        ..Default::default()
+    }
+    .insert(&txn)
+    .await?;
+    txn.commit().await?;
+    response::record_correlation_id(tx.correlation_id);
+    tracing::info!(transaction_id = tx.id, "invoice created");
+    metrics::counter!("payments_created_total", "currency" => tx.currency.clone(), "source" => "api")
+        .increment(1);
+    Ok(Data(InvoiceView::new(tx, &state.payment_gateway)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/merchant/invoices/{public_id}",
+    tag = "merchant",
+    params(("public_id" = Uuid, Path, description = "Id returned when the invoice was created")),
+    security(("api_key" = [])),
+    responses(
+        (status = 200, body = Envelope<InvoiceView>),
+        (status = 401, body = ErrorEnvelope),
+        (status = 403, body = ErrorEnvelope),
+        (status = 404, body = ErrorEnvelope),
+    )
+)]
+async fn get_invoice(
+    State(state): State<AppState>,
+    Param(public_id): Param<Uuid>,
+    merchant: api_key::MerchantKey,
+) -> Result<Data<InvoiceView>, ApiError> {
+    let key = merchant.require(db::api_key::SCOPE_INVOICES_READ)?;
+    // Счёт видят все ключи владельца, а чужие счета выглядят несуществующими
+    let (tx, created_with) = Transaction::find()
+        .filter(db::transaction::Column::PublicId.eq(public_id))
+        .find_also_related(db::ApiKey)
+        .one(&state.db)
+        .await?
+        .ok_or(ApiError::TransactionNotFound)?;
+    if created_with.is_none_or(|created_with| created_with.owner_telegram_id != key.owner_telegram_id) {
+        return Err(ApiError::TransactionNotFound);
+    }
+    response::record_correlation_id(tx.correlation_id);
+    Ok(Data(InvoiceView::new(tx, &state.payment_gateway)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOMAIN: &str = "app.krypton.com";
+    const NOW: i64 = 1760000030;
+
+    /// A `ton_proof` reply as the frontend forwards it, for a v4r2 wallet with the real
+    /// contract code. It was produced outside this crate: the StateInit, the address and the
+    /// signed message were built from the TON specs and signed with Python's `cryptography`,
+    /// so the test doesn't check our digest against itself.
+    const REPLY: &str = r#"{
+        "address": "0:2b332c111cfa196e9409f4493477f8823ca628086378ea7738dc7325a96f30f6",
+        "public_key": "30f5284b9140e6b4ad59ca24037376c216f491ced3cb82affbd3ea0234b29dbe",
+        "proof": {
+            "timestamp": 1760000000,
+            "domain": { "lengthBytes": 15, "value": "app.krypton.com" },
+            "payload": "e2e7a3c1f0b94d2a8c65",
+            "signature": "Ku6WcMj9a5yD+LLJcdUvJppDJn8rQoxZOPEgoBiHgNzRseBTlv7Mp6rlxZbidk2gMusRaHL58/MveryhX2rgCA==",
+            "state_init": "te6ccgECFgEAAwQAAgE0ARUBFP8A9KQT9LzyyAsCAgEgAxACAUgEBwLm0AHQ0wMhcbCSXwTgItdJwSCSXwTgAtMfIYIQcGx1Z70ighBkc3RyvbCSXwXgA/pAMCD6RAHIygfL/8nQ7UTQgQFA1yH0BDBcgQEI9ApvoTGzkl8H4AXTP8glghBwbHVnupI4MOMNA4IQZHN0crqSXwbjDQUGAHgB+gD0BDD4J28iMFAKoSG+8uBQghBwbHVngx6xcIAYUATLBSbPFlj6Ahn0AMtpF8sfUmDLPyDJgED7AAYAilAEgQEI9Fkw7UTQgQFA1yDIAc8W9ADJ7VQBcrCOI4IQZHN0coMesXCAGFAFywVQA88WI/oCE8tqyx/LP8mAQPsAkl8D4gIBIAgPAgEgCQ4CAVgKCwA9sp37UTQgQFA1yH0BDACyMoHy//J0AGBAQj0Cm+hMYAIBIAwNABmtznaiaEAga5Drhf/AABmvHfaiaEAQa5DrhY/AABG4yX7UTQ1wsfgAWb0kK29qJoQICga5D6AhhHDUCAhHpJN9KZEM5pA+n/mDeBKAG3gQFImHFZ8xhAT48oMI1xgg0x/TH9MfAvgju/Jk7UTQ0x/TH9P/9ATRUUO68qFRUbryogX5AVQQZPkQ8qP4ACSkyMsfUkDLH1Iwy/9SEPQAye1U+A8B0wchwACfbFGTINdKltMH1AL7AOgw4CHAAeMAIcAC4wABwAORMOMNA6TIyx8Syx/L/xESExQAbtIH+gDU1CL5AAXIygcVy//J0Hd0gBjIywXLAiLPFlAF+gIUy2sSzMzJc/sAyEAUgQEI9FHypwIAcIEBCNcY+gDTP8hUIEeBAQj0UfKnghBub3RlcHSAGMjLBcsCUAbPFlAE+gIUy2oSyx/LP8lz+wACAGyBAQjXGPoA0z8wUiSBAQj0WfKnghBkc3RycHSAGMjLBcsCUAXPFlAD+gITy2rLHxLLP8lz+wAACvQAye1UAFEAAAAAKamjFzD1KEuRQOa0rVnKJANzdsIW9JHO08uCr/vT6gI0sp2+QA=="
+        }
+    }"#;
+
+    fn reply() -> TonProofRequest {
+        serde_json::from_str(REPLY).unwrap()
+    }
+
+    #[tokio::test]
+    async fn accepts_wallet_reply() {
+        let address = check_proof(&reply(), DOMAIN, "", NOW).await.unwrap();
+        assert_eq!(address, "EQArMywRHPoZbpQJ9Ek0d_iCPKYoCGN46nc43HMlqW8w9m9X".parse().unwrap());
+    }
+
+    #[tokio::test]
+    async fn rejects_reply_for_another_domain_or_time() {
+        let err = check_proof(&reply(), "krypton.com", "", NOW).await.unwrap_err();
+        assert!(matches!(err, ApiError::InvalidDomain), "{:?}", err);
+
+        let err = check_proof(&reply(), DOMAIN, "", NOW + 6 * 60).await.unwrap_err();
+        assert!(matches!(err, ApiError::ProofExpired), "{:?}", err);
+    }
+
+    #[tokio::test]
+    async fn rejects_tampered_reply() {
+        let mut req = reply();
+        req.proof.payload = "e2e7a3c1f0b94d2a8c66".to_string();
+        let err = check_proof(&req, DOMAIN, "", NOW).await.unwrap_err();
+        assert!(matches!(err, ApiError::InvalidProof(ProofError::InvalidSignature)), "{:?}", err);
+
+        let mut req = reply();
+        req.public_key = Some("00".repeat(32));
+        let err = check_proof(&req, DOMAIN, "", NOW).await.unwrap_err();
+        assert!(matches!(err, ApiError::InvalidProof(ProofError::PublicKeyMismatch)), "{:?}", err);
+
+        // StateInit другого кошелька к этому адресу не подходит
+        let mut req = reply();
+        req.address = "0:2ab14395b929e925e99cabd1f4d4e0d35365097305c9f60a0860f59979a67ddc".to_string();
+        let err = check_proof(&req, DOMAIN, "", NOW).await.unwrap_err();
+        assert!(matches!(err, ApiError::InvalidProof(ProofError::AddressMismatch)), "{:?}", err);
+    }
+}
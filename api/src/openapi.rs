@@ -0,0 +1,105 @@
+//! OpenAPI document of the API, served at `/api/openapi.json`.
+//!
+//! `openapi.json` next to Cargo.toml is the committed snapshot the frontends are built
+//! against; the test below fails when the generated document drifts from it.
+//! Regenerate it with `UPDATE_OPENAPI=1 cargo test`.
+
+use axum::Json;
+use utoipa::{
+    Modify, OpenApi,
+    openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme},
+};
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Krypton API"),
+    paths(
+        crate::get_transaction,
+        crate::start_payment,
+        crate::payment_events,
+        crate::get_challenge,
+        crate::verify_proof,
+        crate::link_wallet,
+        crate::list_channels,
+        crate::get_channel,
+        crate::list_owned_channels,
+        crate::get_channel_metrics,
+        crate::create_webhook,
+        crate::list_webhooks,
+        crate::delete_webhook,
+        crate::list_webhook_deliveries,
+        crate::replay_webhook_delivery,
+        crate::create_api_key,
+        crate::list_api_keys,
+        crate::revoke_api_key,
+        crate::create_invoice,
+        crate::get_invoice,
+    ),
+    modifiers(&SecuritySchemes),
+    tags(
+        (name = "payments", description = "Payment page"),
+        (name = "auth", description = "TON Proof login"),
+        (name = "wallets", description = "Linking wallets to Telegram accounts"),
+        (name = "channels", description = "Channel catalog"),
+        (name = "owner", description = "Dashboard of channel owners"),
+        (name = "merchant", description = "Invoices created with API keys"),
+    )
+)]
+pub struct ApiDoc;
+
+struct SecuritySchemes;
+
+impl Modify for SecuritySchemes {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "session",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .description(Some("Token returned by /api/auth/tonproof"))
+                    .build(),
+            ),
+        );
+        components.add_security_scheme(
+            "init_data",
+            SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::with_description(
+                "Authorization",
+                "`tma <initData>` of the Telegram Mini App",
+            ))),
+        );
+        components.add_security_scheme(
+            "api_key",
+            SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::with_description(
+                crate::api_key::API_KEY_HEADER,
+                "Merchant key created at /api/owner/api-keys",
+            ))),
+        );
+    }
+}
+
+pub async fn openapi_json() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use super::*;
+
+    const SNAPSHOT: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/openapi.json");
+
+    #[test]
+    fn schema_matches_snapshot() {
+        let generated = ApiDoc::openapi().to_pretty_json().unwrap() + "\n";
+        if env::var_os("UPDATE_OPENAPI").is_some() {
+            fs::write(SNAPSHOT, &generated).unwrap();
+        }
+        let committed = fs::read_to_string(SNAPSHOT).expect("openapi.json snapshot is missing");
+        assert!(
+            generated == committed,
+            "OpenAPI schema drifted from openapi.json, run `UPDATE_OPENAPI=1 cargo test` and commit the result"
+        );
+    }
+}
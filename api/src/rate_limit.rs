@@ -0,0 +1,230 @@
+//! Redis token buckets shared by every API instance. A bucket holds up to `capacity` tokens
+//! and refills evenly over `period`; each request takes one. The auth endpoints are limited
+//! per client IP by [`layer`] and per wallet with [`check_wallet`], so neither many wallets
+//! from one IP nor one wallet from many IPs get through unthrottled.
+
+use std::{net::SocketAddr, time::Duration};
+
+use axum::{
+    extract::{ConnectInfo, MatchedPath, Request, State},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::Utc;
+use redis::{Script, aio::MultiplexedConnection};
+
+use crate::{AppState, error::ApiError, ton_proof::TonAddress};
+
+// Пополнение и списание одним скриптом, иначе параллельные запросы возьмут один и тот же токен
+const TOKEN_BUCKET_LUA: &str = r"
+local capacity = tonumber(ARGV[1])
+local refill_ms = tonumber(ARGV[2])
+local now = tonumber(ARGV[3])
+local state = redis.call('HMGET', KEYS[1], 'tokens', 'ts')
+local tokens = tonumber(state[1]) or capacity
+local ts = tonumber(state[2]) or now
+tokens = math.min(capacity, tokens + math.max(0, now - ts) / refill_ms)
+local wait = 0
+if tokens >= 1 then
+  tokens = tokens - 1
+else
+  wait = math.ceil((1 - tokens) * refill_ms)
+end
+redis.call('HSET', KEYS[1], 'tokens', tostring(tokens), 'ts', now)
+redis.call('PEXPIRE', KEYS[1], math.ceil(capacity * refill_ms))
+return wait
+";
+
+/// `capacity` requests per `period`, written as `capacity/seconds` in the configuration.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Limit {
+    pub capacity: u32,
+    pub period: Duration,
+}
+
+impl Limit {
+    pub const fn new(capacity: u32, seconds: u64) -> Self {
+        Limit {
+            capacity,
+            period: Duration::from_secs(seconds),
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        let (capacity, seconds) = value.trim().split_once('/')?;
+        let capacity: u32 = capacity.trim().parse().ok()?;
+        let seconds: u64 = seconds.trim().parse().ok()?;
+        (capacity > 0 && seconds > 0).then(|| Limit::new(capacity, seconds))
+    }
+
+    fn refill_ms(&self) -> u64 {
+        (self.period.as_millis() as u64 / self.capacity as u64).max(1)
+    }
+}
+
+/// Limits of the API, each overridable in the configuration.
+#[derive(Clone, Debug)]
+pub struct RateLimits {
+    /// `rate_limit.challenge`, per IP.
+    pub challenge: Limit,
+    /// `rate_limit.tonproof`, per IP.
+    pub tonproof: Limit,
+    /// `rate_limit.challenge_wallet`, per wallet address.
+    pub challenge_wallet: Limit,
+    /// `rate_limit.tonproof_wallet`, per wallet address.
+    pub tonproof_wallet: Limit,
+    /// `trust_forwarded_for`: take the client IP from `X-Forwarded-For`. Only safe
+    /// behind a proxy that overwrites the header.
+    pub trust_forwarded_for: bool,
+}
+
+impl RateLimits {
+    pub fn read(r: &mut config::Reader) -> Self {
+        let mut limit = |key: &str, default: Limit| match r.raw(key) {
+            Some(value) => Limit::parse(&value).unwrap_or_else(|| {
+                r.invalid(key, "must look like 20/60");
+                default
+            }),
+            None => default,
+        };
+        RateLimits {
+            challenge: limit("rate_limit.challenge", Limit::new(30, 60)),
+            tonproof: limit("rate_limit.tonproof", Limit::new(10, 60)),
+            challenge_wallet: limit("rate_limit.challenge_wallet", Limit::new(10, 60)),
+            tonproof_wallet: limit("rate_limit.tonproof_wallet", Limit::new(5, 60)),
+            trust_forwarded_for: r.or("trust_forwarded_for", false),
+        }
+    }
+
+    /// Per-IP limit of a route, by its path pattern.
+    fn for_route(&self, path: &str) -> Option<(&'static str, Limit)> {
+        match path {
+            "/api/auth/challenge" => Some(("challenge", self.challenge)),
+            "/api/auth/tonproof" => Some(("tonproof", self.tonproof)),
+            _ => None,
+        }
+    }
+}
+
+/// Takes a token from the `scope` bucket of `subject`. Redis failures let the request
+/// through: losing the limiter must not take logins down with it.
+async fn check(
+    redis: &mut MultiplexedConnection,
+    scope: &'static str,
+    subject: &str,
+    limit: Limit,
+) -> Result<(), ApiError> {
+    let wait_ms: Result<u64, _> = Script::new(TOKEN_BUCKET_LUA)
+        .key(format!("rate_limit:{}:{}", scope, subject))
+        .arg(limit.capacity)
+        .arg(limit.refill_ms())
+        .arg(Utc::now().timestamp_millis())
+        .invoke_async(redis)
+        .await;
+    match wait_ms {
+        Ok(0) => Ok(()),
+        Ok(wait_ms) => {
+            metrics::counter!("api_rate_limited_total", "scope" => scope).increment(1);
+            Err(ApiError::RateLimited(wait_ms.div_ceil(1000)))
+        }
+        Err(err) => {
+            tracing::warn!("❌ Ограничение запросов {} не проверено: {}", scope, err);
+            Ok(())
+        }
+    }
+}
+
+/// Bucket subject of a wallet: the raw form of its address, so the raw and every
+/// user-friendly spelling of one wallet share a bucket.
+fn wallet_subject(address: &str) -> Result<String, ApiError> {
+    Ok(address.trim().parse::<TonAddress>()?.to_string())
+}
+
+/// Takes a token from the `scope` bucket of the wallet `address`; an address that doesn't
+/// parse is rejected before it can name a bucket.
+pub async fn check_wallet(
+    redis: &mut MultiplexedConnection,
+    scope: &'static str,
+    address: &str,
+    limit: Limit,
+) -> Result<(), ApiError> {
+    let subject = wallet_subject(address)?;
+    check(redis, scope, &subject, limit).await
+}
+
+fn client_ip(request: &Request, trust_forwarded_for: bool) -> String {
+    let forwarded = trust_forwarded_for
+        .then(|| request.headers().get("x-forwarded-for"))
+        .flatten()
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(',').next())
+        .map(str::trim)
+        .filter(|ip| !ip.is_empty());
+    match forwarded {
+        Some(ip) => ip.to_string(),
+        None => request
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip().to_string())
+            .unwrap_or_else(|| "unknown".to_string()),
+    }
+}
+
+/// Applies the per-IP limit of the matched route, if it has one.
+pub async fn layer(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .and_then(|path| state.rate_limits.for_route(path.as_str()));
+    if let Some((scope, limit)) = route {
+        let ip = client_ip(&request, state.rate_limits.trust_forwarded_for);
+        let mut redis = state.redis.clone();
+        if let Err(err) = check(&mut redis, scope, &ip, limit).await {
+            return err.into_response();
+        }
+    }
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+
+    #[test]
+    fn parses_limits() {
+        assert_eq!(Limit::parse("20/60"), Some(Limit::new(20, 60)));
+        assert_eq!(Limit::parse(" 5 / 1 "), Some(Limit::new(5, 1)));
+        assert_eq!(Limit::parse("0/60"), None);
+        assert_eq!(Limit::parse("20"), None);
+        assert_eq!(Limit::parse("a/b"), None);
+    }
+
+    #[test]
+    fn refills_evenly_over_the_period() {
+        assert_eq!(Limit::new(20, 60).refill_ms(), 3000);
+        assert_eq!(Limit::new(5000, 1).refill_ms(), 1);
+    }
+
+    #[test]
+    fn wallet_spellings_share_one_bucket() {
+        let raw = "0:637bbbf36c97264d98659913eaad193c076fe30d8810c9c5ecabaf14a343a83b";
+        assert_eq!(wallet_subject(raw).unwrap(), raw);
+        assert_eq!(wallet_subject("EQBje7vzbJcmTZhlmRPqrRk8B2_jDYgQycXsq68Uo0OoO92-").unwrap(), raw);
+        assert_eq!(wallet_subject(&format!(" {} ", raw.to_uppercase())).unwrap(), raw);
+        assert!(matches!(wallet_subject("not-a-wallet"), Err(ApiError::InvalidProof(_))));
+    }
+
+    #[test]
+    fn forwarded_for_is_used_only_when_trusted() {
+        let mut request = Request::builder()
+            .header("x-forwarded-for", "203.0.113.7, 10.0.0.1")
+            .body(Body::empty())
+            .unwrap();
+        request
+            .extensions_mut()
+            .insert(ConnectInfo(SocketAddr::from(([10, 0, 0, 1], 4000))));
+        assert_eq!(client_ip(&request, true), "203.0.113.7");
+        assert_eq!(client_ip(&request, false), "10.0.0.1");
+    }
+}
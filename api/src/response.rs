@@ -0,0 +1,111 @@
+//! The response envelope shared by all handlers and the request id that goes with it.
+//!
+//! Success: `{"status": "ok", "data": ..., "request_id": "..."}`.
+//! Failure: `{"status": "error", "error": {"code": ..., "message": ...}, "request_id": "..."}`,
+//! see [`crate::error::ApiError`].
+
+use axum::{
+    Json,
+    extract::{FromRequest, FromRequestParts, Request},
+    http::{HeaderName, HeaderValue},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use sea_orm::prelude::Uuid;
+use serde::Serialize;
+use std::time::Instant;
+use tracing::Instrument;
+use utoipa::ToSchema;
+
+use crate::error::ApiError;
+
+pub static X_REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// Id of the request being handled, `None` outside of [`request_id_layer`].
+pub fn request_id() -> Option<String> {
+    REQUEST_ID.try_with(Clone::clone).ok()
+}
+
+/// Body of every successful response.
+#[derive(Serialize, ToSchema)]
+pub struct Envelope<T> {
+    #[schema(value_type = String, example = "ok")]
+    status: &'static str,
+    data: T,
+    request_id: Option<String>,
+}
+
+/// Successful response: `data` wrapped into the envelope.
+pub struct Data<T>(pub T);
+
+impl<T: Serialize> IntoResponse for Data<T> {
+    fn into_response(self) -> Response {
+        Json(Envelope {
+            status: "ok",
+            data: self.0,
+            request_id: request_id(),
+        })
+        .into_response()
+    }
+}
+
+/// `axum::Json` whose rejection is an [`ApiError`], so malformed bodies get the envelope too.
+#[derive(FromRequest)]
+#[from_request(via(axum::Json), rejection(ApiError))]
+pub struct Body<T>(pub T);
+
+/// `axum::extract::Path` whose rejection is an [`ApiError`].
+#[derive(FromRequestParts)]
+#[from_request(via(axum::extract::Path), rejection(ApiError))]
+pub struct Param<T>(pub T);
+
+/// `axum::extract::Query` whose rejection is an [`ApiError`].
+#[derive(FromRequestParts)]
+#[from_request(via(axum::extract::Query), rejection(ApiError))]
+pub struct QueryParams<T>(pub T);
+
+/// Attaches the payment a request works with to its log lines.
+pub fn record_correlation_id(correlation_id: Uuid) {
+    tracing::Span::current().record("correlation_id", tracing::field::display(correlation_id));
+}
+
+/// Takes the caller's `X-Request-Id` or generates one, makes it available to
+/// [`request_id`] while the request is handled and echoes it in the response header.
+/// The request is handled inside a `request` span carrying the id, and its outcome is logged.
+pub async fn request_id_layer(request: Request, next: Next) -> Response {
+    let id = request
+        .headers()
+        .get(&X_REQUEST_ID)
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| !value.is_empty() && value.len() <= 64)
+        .map(str::to_string)
+        .unwrap_or_else(|| hex::encode(rand::random::<[u8; 16]>()));
+
+    let span = tracing::info_span!(
+        "request",
+        request_id = %id,
+        method = %request.method(),
+        path = %request.uri().path(),
+        correlation_id = tracing::field::Empty,
+    );
+    let started = Instant::now();
+    let mut response = REQUEST_ID
+        .scope(id.clone(), next.run(request))
+        .instrument(span.clone())
+        .await;
+    span.in_scope(|| {
+        tracing::info!(
+            status = response.status().as_u16(),
+            elapsed_ms = started.elapsed().as_millis() as u64,
+            "request handled"
+        )
+    });
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        response.headers_mut().insert(X_REQUEST_ID.clone(), value);
+    }
+    response
+}
@@ -0,0 +1,49 @@
+use axum::{extract::FromRequestParts, http::request::Parts};
+use axum_extra::{
+    TypedHeader,
+    headers::{Authorization, authorization::Bearer},
+};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use redis::{AsyncCommands, RedisResult, aio::MultiplexedConnection};
+
+use crate::{AppState, error::ApiError};
+
+/// How long a session issued by verify_proof stays valid.
+pub const SESSION_TTL_SECONDS: u64 = 24 * 60 * 60;
+
+fn key(token: &str) -> String {
+    format!("session:{}", token)
+}
+
+/// Issues an opaque token bound to `address`. The token is only a random Redis key,
+/// so it can't be forged and is revoked by deleting the key.
+pub async fn create(redis: &mut MultiplexedConnection, address: &str) -> RedisResult<String> {
+    let token = URL_SAFE_NO_PAD.encode(rand::random::<[u8; 32]>());
+    redis
+        .set_ex::<_, _, ()>(key(&token), address, SESSION_TTL_SECONDS)
+        .await?;
+    Ok(token)
+}
+
+/// The wallet authenticated by a TON Proof login, taken from `Authorization: Bearer <token>`.
+#[derive(Debug, Clone)]
+pub struct Session {
+    pub address: String,
+}
+
+impl FromRequestParts<AppState> for Session {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        let TypedHeader(Authorization(bearer)) =
+            TypedHeader::<Authorization<Bearer>>::from_request_parts(parts, state)
+                .await
+                .map_err(|_| ApiError::MissingCredentials)?;
+
+        let mut redis = state.redis.clone();
+        let address: Option<String> = redis.get(key(bearer.token())).await?;
+        address
+            .map(|address| Session { address })
+            .ok_or(ApiError::InvalidSession)
+    }
+}
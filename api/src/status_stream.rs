@@ -0,0 +1,98 @@
+//! Server-sent events with the status of one payment transaction.
+//!
+//! The stream opens with a snapshot of the current status, then relays what ton-watcher
+//! publishes through `events::payment_status`. Every relayed event carries its Redis
+//! stream id, so a client reconnecting with `Last-Event-ID` gets the snapshot plus
+//! everything it missed. The stream ends after an event with `terminal: true`.
+
+use std::{collections::VecDeque, time::Duration};
+
+use axum::response::sse::Event;
+use events::payment_status::{PaymentStatusEvent, read_payment_status};
+use futures_util::{Stream, stream};
+use redis::aio::MultiplexedConnection;
+
+use crate::error::ApiError;
+
+/// Longest wait for new events in one XREAD; the connection is checked for a hang-up in between.
+const READ_BLOCK_MS: usize = 15_000;
+/// Reconnection delay suggested to EventSource clients.
+const RETRY: Duration = Duration::from_secs(3);
+
+/// Whether `id` looks like a Redis stream id, `<ms>-<seq>`. Anything else in
+/// `Last-Event-ID` is ignored rather than passed to XREAD.
+pub fn is_event_id(id: &str) -> bool {
+    id.split_once('-').is_some_and(|(ms, seq)| {
+        !ms.is_empty()
+            && !seq.is_empty()
+            && ms.bytes().all(|b| b.is_ascii_digit())
+            && seq.bytes().all(|b| b.is_ascii_digit())
+    })
+}
+
+fn to_sse(id: Option<&str>, event: &PaymentStatusEvent) -> Event {
+    let sse = Event::default()
+        .event("status")
+        .data(serde_json::to_string(event).unwrap());
+    match id {
+        Some(id) => sse.id(id),
+        None => sse,
+    }
+}
+
+struct State {
+    redis: MultiplexedConnection,
+    transaction_id: i64,
+    after: String,
+    queue: VecDeque<Event>,
+    done: bool,
+}
+
+/// Events of `transaction_id` published after `after`, preceded by `snapshot`.
+/// `redis` is blocked by XREAD and must not be shared.
+pub fn status_stream(
+    redis: MultiplexedConnection,
+    transaction_id: i64,
+    after: String,
+    snapshot: PaymentStatusEvent,
+) -> impl Stream<Item = Result<Event, ApiError>> {
+    let state = State {
+        redis,
+        transaction_id,
+        after,
+        queue: VecDeque::from([to_sse(None, &snapshot).retry(RETRY)]),
+        done: snapshot.terminal,
+    };
+    stream::unfold(state, |mut state| async move {
+        loop {
+            if let Some(event) = state.queue.pop_front() {
+                return Some((Ok(event), state));
+            }
+            if state.done {
+                return None;
+            }
+            let events = match read_payment_status(
+                &mut state.redis,
+                state.transaction_id,
+                &state.after,
+                READ_BLOCK_MS,
+            )
+            .await
+            {
+                Ok(events) => events,
+                Err(err) => {
+                    state.done = true;
+                    return Some((Err(err.into()), state));
+                }
+            };
+            for (id, event) in events {
+                state.queue.push_back(to_sse(Some(&id), &event));
+                state.after = id;
+                if event.terminal {
+                    state.done = true;
+                    break;
+                }
+            }
+        }
+    })
+}
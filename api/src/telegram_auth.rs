@@ -0,0 +1,148 @@
+use axum::{
+    extract::FromRequestParts,
+    http::{header::AUTHORIZATION, request::Parts},
+};
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::{AppState, error::ApiError};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// initData older than this is rejected even with a valid signature.
+const INIT_DATA_TTL_SECONDS: i64 = 24 * 60 * 60;
+
+/// Checks Mini App `initData` as described in
+/// https://core.telegram.org/bots/webapps#validating-data-received-via-the-mini-app
+/// and returns the id of the Telegram user who opened the app.
+pub fn verify_init_data(init_data: &str, bot_token: &str, now: i64) -> Result<i64, &'static str> {
+    let mut hash = None;
+    let mut pairs: Vec<(String, String)> = Vec::new();
+    for (key, value) in form_urlencoded::parse(init_data.as_bytes()) {
+        if key == "hash" {
+            hash = Some(value.into_owned());
+        } else {
+            pairs.push((key.into_owned(), value.into_owned()));
+        }
+    }
+    let hash = hash
+        .and_then(|hash| hex::decode(hash).ok())
+        .ok_or("Missing initData hash")?;
+    pairs.sort_by(|a, b| a.0.cmp(&b.0));
+    let data_check_string = pairs
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let mut secret = HmacSha256::new_from_slice(b"WebAppData").expect("HMAC takes any key size");
+    secret.update(bot_token.as_bytes());
+    let secret = secret.finalize().into_bytes();
+    let mut mac = HmacSha256::new_from_slice(&secret).expect("HMAC takes any key size");
+    mac.update(data_check_string.as_bytes());
+    mac.verify_slice(&hash).map_err(|_| "Invalid initData signature")?;
+
+    let field = |name: &str| {
+        pairs
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    };
+    let auth_date: i64 = field("auth_date")
+        .and_then(|date| date.parse().ok())
+        .ok_or("Missing auth_date")?;
+    if now - auth_date > INIT_DATA_TTL_SECONDS {
+        return Err("initData expired");
+    }
+    let user: serde_json::Value = field("user")
+        .and_then(|user| serde_json::from_str(user).ok())
+        .ok_or("Missing user")?;
+    user["id"].as_i64().ok_or("Missing user id")
+}
+
+/// The Telegram user behind `Authorization: tma <initData>`, sent by the payment page
+/// when it's opened as a Mini App from the bot.
+#[derive(Debug, Clone)]
+pub struct TelegramUser {
+    pub telegram_id: i64,
+}
+
+impl FromRequestParts<AppState> for TelegramUser {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self, Self::Rejection> {
+        let init_data = parts
+            .headers
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("tma "))
+            .ok_or(ApiError::MissingCredentials)?;
+        verify_init_data(init_data, &state.bot_token, Utc::now().timestamp())
+            .map(|telegram_id| TelegramUser { telegram_id })
+            .map_err(ApiError::InvalidInitData)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOT_TOKEN: &str = "7000000001:AAHkrypton-test-token-not-real-0000";
+    /// Signed with `BOT_TOKEN` outside this code, the way Telegram signs initData.
+    const INIT_DATA: &str = "auth_date=1760000000&query_id=AAHdF6IQAAAAAN0XohDhrOrc\
+        &user=%7B%22id%22%3A279058397%2C%22first_name%22%3A%22Test%22%2C%22username%22%3A%22krypton_test%22%2C%22language_code%22%3A%22en%22%7D\
+        &hash=eca7f28225ade8e1e614f3189d31a15ef62c3bcc5cf77345027254e7fe92c26e";
+    const AUTH_DATE: i64 = 1760000000;
+
+    #[test]
+    fn accepts_signed_init_data() {
+        assert_eq!(verify_init_data(INIT_DATA, BOT_TOKEN, AUTH_DATE + 60), Ok(279058397));
+    }
+
+    #[test]
+    fn rejects_another_bot_token() {
+        let other = "7000000002:AAHkrypton-test-token-not-real-0000";
+        assert_eq!(
+            verify_init_data(INIT_DATA, other, AUTH_DATE),
+            Err("Invalid initData signature")
+        );
+    }
+
+    #[test]
+    fn rejects_tampered_fields() {
+        let tampered = INIT_DATA.replace("279058397", "279058398");
+        assert_eq!(
+            verify_init_data(&tampered, BOT_TOKEN, AUTH_DATE),
+            Err("Invalid initData signature")
+        );
+        let tampered = INIT_DATA.replace("auth_date=1760000000", "auth_date=1760090000");
+        assert_eq!(
+            verify_init_data(&tampered, BOT_TOKEN, AUTH_DATE),
+            Err("Invalid initData signature")
+        );
+    }
+
+    #[test]
+    fn rejects_expired_auth_date() {
+        let now = AUTH_DATE + INIT_DATA_TTL_SECONDS;
+        assert_eq!(verify_init_data(INIT_DATA, BOT_TOKEN, now), Ok(279058397));
+        assert_eq!(
+            verify_init_data(INIT_DATA, BOT_TOKEN, now + 1),
+            Err("initData expired")
+        );
+    }
+
+    #[test]
+    fn rejects_missing_hash() {
+        let (unsigned, _) = INIT_DATA.split_once("&hash=").unwrap();
+        assert_eq!(
+            verify_init_data(unsigned, BOT_TOKEN, AUTH_DATE),
+            Err("Missing initData hash")
+        );
+        assert_eq!(
+            verify_init_data(&format!("{}&hash=not-hex", unsigned), BOT_TOKEN, AUTH_DATE),
+            Err("Missing initData hash")
+        );
+    }
+}
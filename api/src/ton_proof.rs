@@ -0,0 +1,363 @@
+//! TON Connect `ton_proof` verification, see
+//! https://docs.ton.org/develop/dapps/ton-connect/sign
+
+use std::str::FromStr;
+
+use base64::{Engine, engine::general_purpose};
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use boc::{Boc, BocError, CellSlice};
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ProofError {
+    #[error("Invalid address")]
+    InvalidAddress,
+
+    #[error("Invalid state init: {0}")]
+    InvalidStateInit(#[from] BocError),
+
+    #[error("State init doesn't derive the claimed address")]
+    AddressMismatch,
+
+    #[error("Unsupported wallet contract")]
+    UnsupportedWallet,
+
+    #[error("Public key doesn't match the wallet")]
+    PublicKeyMismatch,
+
+    #[error("Invalid signature")]
+    InvalidSignature,
+
+    #[error("Can't get the wallet public key: {0}")]
+    PublicKeyUnavailable(String),
+}
+
+/// Account address: workchain and the hash of the contract's StateInit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TonAddress {
+    pub workchain: i32,
+    pub hash: [u8; 32],
+}
+
+impl FromStr for TonAddress {
+    type Err = ProofError;
+
+    /// Accepts the raw form (`0:<64 hex chars>`) TON Connect sends and user-friendly base64 addresses.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some((workchain, hash)) = s.split_once(':') {
+            let workchain = workchain.parse().map_err(|_| ProofError::InvalidAddress)?;
+            let hash = hex::decode(hash)
+                .ok()
+                .and_then(|hash| hash.try_into().ok())
+                .ok_or(ProofError::InvalidAddress)?;
+            return Ok(Self { workchain, hash });
+        }
+        let bytes = general_purpose::URL_SAFE
+            .decode(s)
+            .or_else(|_| general_purpose::STANDARD.decode(s))
+            .map_err(|_| ProofError::InvalidAddress)?;
+        if bytes.len() != 36 || crc16(&bytes[..34]).to_be_bytes() != bytes[34..] {
+            return Err(ProofError::InvalidAddress);
+        }
+        Ok(Self {
+            workchain: bytes[1] as i8 as i32,
+            hash: bytes[2..34].try_into().map_err(|_| ProofError::InvalidAddress)?,
+        })
+    }
+}
+
+impl std::fmt::Display for TonAddress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.workchain, hex::encode(self.hash))
+    }
+}
+
+/// CRC-16/XMODEM used by user-friendly addresses.
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc = 0u16;
+    for byte in data {
+        crc ^= (*byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+/// Where the public key lives in the persistent data of each supported wallet contract,
+/// keyed by the hash of the contract code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WalletVersion {
+    V3R1,
+    V3R2,
+    V4R1,
+    V4R2,
+    V5R1,
+}
+
+impl WalletVersion {
+    const CODE_HASHES: [(&'static str, WalletVersion); 5] = [
+        ("b61041a58a7980b946e8fb9e198e3c904d24799ffa36574ea4251c41a566f581", WalletVersion::V3R1),
+        ("84dafa449f98a6987789ba232358072bc0f76dc4524002a5d0918b9a75d2d599", WalletVersion::V3R2),
+        ("64dd54805522c5be8a9db59cea0105ccf0d08786ca79beb8cb79e880a8d7322d", WalletVersion::V4R1),
+        ("feb5ff6820e2ff0d9483e7e0d62c817d846789fb4ae580c878866d959dabd5c0", WalletVersion::V4R2),
+        ("20834b7b72b112147e1b2fb457b84e74d1a30f04f737d4f62a668e9552d2b72f", WalletVersion::V5R1),
+    ];
+
+    fn from_code_hash(hash: &[u8; 32]) -> Option<Self> {
+        let hash = hex::encode(hash);
+        Self::CODE_HASHES
+            .iter()
+            .find(|(code_hash, _)| *code_hash == hash)
+            .map(|(_, version)| *version)
+    }
+
+    fn public_key(&self, data: &mut CellSlice) -> Result<[u8; 32], BocError> {
+        match self {
+            // seqno:uint32 subwallet_id:uint32 public_key:bits256 ...
+            WalletVersion::V3R1 | WalletVersion::V3R2 | WalletVersion::V4R1 | WalletVersion::V4R2 => {
+                data.load_uint(64)?;
+            }
+            // is_signature_allowed:bool seqno:uint32 wallet_id:uint32 public_key:bits256 ...
+            WalletVersion::V5R1 => {
+                data.load_bit()?;
+                data.load_uint(64)?;
+            }
+        }
+        data.load_bytes::<32>()
+    }
+}
+
+/// Reads the public key from a base64 StateInit BOC after checking that the StateInit
+/// is really the one of `address` and that its code is a known wallet.
+pub fn public_key_from_state_init(
+    state_init: &str,
+    address: &TonAddress,
+) -> Result<[u8; 32], ProofError> {
+    let bytes = general_purpose::STANDARD
+        .decode(state_init)
+        .map_err(|_| ProofError::InvalidStateInit(BocError::InvalidMagic))?;
+    let boc = Boc::parse(&bytes)?;
+    let mut root = boc.root();
+    if root.hash() != address.hash {
+        return Err(ProofError::AddressMismatch);
+    }
+
+    // split_depth:(Maybe (## 5)) special:(Maybe TickTock) code:(Maybe ^Cell) data:(Maybe ^Cell) ...
+    if root.load_bit()? {
+        root.load_uint(5)?;
+    }
+    if root.load_bit()? {
+        root.load_uint(2)?;
+    }
+    let (true, true) = (root.load_bit()?, root.load_bit()?) else {
+        return Err(ProofError::UnsupportedWallet);
+    };
+    let code = root.load_ref()?;
+    let mut data = root.load_ref()?;
+    let version =
+        WalletVersion::from_code_hash(&code.hash()).ok_or(ProofError::UnsupportedWallet)?;
+    Ok(version.public_key(&mut data)?)
+}
+
+/// The digest the wallet signs:
+/// `sha256(0xffff ‖ "ton-connect" ‖ sha256("ton-proof-item-v2/" ‖ workchain ‖ hash ‖ domain_len ‖ domain ‖ timestamp ‖ payload))`.
+pub fn signed_digest(address: &TonAddress, domain: &str, timestamp: u64, payload: &str) -> [u8; 32] {
+    let mut message = Sha256::new();
+    message.update(b"ton-proof-item-v2/");
+    message.update(address.workchain.to_be_bytes());
+    message.update(address.hash);
+    message.update((domain.len() as u32).to_le_bytes());
+    message.update(domain.as_bytes());
+    message.update(timestamp.to_le_bytes());
+    message.update(payload.as_bytes());
+
+    let mut full = Sha256::new();
+    full.update([0xff, 0xff]);
+    full.update(b"ton-connect");
+    full.update(message.finalize());
+    full.finalize().into()
+}
+
+pub fn verify_signature(
+    public_key: &[u8; 32],
+    address: &TonAddress,
+    domain: &str,
+    timestamp: u64,
+    payload: &str,
+    signature: &str,
+) -> Result<(), ProofError> {
+    let signature: [u8; 64] = general_purpose::STANDARD
+        .decode(signature)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or(ProofError::InvalidSignature)?;
+    let public_key =
+        VerifyingKey::from_bytes(public_key).map_err(|_| ProofError::PublicKeyMismatch)?;
+    public_key
+        .verify(
+            &signed_digest(address, domain, timestamp, payload),
+            &Signature::from_bytes(&signature),
+        )
+        .map_err(|_| ProofError::InvalidSignature)
+}
+
+/// Asks an already deployed wallet for its key with the `get_public_key` get-method,
+/// for clients that don't send the StateInit.
+pub async fn fetch_public_key(api_key: &str, address: &TonAddress) -> Result<[u8; 32], ProofError> {
+    let unavailable = |err: &dyn std::fmt::Display| ProofError::PublicKeyUnavailable(err.to_string());
+    let response: Value = reqwest::Client::new()
+        .post("https://toncenter.com/api/v2/runGetMethod")
+        .header("X-API-Key", api_key)
+        .json(&json!({
+            "address": address.to_string(),
+            "method": "get_public_key",
+            "stack": [],
+        }))
+        .send()
+        .await
+        .map_err(|err| unavailable(&err))?
+        .json()
+        .await
+        .map_err(|err| unavailable(&err))?;
+
+    let result = &response["result"];
+    if result["exit_code"].as_i64() != Some(0) {
+        return Err(unavailable(&"get_public_key failed"));
+    }
+    let number = result["stack"][0][1]
+        .as_str()
+        .and_then(|n| n.strip_prefix("0x"))
+        .ok_or_else(|| unavailable(&"unexpected stack"))?;
+    let bytes = hex::decode(format!("{:0>64}", number)).map_err(|err| unavailable(&err))?;
+    bytes.try_into().map_err(|_| unavailable(&"public key is not 256 bits"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOMAIN: &str = "krypton.com";
+    const TIMESTAMP: u64 = 1760000000;
+    const PAYLOAD: &str = "nonce_42";
+
+    /// StateInit and address of freshly derived wallets with the real contract code,
+    /// signed over DOMAIN, TIMESTAMP and PAYLOAD with the wallet's key.
+    struct Vector {
+        version: WalletVersion,
+        address: &'static str,
+        friendly: &'static str,
+        public_key: &'static str,
+        state_init: &'static str,
+        signature: &'static str,
+    }
+
+    const VECTORS: [Vector; 3] = [
+        Vector {
+            version: WalletVersion::V3R2,
+            address: "0:637bbbf36c97264d98659913eaad193c076fe30d8810c9c5ecabaf14a343a83b",
+            friendly: "EQBje7vzbJcmTZhlmRPqrRk8B2_jDYgQycXsq68Uo0OoO92-",
+            public_key: "ed4928c628d1c2c6eae90338905995612959273a5c63f93636c14614ac8737d1",
+            state_init: "te6ccgEBAwEAoAACATQBAgDe/wAg3SCCAUyXuiGCATOcurGfcbDtRNDTH9MfMdcL/+ME4KTyYIMI1xgg0x/TH9Mf+CMTu/Jj7UTQ0x/TH9P/0VEyuvKhUUS68qIE+QFUEFX5EPKj+ACTINdKltMH1AL7AOjRAaTIyx/LH8v/ye1UAFAAAAAAKamjF+1JKMYo0cLG6ukDOJBZlWEpWSc6XGP5NjbBRhSshzfR",
+            signature: "ygK1UfDMCbsNmFYWPG049nIJmqJLPySMlXqdkGa+3dnKR/s59YHc82dRBYKLBnRchpPY0V8lfRZ2V3iRzAOZAw==",
+        },
+        Vector {
+            version: WalletVersion::V4R2,
+            address: "0:2ab14395b929e925e99cabd1f4d4e0d35365097305c9f60a0860f59979a67ddc",
+            friendly: "EQAqsUOVuSnpJemcq9H01ODTU2UJcwXJ9goIYPWZeaZ93B2n",
+            public_key: "ca93ac1705187071d67b83c7ff0efe8108e8ec4530575d7726879333dbdabe7c",
+            state_init: "te6ccgECFgEAAwQAAgE0AQIBFP8A9KQT9LzyyAsDAFEAAAAAKamjF8qTrBcFGHBx1nuDx/8O/oEI6OxFMFdddyaHkzPb2r58QAIBIAQFAgFIBgcE+PKDCNcYINMf0x/THwL4I7vyZO1E0NMf0x/T//QE0VFDuvKhUVG68qIF+QFUEGT5EPKj+AAkpMjLH1JAyx9SMMv/UhD0AMntVPgPAdMHIcAAn2xRkyDXSpbTB9QC+wDoMOAhwAHjACHAAuMAAcADkTDjDQOkyMsfEssfy/8ICQoLAubQAdDTAyFxsJJfBOAi10nBIJJfBOAC0x8hghBwbHVnvSKCEGRzdHK9sJJfBeAD+kAwIPpEAcjKB8v/ydDtRNCBAUDXIfQEMFyBAQj0Cm+hMbOSXwfgBdM/yCWCEHBsdWe6kjgw4w0DghBkc3RyupJfBuMNDA0CASAODwBu0gf6ANTUIvkABcjKBxXL/8nQd3SAGMjLBcsCIs8WUAX6AhTLaxLMzMlz+wDIQBSBAQj0UfKnAgBwgQEI1xj6ANM/yFQgR4EBCPRR8qeCEG5vdGVwdIAYyMsFywJQBs8WUAT6AhTLahLLH8s/yXP7AAIAbIEBCNcY+gDTPzBSJIEBCPRZ8qeCEGRzdHJwdIAYyMsFywJQBc8WUAP6AhPLassfEss/yXP7AAAK9ADJ7VQAeAH6APQEMPgnbyIwUAqhIb7y4FCCEHBsdWeDHrFwgBhQBMsFJs8WWPoCGfQAy2kXyx9SYMs/IMmAQPsABgCKUASBAQj0WTDtRNCBAUDXIMgBzxb0AMntVAFysI4jghBkc3Rygx6xcIAYUAXLBVADzxYj+gITy2rLH8s/yYBA+wCSXwPiAgEgEBEAWb0kK29qJoQICga5D6AhhHDUCAhHpJN9KZEM5pA+n/mDeBKAG3gQFImHFZ8xhAIBWBITABG4yX7UTQ1wsfgAPbKd+1E0IEBQNch9AQwAsjKB8v/ydABgQEI9ApvoTGACASAUFQAZrc52omhAIGuQ64X/wAAZrx32omhAEGuQ64WPwA==",
+            signature: "kTNoCz+fXYXGJG6c/NkjTJvEC5XLrLLlFQkzrOICo0w8/50XNxcSeCkDg0Y49qnss1+JHDxDSwK0rwUSZg4jCQ==",
+        },
+        Vector {
+            version: WalletVersion::V5R1,
+            address: "0:7fd215a40b81daadef08d32aec841f737c3dd92908d0b9231cf7058158991096",
+            friendly: "EQB_0hWkC4Hare8I0yrshB9zfD3ZKQjQuSMc9wWBWJkQlleQ",
+            public_key: "6e7a1cdd29b0b78fd13af4c5598feff4ef2a97166e3ca6f2e4fbfccd80505bf1",
+            state_init: "te6ccgECFgEAArEAAgE0AQIBFP8A9KQT9LzyyAsDAFGAAAAAP///iLc9Dm6U2FvH6J16YqzH9/p3lUuLNx5TeXJ9/mbAKC34oAIBIAQFAgFIBgcBAvIIAtzQINdJwSCRW49jINcLHyCCEGV4dG69IYIQc2ludL2wkl8D4IIQZXh0brqOtIAg1yEB0HTXIfpAMPpE+Cj6RDBYvZFb4O1E0IEBQdch9AWDB/QOb6ExkTDhgEDXIXB/2zzgMSDXSYECgLmRMOBw4hIJAgEgCgsBHiDXCx+CEHNpZ2668uCKfwkB5o7w7aLt+yGDCNciAoMI1yMggCDXIdMf0x/TH+1E0NIA0x8g0x/T/9cKAAr5AUDM+RCaKJRfCtsx4fLAh98Cs1AHsPLQhFEluvLghVA2uvLghvgju/LQiCKS+ADeAaR/yMoAyx8BzxbJ7VQgkvgP3nDbPNgSAgEgDA0AGb5fD2omhAgKDrkPoCwCAW4ODwIBSBARABmtznaiaEAg65Drhf/AABmvHfaiaEAQ65DrhY/AABezJftRNBx1yHXCx+AAEbJi+1E0NcKAIAP27aLt+wL0BCFukmwhjkwCIdc5MHCUIccAs44tAdcoIHYeQ2wg10nACPLgkyDXSsAC8uCTINcdBscSwgBSMLDy0InXTNc5MAGk6GwShAe78uCT10rAAPLgk+1V4tIAAcAAkVvg69csCBQgkXCWAdcsCBwS4lIQseMPINdKExQVAJYB+kAB+kT4KPpEMFi68uCR7UTQgQFB1xj0BQSdf8jKAEAEgwf0U/Lgi44UA4MH9Fvy4Iwi1woAIW4Bs7Dy0JDiyFADzxYS9ADJ7VQAcjDXLAgkji0h8uCS0gDtRNDSAFETuvLQj1RQMJExnAGBAUDXIdcKAPLgjuLIygBYzxbJ7VST8sCN4gAQk1vbMeHXTNA=",
+            signature: "aoFhiTUvlaTwJWgIfZjhMvUOfdhQCsgoYp4ikgP0JMxnzhTRmAYSQA9ymRDwr+K7wNaJjPFnqaj9BZQjTZoVCw==",
+        },
+    ];
+
+    #[test]
+    fn parses_raw_and_friendly_addresses() {
+        for vector in &VECTORS {
+            let raw: TonAddress = vector.address.parse().unwrap();
+            let friendly: TonAddress = vector.friendly.parse().unwrap();
+            assert_eq!(raw, friendly);
+            assert_eq!(raw.to_string(), vector.address);
+        }
+        let mut broken = VECTORS[0].friendly.to_string();
+        broken.replace_range(10..11, "A");
+        assert_eq!(broken.parse::<TonAddress>(), Err(ProofError::InvalidAddress));
+    }
+
+    #[test]
+    fn recognizes_wallet_code() {
+        for vector in &VECTORS {
+            let bytes = general_purpose::STANDARD.decode(vector.state_init).unwrap();
+            let boc = Boc::parse(&bytes).unwrap();
+            let mut root = boc.root();
+            root.load_uint(4).unwrap();
+            let code = root.load_ref().unwrap();
+            assert_eq!(WalletVersion::from_code_hash(&code.hash()), Some(vector.version));
+        }
+    }
+
+    #[test]
+    fn extracts_public_key_from_state_init() {
+        for vector in &VECTORS {
+            let address = vector.address.parse().unwrap();
+            let public_key = public_key_from_state_init(vector.state_init, &address).unwrap();
+            assert_eq!(hex::encode(public_key), vector.public_key);
+        }
+    }
+
+    #[test]
+    fn rejects_state_init_of_another_address() {
+        let address = VECTORS[1].address.parse().unwrap();
+        assert_eq!(
+            public_key_from_state_init(VECTORS[0].state_init, &address),
+            Err(ProofError::AddressMismatch)
+        );
+    }
+
+    #[test]
+    fn verifies_signature() {
+        for vector in &VECTORS {
+            let address = vector.address.parse().unwrap();
+            let public_key = public_key_from_state_init(vector.state_init, &address).unwrap();
+            assert_eq!(
+                verify_signature(&public_key, &address, DOMAIN, TIMESTAMP, PAYLOAD, vector.signature),
+                Ok(())
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_tampered_proof() {
+        let vector = &VECTORS[1];
+        let address = vector.address.parse().unwrap();
+        let public_key = public_key_from_state_init(vector.state_init, &address).unwrap();
+        let verify = |domain, timestamp, payload| {
+            verify_signature(&public_key, &address, domain, timestamp, payload, vector.signature)
+        };
+        assert_eq!(verify("evil.com", TIMESTAMP, PAYLOAD), Err(ProofError::InvalidSignature));
+        assert_eq!(verify(DOMAIN, TIMESTAMP + 1, PAYLOAD), Err(ProofError::InvalidSignature));
+        assert_eq!(verify(DOMAIN, TIMESTAMP, "nonce_43"), Err(ProofError::InvalidSignature));
+
+        // та же подпись от имени другого кошелька
+        let other: TonAddress = VECTORS[0].address.parse().unwrap();
+        assert_eq!(
+            verify_signature(&public_key, &other, DOMAIN, TIMESTAMP, PAYLOAD, vector.signature),
+            Err(ProofError::InvalidSignature)
+        );
+    }
+}
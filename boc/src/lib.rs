@@ -0,0 +1,269 @@
+//! Just enough of the TON bag-of-cells format to read wallet StateInits and message bodies:
+//! deserialization of ordinary cells, their representation hash and a bit reader.
+
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+const BOC_MAGIC: [u8; 4] = [0xb5, 0xee, 0x9c, 0x72];
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum BocError {
+    #[error("Not a bag of cells")]
+    InvalidMagic,
+
+    #[error("Bag of cells is truncated")]
+    Truncated,
+
+    #[error("Bag of cells must have exactly one root")]
+    InvalidRoots,
+
+    #[error("Cell {0} references a cell that isn't after it")]
+    InvalidReference(usize),
+
+    #[error("Exotic cells are not supported")]
+    ExoticCell,
+
+    #[error("Cell has no more data")]
+    CellUnderflow,
+
+    #[error("Only addr_none and addr_std without anycast are supported")]
+    UnsupportedAddress,
+}
+
+#[derive(Debug, Clone)]
+pub struct Cell {
+    /// Data bytes as serialized, including the completion tag of an incomplete last byte.
+    data: Vec<u8>,
+    bit_len: usize,
+    refs: Vec<usize>,
+    hash: [u8; 32],
+    depth: u16,
+}
+
+#[derive(Debug, Clone)]
+pub struct Boc {
+    cells: Vec<Cell>,
+    root: usize,
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], BocError> {
+        let end = self.pos.checked_add(len).ok_or(BocError::Truncated)?;
+        let slice = self.bytes.get(self.pos..end).ok_or(BocError::Truncated)?;
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn uint(&mut self, len: usize) -> Result<usize, BocError> {
+        Ok(self
+            .take(len)?
+            .iter()
+            .fold(0usize, |acc, byte| (acc << 8) | *byte as usize))
+    }
+}
+
+impl Boc {
+    pub fn parse(bytes: &[u8]) -> Result<Self, BocError> {
+        let mut reader = Reader { bytes, pos: 0 };
+        if reader.take(4)? != BOC_MAGIC {
+            return Err(BocError::InvalidMagic);
+        }
+        let flags = reader.take(1)?[0];
+        let has_idx = flags & 0x80 != 0;
+        let ref_size = (flags & 0x07) as usize;
+        let offset_size = reader.uint(1)?;
+        let cell_count = reader.uint(ref_size)?;
+        let root_count = reader.uint(ref_size)?;
+        let _absent = reader.uint(ref_size)?;
+        let _total_size = reader.uint(offset_size)?;
+        if root_count != 1 {
+            return Err(BocError::InvalidRoots);
+        }
+        let root = reader.uint(ref_size)?;
+        if has_idx {
+            reader.take(cell_count * offset_size)?;
+        }
+
+        let mut raw = Vec::with_capacity(cell_count);
+        for index in 0..cell_count {
+            let d1 = reader.take(1)?[0];
+            let d2 = reader.take(1)?[0] as usize;
+            if d1 & 0x08 != 0 {
+                return Err(BocError::ExoticCell);
+            }
+            if d1 & 0x10 != 0 {
+                // хеши уже сериализованы вместе с ячейкой, но мы всё равно пересчитываем их
+                let level = (d1 >> 5) as usize;
+                reader.take((level + 1) * (32 + 2))?;
+            }
+            let data = reader.take(d2.div_ceil(2))?.to_vec();
+            let mut refs = Vec::with_capacity((d1 & 0x07) as usize);
+            for _ in 0..(d1 & 0x07) {
+                let child = reader.uint(ref_size)?;
+                if child <= index || child >= cell_count {
+                    return Err(BocError::InvalidReference(index));
+                }
+                refs.push(child);
+            }
+            let bit_len = if d2.is_multiple_of(2) {
+                data.len() * 8
+            } else {
+                // последний байт дополнен битом 1 и нулями до конца
+                let last = *data.last().ok_or(BocError::Truncated)?;
+                (data.len() - 1) * 8 + 7 - last.trailing_zeros() as usize
+            };
+            raw.push((d2 as u8, data, bit_len, refs));
+        }
+        if root >= cell_count {
+            return Err(BocError::InvalidRoots);
+        }
+
+        // Ссылки указывают только вперёд, поэтому хеши считаются с конца
+        let mut cells: Vec<Option<Cell>> = vec![None; cell_count];
+        for index in (0..cell_count).rev() {
+            let (d2, data, bit_len, refs) = raw[index].clone();
+            let children: Vec<&Cell> = refs
+                .iter()
+                .map(|child| cells[*child].as_ref().ok_or(BocError::InvalidReference(index)))
+                .collect::<Result<_, _>>()?;
+            let depth = children.iter().map(|c| c.depth + 1).max().unwrap_or(0);
+            let mut hasher = Sha256::new();
+            hasher.update([refs.len() as u8, d2]);
+            hasher.update(&data);
+            for child in &children {
+                hasher.update(child.depth.to_be_bytes());
+            }
+            for child in &children {
+                hasher.update(child.hash);
+            }
+            cells[index] = Some(Cell {
+                data,
+                bit_len,
+                refs,
+                hash: hasher.finalize().into(),
+                depth,
+            });
+        }
+        Ok(Self {
+            cells: cells.into_iter().flatten().collect(),
+            root,
+        })
+    }
+
+    pub fn root(&self) -> CellSlice<'_> {
+        self.slice(self.root)
+    }
+
+    fn slice(&self, index: usize) -> CellSlice<'_> {
+        CellSlice {
+            boc: self,
+            cell: &self.cells[index],
+            bit_pos: 0,
+            ref_pos: 0,
+        }
+    }
+}
+
+/// Sequential reader over the bits and references of one cell.
+pub struct CellSlice<'a> {
+    boc: &'a Boc,
+    cell: &'a Cell,
+    bit_pos: usize,
+    ref_pos: usize,
+}
+
+impl<'a> CellSlice<'a> {
+    /// Representation hash of the whole cell, regardless of what was already read.
+    pub fn hash(&self) -> [u8; 32] {
+        self.cell.hash
+    }
+
+    pub fn load_bit(&mut self) -> Result<bool, BocError> {
+        if self.bit_pos >= self.cell.bit_len {
+            return Err(BocError::CellUnderflow);
+        }
+        let byte = self.cell.data[self.bit_pos / 8];
+        let bit = byte >> (7 - self.bit_pos % 8) & 1 == 1;
+        self.bit_pos += 1;
+        Ok(bit)
+    }
+
+    pub fn load_uint(&mut self, bits: usize) -> Result<u64, BocError> {
+        let mut value = 0u64;
+        for _ in 0..bits {
+            value = (value << 1) | self.load_bit()? as u64;
+        }
+        Ok(value)
+    }
+
+    pub fn load_bytes<const N: usize>(&mut self) -> Result<[u8; N], BocError> {
+        let mut bytes = [0u8; N];
+        for byte in bytes.iter_mut() {
+            *byte = self.load_uint(8)? as u8;
+        }
+        Ok(bytes)
+    }
+
+    /// `VarUInteger 16`, the encoding of TON and jetton amounts: a 4-bit byte length,
+    /// then that many bytes.
+    pub fn load_coins(&mut self) -> Result<u128, BocError> {
+        let len = self.load_uint(4)? as usize;
+        let mut value = 0u128;
+        for _ in 0..len {
+            value = (value << 8) | self.load_uint(8)? as u128;
+        }
+        Ok(value)
+    }
+
+    /// `MsgAddress` as a raw `workchain:<hex>` address, `None` for `addr_none`.
+    pub fn load_address(&mut self) -> Result<Option<String>, BocError> {
+        match self.load_uint(2)? {
+            0b00 => Ok(None),
+            0b10 => {
+                // anycast:(Maybe Anycast) — в кошельках не встречается
+                if self.load_bit()? {
+                    return Err(BocError::UnsupportedAddress);
+                }
+                let workchain = self.load_uint(8)? as u8 as i8;
+                let hash = self.load_bytes::<32>()?;
+                let hash: String = hash.iter().map(|byte| format!("{:02x}", byte)).collect();
+                Ok(Some(format!("{}:{}", workchain, hash)))
+            }
+            _ => Err(BocError::UnsupportedAddress),
+        }
+    }
+
+    /// The rest of the cell and the chain of first references after it, byte by byte:
+    /// the snake format of text comments.
+    pub fn load_snake_bytes(mut self) -> Result<Vec<u8>, BocError> {
+        let mut bytes = Vec::new();
+        loop {
+            while self.remaining_bits() >= 8 {
+                bytes.push(self.load_uint(8)? as u8);
+            }
+            if self.ref_pos >= self.cell.refs.len() {
+                return Ok(bytes);
+            }
+            self = self.load_ref()?;
+        }
+    }
+
+    pub fn remaining_bits(&self) -> usize {
+        self.cell.bit_len - self.bit_pos
+    }
+
+    pub fn load_ref(&mut self) -> Result<CellSlice<'a>, BocError> {
+        let child = *self
+            .cell
+            .refs
+            .get(self.ref_pos)
+            .ok_or(BocError::CellUnderflow)?;
+        self.ref_pos += 1;
+        Ok(self.boc.slice(child))
+    }
+}
@@ -0,0 +1,115 @@
+use chrono::Utc;
+use db::{Channel, subscriptions};
+use sea_orm::sea_query::Expr;
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, PaginatorTrait, QueryFilter, TransactionTrait};
+use serde_json::json;
+use std::time::Duration;
+use teloxide::prelude::*;
+use teloxide::types::UserId;
+use webhooks::WebhookEvent;
+
+use crate::ui::BotError;
+
+/// How often ended subscriptions are looked for.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Expires subscriptions whose period is over and removes their users from the channel
+/// unless another paid period has already started.
+pub async fn run(bot: Bot, db: DatabaseConnection, shutdown: ops::Shutdown) {
+    let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+    loop {
+        tokio::select! {
+            _ = interval.tick() => {}
+            _ = shutdown.wait() => return,
+        }
+        if let Err(err) = sweep(&bot, &db).await {
+            tracing::error!("❌ Не удалось обработать истёкшие подписки: {}", err);
+        }
+    }
+}
+
+async fn sweep(bot: &Bot, db: &DatabaseConnection) -> Result<(), BotError> {
+    let now = Utc::now();
+    let ended = subscriptions::Entity::find()
+        .filter(subscriptions::Column::Status.eq("active"))
+        .filter(subscriptions::Column::TimeTo.lte(now))
+        .all(db)
+        .await?;
+
+    for subscription in ended {
+        let txn = db.begin().await?;
+        let expired = subscriptions::Entity::update_many()
+            .col_expr(subscriptions::Column::Status, Expr::value("expired"))
+            .col_expr(subscriptions::Column::UpdatedAt, Expr::value(now))
+            .filter(subscriptions::Column::TelegramId.eq(subscription.telegram_id))
+            .filter(subscriptions::Column::ChannelId.eq(subscription.channel_id))
+            .filter(subscriptions::Column::TransactionId.eq(subscription.transaction_id))
+            .filter(subscriptions::Column::Status.eq("active"))
+            .exec(&txn)
+            .await?;
+        // Уже обработана параллельно
+        if expired.rows_affected == 0 {
+            continue;
+        }
+        webhooks::enqueue(
+            &txn,
+            subscription.channel_id,
+            WebhookEvent::SubscriptionExpired,
+            json!({
+                "channel_id": subscription.channel_id,
+                "telegram_id": subscription.telegram_id,
+                "time_from": subscription.time_from,
+                "time_to": subscription.time_to,
+            }),
+        )
+        .await?;
+        txn.commit().await?;
+
+        // Продление уже оплачено — следующая подписка начинается ровно здесь
+        let renewed = subscriptions::Entity::find()
+            .filter(subscriptions::Column::TelegramId.eq(subscription.telegram_id))
+            .filter(subscriptions::Column::ChannelId.eq(subscription.channel_id))
+            .filter(subscriptions::Column::Status.eq("active"))
+            .filter(subscriptions::Column::TimeTo.gt(now))
+            .count(db)
+            .await?
+            > 0;
+        let is_owner = Channel::find_by_id(subscription.channel_id)
+            .one(db)
+            .await?
+            .is_some_and(|channel| channel.owner_telegram_id == subscription.telegram_id);
+        if renewed || is_owner {
+            continue;
+        }
+
+        if let Err(err) = remove_member(bot, subscription.channel_id, subscription.telegram_id).await {
+            tracing::error!(
+                "❌ Не удалось удалить {} из канала {}: {}",
+                subscription.telegram_id, subscription.channel_id, err
+            );
+            continue;
+        }
+        webhooks::enqueue(
+            db,
+            subscription.channel_id,
+            WebhookEvent::MemberRemoved,
+            json!({
+                "channel_id": subscription.channel_id,
+                "telegram_id": subscription.telegram_id,
+                "reason": "subscription_expired",
+            }),
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+/// Kicks the user out without leaving them banned, so they can come back after paying again.
+async fn remove_member(bot: &Bot, channel_id: i64, telegram_id: i64) -> Result<(), BotError> {
+    let user_id = UserId(telegram_id as u64);
+    bot.ban_chat_member(ChatId(channel_id), user_id).await?;
+    bot.unban_chat_member(ChatId(channel_id), user_id)
+        .only_if_banned(true)
+        .await?;
+    Ok(())
+}
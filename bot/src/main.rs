@@ -1,4 +1,5 @@
 // mod section;
+mod expiry;
 mod qr;
 mod ton;
 mod ui;
@@ -6,73 +7,117 @@ mod ui;
 
 use chrono::Utc;
 use db::{Channel, ChannelModel, User, UserModel};
-use dotenv::dotenv;
 use sea_orm::{ActiveModelTrait, Database, DatabaseConnection, EntityTrait, Set};
+use std::ops::ControlFlow;
 use std::sync::Arc;
-use std::env;
+use std::time::Instant;
+use tracing::Instrument;
 use teloxide::dispatching::dialogue::Storage;
 use teloxide::dispatching::dialogue::serializer::{Bincode, Json};
 use teloxide::types::ChatKind;
 use teloxide::{
     dispatching::{
-        DpHandlerDescription,
+        DpHandlerDescription, UpdateHandler,
         dialogue::{ErasedStorage, RedisStorage},
     },
     prelude::*,
     types::{ChatMemberKind, ChatMemberStatus},
     utils::command::BotCommands,
 };
-use ui::{BotError, Commands, GateCryptoAddress, PaymentGateway, State};
+use ui::{BotError, Commands, GateCryptoAddress, PaymentGateway, State, WalletLinkPage};
 
 type DialogueStorage = std::sync::Arc<ErasedStorage<State>>;
 
+struct BotConfig {
+    database: config::DatabaseConfig,
+    redis: config::RedisConfig,
+    ton: config::TonConfig,
+    telegram: config::TelegramConfig,
+    payment_page: config::PaymentPageConfig,
+    health: config::HealthConfig,
+    log: config::LogConfig,
+    /// Page that links a TON wallet to the Telegram account, `payments.wallet_link_url`.
+    wallet_link_url: String,
+}
+
 #[tokio::main]
 async fn main() -> Result<(), BotError> {
-    pretty_env_logger::init();
-    dotenv().ok();
-    log::info!("Starting throw dice bot...");
-    let gate_crypto_address = GateCryptoAddress(Arc::new(
-        env::var("GATE_CRYPTO_ADDRESS").expect("GATE_CRYPTO_ADDRESS must be set"),
-    ));
-    let payment_gateway = PaymentGateway(Arc::new(
-        env::var("PAYMENT_GATEWAY").expect("PAYMENT_GATEWAY must be set"),
-    ));
-    let token = env::var("BOT_TOKEN").expect("BOT_TOKEN must be set");
-    let connection_string = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
-    let dragonfly_password =
-        env::var("DRAGONFLY_PASSWORD").expect("DRAGONFLY_PASSWORD must be set");
-    log::info!("Establishing db connection...");
-
-    let db: DatabaseConnection = Database::connect(connection_string).await?;
-    let redis_url = format!("redis://:{}@127.0.0.1:6379", dragonfly_password);
-    // let client = Client::open(format!("redis://:{}@127.0.0.1:6379", dragonfly_password))?;
-    // let manager = client.get_multiplexed_tokio_connection().await?;
-    log::info!("Db connection esteblished!");
-    let bot = Bot::new(token);
+    let config = config::load_or_exit("bot", |r| BotConfig {
+        database: config::DatabaseConfig::read(r),
+        redis: config::RedisConfig::read(r),
+        ton: config::TonConfig::read(r),
+        telegram: config::TelegramConfig::read(r),
+        payment_page: config::PaymentPageConfig::read(r),
+        health: config::HealthConfig::read(r),
+        log: config::LogConfig::read(r),
+        wallet_link_url: r.required("payments.wallet_link_url"),
+    });
+    ops::logging::init(config.log.json);
+    tracing::info!("Starting throw dice bot...");
+    let gate_crypto_address = GateCryptoAddress(Arc::new(config.ton.gate_address));
+    let payment_gateway = PaymentGateway(Arc::new(config.payment_page.gateway_url));
+    let wallet_link_page = WalletLinkPage(Arc::new(config.wallet_link_url));
+    tracing::info!("Establishing db connection...");
+
+    let db: DatabaseConnection = Database::connect(config.database.url).await?;
+    if config.database.migrate {
+        tracing::info!("Applying pending migrations");
+        migration::up_on_boot(&db).await?;
+    }
+    let redis_url = config.redis.url();
+    let redis = redis::Client::open(redis_url.clone())?
+        .get_multiplexed_tokio_connection()
+        .await?;
+    tracing::info!("Db connection esteblished!");
+    let bot = Bot::new(config.telegram.bot_token);
     // Bincode works, i checked
     let dialogue: DialogueStorage = RedisStorage::open(&redis_url.clone(), Json)
         .await
         .unwrap()
         .erase();
 
-    Dispatcher::builder(bot, handler())
-        .enable_ctrlc_handler()
+    let shutdown = ops::Shutdown::listen();
+    ops::metrics::install();
+    ops::health::spawn(
+        config.health.port,
+        ops::Health::new(
+            vec![ops::Check::Database(db.clone()), ops::Check::Redis(redis.clone())],
+            shutdown.clone(),
+        ),
+    );
+    let expiry = tokio::spawn(expiry::run(bot.clone(), db.clone(), shutdown.clone()));
+
+    let mut dispatcher = Dispatcher::builder(bot, handler())
         .dependencies(dptree::deps![
             db.clone(),
             gate_crypto_address.clone(),
             payment_gateway.clone(),
+            wallet_link_page.clone(),
+            redis,
             dialogue
         ])
-        .build()
-        .dispatch()
-        .await;
+        .build();
+    // Диспетчер дообрабатывает уже полученные обновления и только потом завершается
+    let token = dispatcher.shutdown_token();
+    tokio::spawn(async move {
+        shutdown.wait().await;
+        match token.shutdown() {
+            Ok(stopped) => stopped.await,
+            Err(err) => tracing::warn!("Dispatcher can't be stopped: {}", err),
+        }
+    });
+    dispatcher.dispatch().await;
+    // Начатая проверка подписок тоже должна закончиться
+    let _ = expiry.await;
+    tracing::info!("Bot stopped");
 
     Ok(())
 }
 
 fn handler() -> Handler<'static, DependencyMap, Result<(), BotError>, DpHandlerDescription> {
     dptree::entry()
-        .branch(
+        .branch(timed(
+            "chat_member",
             Update::filter_my_chat_member()
                 .filter_map(|upd: ChatMemberUpdated| {
                     match (&upd.old_chat_member.kind, &upd.new_chat_member.kind) {
@@ -81,17 +126,48 @@ fn handler() -> Handler<'static, DependencyMap, Result<(), BotError>, DpHandlerD
                     }
                 })
                 .endpoint(handle_chat_member_update),
-        )
-        .branch(ui::info::schema())
-        .branch(ui::pay::schema())
-        .branch(ui::price::schema())
-        .branch(
+        ))
+        .branch(timed("info", ui::info::schema()))
+        .branch(timed("pay", ui::pay::schema()))
+        .branch(timed("price", ui::price::schema()))
+        .branch(timed("refund", ui::refund::schema()))
+        .branch(timed("slug", ui::slug::schema()))
+        .branch(timed("promo", ui::promo::schema()))
+        .branch(timed("wallet", ui::wallet::schema()))
+        .branch(timed(
+            "commands",
             Update::filter_message()
                 .filter_command::<Commands>()
                 .branch(dptree::case![Commands::Start].endpoint(handle_start_command))
                 .branch(dptree::case![Commands::Help].endpoint(handle_help_command))
                 .endpoint(not_implemented),
-        )
+        ))
+}
+
+/// Handles updates inside an `update` span naming `branch` and records
+/// `bot_update_duration_seconds` for the ones it handles; updates it passes on to the next
+/// branch aren't counted.
+fn timed(branch: &'static str, handler: UpdateHandler<BotError>) -> UpdateHandler<BotError> {
+    let description = handler.description().clone();
+    dptree::from_fn_with_description(description, move |update, cont| {
+        let handler = handler.clone();
+        async move {
+            let started = Instant::now();
+            let result = handler
+                .execute(update, cont)
+                .instrument(tracing::info_span!("update", branch))
+                .await;
+            if let ControlFlow::Break(outcome) = &result {
+                metrics::histogram!(
+                    "bot_update_duration_seconds",
+                    "branch" => branch,
+                    "outcome" => if outcome.is_ok() { "ok" } else { "error" },
+                )
+                .record(started.elapsed());
+            }
+            result
+        }
+    })
 }
 
 async fn handle_chat_member_update(
@@ -168,7 +244,7 @@ async fn handle_chat_member_update(
             }
         }
         None => {
-            log::info!("В чате {} нет владельца", chat_id);
+            tracing::info!("В чате {} нет владельца", chat_id);
             bot.send_message(
                 update.chat.id,
                 format!(
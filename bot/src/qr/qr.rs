@@ -14,8 +14,9 @@ pub fn generate_qr_code(
     // Кодируем payload в base64
     let payload_base64 = STANDARD.encode(raw_payload.as_bytes());
 
-    // Комментарий (опционально)
-    let text = format!("Fee Split Transfer for Krypton transaction {}", transaction_id);
+    // Комментарий (опционально). Платёж идёт на адрес Krypton, комиссия и доля владельца
+    // уже посчитаны в payment_transactions (platform_fee / owner_amount)
+    let text = format!("Krypton payment for transaction {}", transaction_id);
 
     // Финальный deeplink
     let ton_link = format!(
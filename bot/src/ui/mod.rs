@@ -1,6 +1,10 @@
 pub mod price;
 pub mod pay;
 pub mod info;
+pub mod refund;
+pub mod slug;
+pub mod promo;
+pub mod wallet;
 
 use std::{convert::Infallible, fmt::{Display, Formatter}, sync::Arc};
 use thiserror::Error;
@@ -18,7 +22,17 @@ pub enum Commands {
     #[command(description="Show info about owned Telegram channel")]
     Info,
     #[command(description="Pay for channel subscription")]
-    Pay(String)
+    Pay(String),
+    #[command(description="Review refund requests for owned Telegram channels")]
+    Refunds,
+    #[command(description="Link a TON wallet to your Telegram account")]
+    LinkWallet,
+    #[command(description="Set a public name for owned Telegram channel page")]
+    SetSlug,
+    #[command(description="Create a promo code for owned Telegram channel")]
+    NewPromo,
+    #[command(description="List and deactivate promo codes of owned Telegram channels")]
+    PromoCodes
 }
 
 #[derive(Clone, Default, Debug, serde::Serialize, serde::Deserialize)]
@@ -28,6 +42,8 @@ pub enum State {
     Price(price::State),
     Pay(pay::State),
     Info(info::State),
+    Slug(slug::State),
+    Promo(promo::State),
 }
 
 pub type UserDialogue = Dialogue<State, ErasedStorage<State>>;
@@ -48,6 +64,12 @@ pub enum BotError {
 
     #[error("RedisStorage error: {0}")]
     ErasedStorage(#[from] Box<dyn std::error::Error + Send + Sync>),
+
+    #[error("Ledger error: {0}")]
+    Ledger(#[from] ledger::LedgerError),
+
+    #[error("Redis error: {0}")]
+    Redis(#[from] redis::RedisError),
 }
 
 #[derive(Clone)]
@@ -56,6 +78,9 @@ pub struct GateCryptoAddress(pub Arc<String>);
 #[derive(Clone)]
 pub struct PaymentGateway(pub Arc<String>);
 
+#[derive(Clone)]
+pub struct WalletLinkPage(pub Arc<String>);
+
 impl Display for GateCryptoAddress {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         self.0.fmt(f)
@@ -66,4 +91,10 @@ impl Display for PaymentGateway {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         self.0.fmt(f)
     }
+}
+
+impl Display for WalletLinkPage {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
 }
\ No newline at end of file
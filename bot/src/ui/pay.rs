@@ -1,14 +1,23 @@
-use super::{BotError, Commands, PaymentGateway, State as GlobalState, UserDialogue};
-use chrono::Utc;
-use db::{Channel, TransactionModel};
+use super::{BotError, Commands, GateCryptoAddress, PaymentGateway, State as GlobalState, UserDialogue};
+use chrono::{Duration, Utc};
+use events::payment_status::{PaymentStatusEvent, publish_payment_status};
+use redis::aio::MultiplexedConnection;
+use db::{
+    Channel, FeeLedgerModel, Transaction, TransactionModel,
+    channel::{SUBSCRIPTION_CURRENCY, SUBSCRIPTION_PERIOD_DAYS},
+    promo_code,
+    settings::PlatformFee,
+    subscriptions, transaction,
+};
 use sea_orm::{
-    ActiveModelTrait, ActiveValue::Set, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter,
+    ActiveModelTrait, ActiveValue::Set, ColumnTrait, ConnectionTrait, DatabaseConnection, EntityTrait,
+    QueryFilter, QueryOrder, SqlErr, TransactionTrait, sea_query::Expr,
 };
 use teloxide::{
     Bot,
     dispatching::{UpdateHandler, dialogue::ErasedStorage},
     prelude::*,
-    types::{InlineKeyboardButton, InlineKeyboardMarkup, Message},
+    types::{InlineKeyboardButton, InlineKeyboardMarkup, Message, MessageId},
 };
 // Define states for the second dialogue (Pay)
 #[derive(Clone, Default, Debug, serde::Serialize, serde::Deserialize)]
@@ -18,6 +27,9 @@ pub enum State {
     SelectChannel {
         channel_id: Option<i64>,
     },
+    EnterPromoCode {
+        channel_id: i64,
+    },
     Pay {
         channel_id: i64,
         channel_name: String,
@@ -25,27 +37,138 @@ pub enum State {
     PaymentStatus,
 }
 
+const SKIP_PROMO_CODE: &str = "promo_skip";
+/// How long a payment link accepts payments; later transfers are refunded.
+const PAYMENT_TTL: Duration = Duration::hours(1);
+
+fn skip_promo_code_keyboard() -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::new(vec![vec![InlineKeyboardButton::callback(
+        "Skip",
+        SKIP_PROMO_CODE,
+    )]])
+}
+
+fn extend_subscription_keyboard(channel_id: i64) -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::new(vec![vec![InlineKeyboardButton::callback(
+        "Extend",
+        format!("extend_{}", channel_id),
+    )]])
+}
+
+/// The user's subscription to the channel that is still running, the latest one if several are.
+async fn active_subscription(
+    db: &DatabaseConnection,
+    telegram_id: i64,
+    channel_id: i64,
+) -> Result<Option<subscriptions::Model>, sea_orm::DbErr> {
+    subscriptions::Entity::find()
+        .filter(subscriptions::Column::TelegramId.eq(telegram_id))
+        .filter(subscriptions::Column::ChannelId.eq(channel_id))
+        .filter(subscriptions::Column::Status.eq("active"))
+        .filter(subscriptions::Column::TimeTo.gt(Utc::now()))
+        .order_by_desc(subscriptions::Column::TimeTo)
+        .one(db)
+        .await
+}
+
+/// The user's open transaction for the channel whose link still accepts payments.
+async fn open_transaction(
+    db: &DatabaseConnection,
+    telegram_id: i64,
+    channel_id: i64,
+) -> Result<Option<transaction::Model>, sea_orm::DbErr> {
+    Transaction::find()
+        .filter(transaction::Column::TelegramId.eq(telegram_id))
+        .filter(transaction::Column::ChannelId.eq(channel_id))
+        .filter(transaction::Column::Status.eq("active"))
+        .filter(transaction::Column::ExpiresAt.gt(Utc::now()))
+        .one(db)
+        .await
+}
+
+/// Cancels the user's active transactions for the channel, expired or not: the new one
+/// takes their place, and the promo code uses they took are given back. A transfer that
+/// still arrives for them is refunded. Returns the ids of the cancelled transactions.
+async fn cancel_superseded(
+    txn: &impl ConnectionTrait,
+    telegram_id: i64,
+    channel_id: i64,
+) -> Result<Vec<i64>, sea_orm::DbErr> {
+    let cancelled = Transaction::update_many()
+        .col_expr(transaction::Column::Status, Expr::value("cancelled"))
+        .filter(transaction::Column::TelegramId.eq(telegram_id))
+        .filter(transaction::Column::ChannelId.eq(channel_id))
+        .filter(transaction::Column::Status.eq("active"))
+        .exec_with_returning(txn)
+        .await?;
+    for promo_code_id in cancelled.iter().filter_map(|tx| tx.promo_code_id) {
+        promo_code::release(txn, promo_code_id).await?;
+    }
+    Ok(cancelled.into_iter().map(|tx| tx.id).collect())
+}
+
+/// Tells the payment pages of cancelled transactions that they are over, the way
+/// ton-watcher does for paid and expired ones. The cancellation is already saved, so a
+/// failure here is only logged.
+async fn publish_cancelled(redis: &mut MultiplexedConnection, transaction_ids: &[i64]) {
+    let event = PaymentStatusEvent {
+        status: "cancelled".to_string(),
+        verdict: None,
+        terminal: true,
+    };
+    for &transaction_id in transaction_ids {
+        if let Err(err) = publish_payment_status(redis, transaction_id, &event).await {
+            tracing::error!("❌ Не удалось опубликовать статус транзакции {}: {}", transaction_id, err);
+        }
+    }
+}
+
+/// Sends the link of the transaction the user already has open for the channel.
+async fn resend_open_link(
+    bot: &Bot,
+    dialogue: &UserDialogue,
+    payment_gateway: &PaymentGateway,
+    chat_id: ChatId,
+    channel: db::channel::Model,
+    open: transaction::Model,
+) -> Result<(), BotError> {
+    let expires_at = open.expires_at.unwrap_or_default();
+    bot.send_message(
+        chat_id,
+        format!(
+            "You already have a payment for {:.2} {} in progress. Please follow this link {}/{} to proceed the action until {}. Thank you!",
+            open.price,
+            open.currency,
+            payment_gateway,
+            open.public_id,
+            expires_at.format("%H:%M UTC")
+        ),
+    )
+    .await?;
+    dialogue
+        .update(GlobalState::Pay(State::Pay {
+            channel_id: channel.channel_id,
+            channel_name: channel.title,
+        }))
+        .await?;
+    Ok(())
+}
+
 async fn start_pay_dialogue(
     bot: Bot,
     msg: Message,
     dialogue: UserDialogue,
 ) -> Result<(), BotError> {
-    match msg.text() {
-        Some(messate) => {
-            if let Some(payload) = messate.strip_prefix("/start ") {
-                if let Some(channel_id_str) = payload.strip_prefix("pay_channel_") {
-                    if let Ok(channel_id) = channel_id_str.parse::<i64>() {
-                        dialogue
-                            .update(GlobalState::Pay(State::SelectChannel {
-                                channel_id: channel_id.into(),
-                            }))
-                            .await?;
-                        return Ok(());
-                    }
-                }
-            }
-        }
-        None => {}
+    if let Some(payload) = msg.text().and_then(|text| text.strip_prefix("/start "))
+        && let Some(channel_id_str) = payload.strip_prefix("pay_channel_")
+        && let Ok(channel_id) = channel_id_str.parse::<i64>()
+    {
+        dialogue
+            .update(GlobalState::Pay(State::SelectChannel {
+                channel_id: channel_id.into(),
+            }))
+            .await?;
+        return Ok(());
     }
     bot.send_message(
         msg.chat.id,
@@ -64,24 +187,20 @@ async fn handle_pay_channel_selection(
     channel_id: Option<i64>,
     db: DatabaseConnection,
 ) -> Result<(), BotError> {
-    let mut channels: Vec<db::channel::Model> = vec![];
-
-    if let Some(channel_id) = channel_id {
-        channels = Channel::find_by_id(channel_id).all(&db).await?;
+    let channels: Vec<db::channel::Model> = if let Some(channel_id) = channel_id {
+        Channel::find_by_id(channel_id).all(&db).await?
+    } else if let Some(name) = msg.text() {
+        Channel::find()
+            .filter(db::channel::Column::Title.starts_with(name))
+            .all(&db)
+            .await?
     } else {
-        if let Some(name) = msg.text() {
-            channels = Channel::find()
-                .filter(db::channel::Column::Title.starts_with(name))
-                .all(&db)
-                .await?;
-        } else {
-            bot.send_message(msg.chat.id, "Please, enter valid channel name")
-                .await?;
-            return Ok(());
-        }
-    }
+        bot.send_message(msg.chat.id, "Please, enter valid channel name")
+            .await?;
+        return Ok(());
+    };
 
-    if channels.len() == 0 {
+    if channels.is_empty() {
         bot.send_message(msg.chat.id, "Channels not found").await?;
         return Ok(());
     }
@@ -113,63 +232,291 @@ async fn handle_pay_button(
     let message = q.message.unwrap();
     let chat_id = message.chat().id;
     let message_id = message.id();
-    let telegram_id = q.from.id.0.try_into().unwrap();
-    if let Some(data) = q.data {
-        if let Some(channel_id_str) = data.strip_prefix("channel_") {
-            if let Ok(channel_id) = channel_id_str.parse::<i64>() {
-                if let Some(channel) = Channel::find_by_id(channel_id).one(&db).await? {
-                    if channel.crypto_address.is_none() {
-                        bot.send_message(chat_id, "Unfortunately, current channel doesn't have a valid crypto address valid")
-                            .await?;
-                        return Ok(());
-                    }
-                    bot.delete_message(chat_id, message_id).await?;
-                    let monthly_price = channel.monthly_price.unwrap();
-                    let wallet_address = channel.crypto_address.unwrap();
-                    let date_now = Utc::now().into();
-                    let transaction = TransactionModel {
-                        telegram_id: Set(telegram_id),
-                        channel_id: Set(channel_id),
-                        price: Set(monthly_price),
-                        status: Set("active".to_string()),
-                        created_at: Set(date_now),
-                        wallet_address: Set(wallet_address.clone()),
-                        message_id: Set(message_id.0.into()),
-                        chat_id: Set(chat_id.0),
-                        currency: Set("USDT".to_string()),
-                        ..Default::default()
-                    };
-                    let transaction_id = transaction.insert(&db).await?;
-                    /*
-                    let transaction_id = insert_result.id;
-                    let event = PaymentEvent {
-                        transaction_id,
-                        telegram_id,
-                        channel_id,
-                        chat_id: chat_id.0,
-                        price: monthly_price,
-                        wallet_address: wallet_address.clone(),
-                    };
-                    send_payment_event(&event, &mut redis_manager).await?;
-                    */
-                    let link = format!("{}/{}", payment_gateway, transaction_id.id);
-                    let message = format!(
-                        "Please follow this link {} to proceed the action. Thank you!",
-                        link
-                    );
-                    bot.send_message(chat_id, message).await?;
-                    dialogue
-                        .update(GlobalState::Pay(State::Pay {
-                            channel_id,
-                            channel_name: channel.title,
-                        }))
-                        .await?;
-                } else {
-                    bot.send_message(chat_id, "Channel not found").await?;
-                }
+    let telegram_id: i64 = q.from.id.0.try_into().unwrap();
+    let data = q.data.as_deref().unwrap_or_default();
+    // extend_ приходит с кнопки продления, когда подписка ещё действует
+    let (extend, channel_id_str) = match data.strip_prefix("extend_") {
+        Some(channel_id_str) => (true, Some(channel_id_str)),
+        None => (false, data.strip_prefix("channel_")),
+    };
+    if let Some(channel_id_str) = channel_id_str
+        && let Ok(channel_id) = channel_id_str.parse::<i64>()
+    {
+        if let Some(channel) = Channel::find_by_id(channel_id).one(&db).await? {
+            if channel.crypto_address.is_none() || channel.monthly_price.is_none() {
+                bot.send_message(chat_id, "Unfortunately, current channel doesn't have a valid crypto address valid")
+                    .await?;
+                return Ok(());
+            }
+            if !extend
+                && let Some(subscription) = active_subscription(&db, telegram_id, channel_id).await?
+            {
+                bot.send_message(
+                    chat_id,
+                    format!(
+                        "You are already subscribed to {} until {}. Paying now adds {} days after that date.",
+                        channel.title,
+                        subscription.time_to.format("%Y-%m-%d %H:%M UTC"),
+                        SUBSCRIPTION_PERIOD_DAYS
+                    ),
+                )
+                .reply_markup(extend_subscription_keyboard(channel_id))
+                .await?;
+                return Ok(());
+            }
+            bot.delete_message(chat_id, message_id).await?;
+            // Повторное нажатие не плодит счета: отправляем ссылку на ещё открытый
+            if let Some(open) = open_transaction(&db, telegram_id, channel_id).await? {
+                return resend_open_link(&bot, &dialogue, &payment_gateway, chat_id, channel, open).await;
+            }
+            bot.send_message(
+                chat_id,
+                "Enter a promo code if you have one, or press Skip:",
+            )
+            .reply_markup(skip_promo_code_keyboard())
+            .await?;
+            dialogue
+                .update(GlobalState::Pay(State::EnterPromoCode { channel_id }))
+                .await?;
+        } else {
+            bot.send_message(chat_id, "Channel not found").await?;
+        }
+    }
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn handle_promo_code_input(
+    bot: Bot,
+    msg: Message,
+    channel_id: i64,
+    db: DatabaseConnection,
+    dialogue: UserDialogue,
+    payment_gateway: PaymentGateway,
+    gate_crypto_address: GateCryptoAddress,
+    mut redis: MultiplexedConnection,
+) -> Result<(), BotError> {
+    let Some(code) = msg.text() else {
+        bot.send_message(msg.chat.id, "Please, enter a promo code or press Skip")
+            .reply_markup(skip_promo_code_keyboard())
+            .await?;
+        return Ok(());
+    };
+    let promo = promo_code::find_for_channel(&db, code, channel_id).await?;
+    match promo {
+        Some(promo) if promo.is_redeemable_at(Utc::now()) => {
+            let telegram_id = msg.from.as_ref().unwrap().id.0.try_into().unwrap();
+            create_payment(
+                &bot,
+                &db,
+                &mut redis,
+                &dialogue,
+                &payment_gateway,
+                &gate_crypto_address,
+                PaymentRequest {
+                    chat_id: msg.chat.id,
+                    message_id: msg.id,
+                    telegram_id,
+                    channel_id,
+                    promo: Some(promo),
+                },
+            )
+            .await
+        }
+        _ => {
+            bot.send_message(
+                msg.chat.id,
+                "This promo code is not valid. Try another one or press Skip:",
+            )
+            .reply_markup(skip_promo_code_keyboard())
+            .await?;
+            Ok(())
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn handle_skip_promo_code(
+    bot: Bot,
+    q: CallbackQuery,
+    channel_id: i64,
+    db: DatabaseConnection,
+    dialogue: UserDialogue,
+    payment_gateway: PaymentGateway,
+    gate_crypto_address: GateCryptoAddress,
+    mut redis: MultiplexedConnection,
+) -> Result<(), BotError> {
+    bot.answer_callback_query(q.id).await?;
+    if q.data.as_deref() != Some(SKIP_PROMO_CODE) {
+        return Ok(());
+    }
+    let message = q.message.unwrap();
+    let chat_id = message.chat().id;
+    bot.delete_message(chat_id, message.id()).await?;
+    create_payment(
+        &bot,
+        &db,
+        &mut redis,
+        &dialogue,
+        &payment_gateway,
+        &gate_crypto_address,
+        PaymentRequest {
+            chat_id,
+            message_id: message.id(),
+            telegram_id: q.from.id.0.try_into().unwrap(),
+            channel_id,
+            promo: None,
+        },
+    )
+    .await
+}
+
+struct PaymentRequest {
+    chat_id: ChatId,
+    message_id: MessageId,
+    telegram_id: i64,
+    channel_id: i64,
+    promo: Option<promo_code::Model>,
+}
+
+/// Creates the payment transaction (redeeming the promo code, cancelling the transactions
+/// it supersedes and recording the fee split in the same db transaction) and sends the
+/// payment link to the user.
+/// The payment goes to the Krypton gate address, the owner's crypto address receives
+/// `owner_amount` later.
+async fn create_payment(
+    bot: &Bot,
+    db: &DatabaseConnection,
+    redis: &mut MultiplexedConnection,
+    dialogue: &UserDialogue,
+    payment_gateway: &PaymentGateway,
+    gate_crypto_address: &GateCryptoAddress,
+    request: PaymentRequest,
+) -> Result<(), BotError> {
+    let PaymentRequest {
+        chat_id,
+        message_id,
+        telegram_id,
+        channel_id,
+        promo,
+    } = request;
+    let Some(channel) = Channel::find_by_id(channel_id).one(db).await? else {
+        bot.send_message(chat_id, "Channel not found").await?;
+        return Ok(());
+    };
+    let (Some(monthly_price), Some(_)) = (channel.monthly_price, &channel.crypto_address) else {
+        bot.send_message(chat_id, "Unfortunately, current channel doesn't have a valid crypto address valid")
+            .await?;
+        return Ok(());
+    };
+
+    let price = match &promo {
+        Some(promo) => match promo.apply(monthly_price) {
+            Some(price) => price,
+            None => {
+                bot.send_message(chat_id, "This promo code can't be applied to the channel price.")
+                    .reply_markup(skip_promo_code_keyboard())
+                    .await?;
+                return Ok(());
             }
+        },
+        None => monthly_price,
+    };
+    let fee = PlatformFee::load(db).await?;
+    let split = fee.split(price);
+
+    let date_now = Utc::now();
+    let txn = db.begin().await?;
+    // Сначала отменяем старый счёт: попытка промокода, занятая им, снова свободна
+    let cancelled = cancel_superseded(&txn, telegram_id, channel_id).await?;
+    if let Some(promo) = &promo
+        && !promo_code::redeem(&txn, promo.id, date_now).await?
+    {
+        txn.rollback().await?;
+        bot.send_message(
+            chat_id,
+            "Sorry, this promo code has just run out or expired. Try another one or press Skip:",
+        )
+        .reply_markup(skip_promo_code_keyboard())
+        .await?;
+        return Ok(());
+    }
+    let transaction = TransactionModel {
+        telegram_id: Set(Some(telegram_id)),
+        channel_id: Set(Some(channel_id)),
+        price: Set(price),
+        original_price: Set(promo.as_ref().map(|_| monthly_price)),
+        promo_code_id: Set(promo.as_ref().map(|p| p.id)),
+        status: Set("active".to_string()),
+        created_at: Set(date_now),
+        expires_at: Set(Some(date_now + PAYMENT_TTL)),
+        platform_fee: Set(split.fee),
+        owner_amount: Set(split.owner_amount),
+        wallet_address: Set(gate_crypto_address.0.to_string()),
+        message_id: Set(Some(message_id.0.into())),
+        chat_id: Set(Some(chat_id.0)),
+        currency: Set(SUBSCRIPTION_CURRENCY.to_string()),
+        ..Default::default()
+    };
+    let transaction = match transaction.insert(&txn).await {
+        Ok(transaction) => transaction,
+        // Параллельный поток успел открыть свой счёт: отправляем его ссылку
+        Err(err) if matches!(err.sql_err(), Some(SqlErr::UniqueConstraintViolation(_))) => {
+            txn.rollback().await?;
+            let Some(open) = open_transaction(db, telegram_id, channel_id).await? else {
+                return Err(err.into());
+            };
+            return resend_open_link(bot, dialogue, payment_gateway, chat_id, channel, open).await;
         }
+        Err(err) => return Err(err.into()),
+    };
+    FeeLedgerModel {
+        transaction_id: Set(transaction.id),
+        channel_id: Set(Some(channel_id)),
+        owner_telegram_id: Set(channel.owner_telegram_id),
+        currency: Set(transaction.currency.clone()),
+        gross_amount: Set(price),
+        fee_amount: Set(split.fee),
+        owner_amount: Set(split.owner_amount),
+        fee_percent: Set(fee.percent),
+        fee_minimum: Set(fee.minimum),
+        created_at: Set(date_now),
+        ..Default::default()
     }
+    .insert(&txn)
+    .await?;
+    txn.commit().await?;
+    publish_cancelled(redis, &cancelled).await;
+    tracing::info!(
+        transaction_id = transaction.id,
+        correlation_id = %transaction.correlation_id,
+        "payment created"
+    );
+    metrics::counter!(
+        "payments_created_total",
+        "currency" => transaction.currency.clone(),
+        "source" => "bot",
+    )
+    .increment(1);
+
+    let link = format!("{}/{}", payment_gateway, transaction.public_id);
+    let message = match promo {
+        Some(promo) => format!(
+            "Promo code {} applied: {:.2} {} instead of {:.2} {}. Please follow this link {} to proceed the action within an hour. Thank you!",
+            promo.code, price, transaction.currency, monthly_price, transaction.currency, link
+        ),
+        None => format!(
+            "Please follow this link {} to proceed the action within an hour. Thank you!",
+            link
+        ),
+    };
+    bot.send_message(chat_id, message).await?;
+    dialogue
+        .update(GlobalState::Pay(State::Pay {
+            channel_id,
+            channel_name: channel.title,
+        }))
+        .await?;
     Ok(())
 }
 
@@ -185,17 +532,30 @@ pub(crate) fn schema() -> UpdateHandler<BotError> {
             Update::filter_message()
                 .enter_dialogue::<Message, ErasedStorage<GlobalState>, GlobalState>()
                 .branch(
-                    dptree::case![GlobalState::Pay(x)].branch(
-                        dptree::case![State::SelectChannel { channel_id }]
-                            .endpoint(handle_pay_channel_selection),
-                    ),
+                    dptree::case![GlobalState::Pay(x)]
+                        .branch(
+                            dptree::case![State::SelectChannel { channel_id }]
+                                .endpoint(handle_pay_channel_selection),
+                        )
+                        .branch(
+                            dptree::case![State::EnterPromoCode { channel_id }]
+                                .endpoint(handle_promo_code_input),
+                        ),
                 ),
         )
         .branch(
             Update::filter_callback_query()
                 .enter_dialogue::<CallbackQuery, ErasedStorage<GlobalState>, GlobalState>()
-                .branch(dptree::case![GlobalState::Pay(x)].branch(
-                    dptree::case![State::SelectChannel { channel_id }].endpoint(handle_pay_button),
-                )),
+                .branch(
+                    dptree::case![GlobalState::Pay(x)]
+                        .branch(
+                            dptree::case![State::SelectChannel { channel_id }]
+                                .endpoint(handle_pay_button),
+                        )
+                        .branch(
+                            dptree::case![State::EnterPromoCode { channel_id }]
+                                .endpoint(handle_skip_promo_code),
+                        ),
+                ),
         )
 }
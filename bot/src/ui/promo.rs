@@ -0,0 +1,342 @@
+use super::{BotError, Commands, State as GlobalState, UserDialogue};
+use chrono::Utc;
+use db::{Channel, PromoCode, channel, promo_code, settings};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, Condition, DatabaseConnection, EntityTrait, QueryFilter,
+    QueryOrder, QuerySelect, SqlErr,
+};
+use teloxide::{
+    Bot,
+    dispatching::{UpdateHandler, dialogue::ErasedStorage},
+    prelude::*,
+    types::{InlineKeyboardButton, InlineKeyboardMarkup, Message},
+};
+
+#[derive(Clone, Default, Debug, serde::Serialize, serde::Deserialize)]
+pub enum State {
+    #[default]
+    Start,
+    SelectChannel,
+    DescribeCode {
+        /// `None` for a global code, only admins create those.
+        channel_id: Option<i64>,
+    },
+}
+
+const CHANNEL_PREFIX: &str = "promo_channel_";
+const GLOBAL: &str = "promo_channel_all";
+const DEACTIVATE_PREFIX: &str = "promo_deactivate_";
+
+async fn owned_channels(
+    db: &DatabaseConnection,
+    telegram_id: i64,
+) -> Result<Vec<channel::Model>, sea_orm::DbErr> {
+    Channel::find()
+        .filter(channel::Column::OwnerTelegramId.eq(telegram_id))
+        .all(db)
+        .await
+}
+
+/// Owners manage the codes of their channels, admins also the global ones.
+async fn can_manage(
+    db: &DatabaseConnection,
+    telegram_id: i64,
+    channel_id: Option<i64>,
+) -> Result<bool, BotError> {
+    if settings::is_admin(db, telegram_id).await? {
+        return Ok(true);
+    }
+    let Some(channel_id) = channel_id else {
+        return Ok(false);
+    };
+    Ok(Channel::find_by_id(channel_id)
+        .one(db)
+        .await?
+        .is_some_and(|channel| channel.owner_telegram_id == telegram_id))
+}
+
+fn describe(promo: &promo_code::Model, channel_title: Option<&str>) -> String {
+    let discount = match promo.discount_type.as_str() {
+        "percent" => format!("{}%", promo.discount_value),
+        _ => format!("{:.2} {}", promo.discount_value, channel::SUBSCRIPTION_CURRENCY),
+    };
+    let uses = match promo.max_uses {
+        Some(max_uses) => format!("{}/{}", promo.used_count, max_uses),
+        None => promo.used_count.to_string(),
+    };
+    let mut text = format!(
+        "{} for {}: -{}, used {}",
+        promo.code,
+        channel_title.unwrap_or("all channels"),
+        discount,
+        uses
+    );
+    if let Some(valid_from) = promo.valid_from {
+        text.push_str(&format!(", from {}", valid_from.format("%Y-%m-%d")));
+    }
+    if let Some(valid_until) = promo.valid_until {
+        text.push_str(&format!(", until {}", valid_until.format("%Y-%m-%d")));
+    }
+    if !promo.is_active {
+        text.push_str(" (deactivated)");
+    }
+    text
+}
+
+async fn start_promo_dialogue(
+    bot: Bot,
+    msg: Message,
+    db: DatabaseConnection,
+    dialogue: UserDialogue,
+) -> Result<(), BotError> {
+    let Some(user) = msg.from else {
+        return Ok(());
+    };
+    let telegram_id = user.id.0 as i64;
+    let channels = owned_channels(&db, telegram_id).await?;
+    let mut buttons: Vec<Vec<InlineKeyboardButton>> = channels
+        .iter()
+        .map(|c| {
+            vec![InlineKeyboardButton::callback(
+                c.title.clone(),
+                format!("{}{}", CHANNEL_PREFIX, c.channel_id),
+            )]
+        })
+        .collect();
+    if settings::is_admin(&db, telegram_id).await? {
+        buttons.push(vec![InlineKeyboardButton::callback("All channels", GLOBAL)]);
+    }
+    if buttons.is_empty() {
+        bot.send_message(msg.chat.id, "You don't own any channels.")
+            .await?;
+        dialogue.exit().await?;
+        return Ok(());
+    }
+    bot.send_message(msg.chat.id, "Select a channel for the promo code: ")
+        .reply_markup(InlineKeyboardMarkup::new(buttons))
+        .await?;
+    dialogue
+        .update(GlobalState::Promo(State::SelectChannel))
+        .await?;
+    Ok(())
+}
+
+async fn handle_channel_selection(
+    bot: Bot,
+    q: CallbackQuery,
+    dialogue: UserDialogue,
+    db: DatabaseConnection,
+) -> Result<(), BotError> {
+    bot.answer_callback_query(q.id).await?;
+    let Some(message) = q.message else {
+        return Ok(());
+    };
+    let channel_id = match q.data.as_deref() {
+        Some(GLOBAL) => None,
+        Some(data) => match data.strip_prefix(CHANNEL_PREFIX).and_then(|id| id.parse::<i64>().ok()) {
+            Some(channel_id) => Some(channel_id),
+            None => return Ok(()),
+        },
+        None => return Ok(()),
+    };
+    if !can_manage(&db, q.from.id.0 as i64, channel_id).await? {
+        bot.send_message(q.from.id, "You are not allowed to create promo codes for this channel.")
+            .await?;
+        dialogue.exit().await?;
+        return Ok(());
+    }
+    bot.edit_message_text(
+        message.chat().id,
+        message.id(),
+        format!(
+            "Describe the promo code as\n{}\n\nFor example: SPRING percent 20 uses=100 until=2026-12-01",
+            promo_code::DRAFT_FORMAT
+        ),
+    )
+    .reply_markup(InlineKeyboardMarkup::default())
+    .await?;
+    dialogue
+        .update(GlobalState::Promo(State::DescribeCode { channel_id }))
+        .await?;
+    Ok(())
+}
+
+async fn handle_code_input(
+    bot: Bot,
+    msg: Message,
+    dialogue: UserDialogue,
+    channel_id: Option<i64>,
+    db: DatabaseConnection,
+) -> Result<(), BotError> {
+    let draft = match promo_code::Draft::parse(msg.text().unwrap_or_default(), Utc::now()) {
+        Ok(draft) => draft,
+        Err(err) => {
+            bot.send_message(msg.chat.id, format!("{} Please try again:", err))
+                .await?;
+            return Ok(());
+        }
+    };
+    let channel = match channel_id {
+        Some(channel_id) => match Channel::find_by_id(channel_id).one(&db).await? {
+            Some(channel) => Some(channel),
+            None => {
+                bot.send_message(msg.chat.id, "Channel not found in the database.")
+                    .await?;
+                dialogue.exit().await?;
+                return Ok(());
+            }
+        },
+        None => None,
+    };
+    // Скидка, съедающая всю текущую цену канала, всё равно не применится
+    if let Some(price) = channel.as_ref().and_then(|channel| channel.monthly_price)
+        && draft.apply(price).is_none()
+    {
+        bot.send_message(
+            msg.chat.id,
+            format!(
+                "This discount leaves nothing of the channel price {:.2} {}. Please try again:",
+                price,
+                channel::SUBSCRIPTION_CURRENCY
+            ),
+        )
+        .await?;
+        return Ok(());
+    }
+    let promo = match draft.into_active_model(channel_id).insert(&db).await {
+        Ok(promo) => promo,
+        Err(err) if matches!(err.sql_err(), Some(SqlErr::UniqueConstraintViolation(_))) => {
+            bot.send_message(msg.chat.id, "This code already exists here. Please choose another one:")
+                .await?;
+            return Ok(());
+        }
+        Err(err) => return Err(err.into()),
+    };
+    bot.send_message(
+        msg.chat.id,
+        format!(
+            "✅ Promo code created: {}",
+            describe(&promo, channel.as_ref().map(|channel| channel.title.as_str()))
+        ),
+    )
+    .await?;
+    dialogue.exit().await?;
+    Ok(())
+}
+
+pub(crate) async fn list_promo_codes(
+    bot: Bot,
+    msg: Message,
+    db: DatabaseConnection,
+) -> Result<(), BotError> {
+    let Some(user) = msg.from else {
+        return Ok(());
+    };
+    let telegram_id = user.id.0 as i64;
+    let channels = owned_channels(&db, telegram_id).await?;
+    let mut scope = Condition::any().add(
+        promo_code::Column::ChannelId.is_in(channels.iter().map(|channel| channel.channel_id)),
+    );
+    if settings::is_admin(&db, telegram_id).await? {
+        scope = scope.add(promo_code::Column::ChannelId.is_null());
+    }
+    let promos = PromoCode::find()
+        .filter(scope)
+        .order_by_desc(promo_code::Column::IsActive)
+        .order_by_asc(promo_code::Column::Id)
+        .limit(50)
+        .all(&db)
+        .await?;
+    if promos.is_empty() {
+        bot.send_message(msg.chat.id, "There are no promo codes yet. Create one with /newpromo.")
+            .await?;
+        return Ok(());
+    }
+    for promo in promos {
+        let title = promo.channel_id.and_then(|channel_id| {
+            channels
+                .iter()
+                .find(|channel| channel.channel_id == channel_id)
+                .map(|channel| channel.title.as_str())
+        });
+        let request = bot.send_message(msg.chat.id, describe(&promo, title));
+        if promo.is_active {
+            request
+                .reply_markup(InlineKeyboardMarkup::new(vec![vec![InlineKeyboardButton::callback(
+                    "Deactivate",
+                    format!("{}{}", DEACTIVATE_PREFIX, promo.id),
+                )]]))
+                .await?;
+        } else {
+            request.await?;
+        }
+    }
+    Ok(())
+}
+
+pub(crate) async fn handle_deactivate(
+    bot: Bot,
+    q: CallbackQuery,
+    db: DatabaseConnection,
+) -> Result<(), BotError> {
+    bot.answer_callback_query(q.id.clone()).await?;
+    let Some(message) = q.message else {
+        return Ok(());
+    };
+    let Some(promo_id) = q
+        .data
+        .as_deref()
+        .and_then(|data| data.strip_prefix(DEACTIVATE_PREFIX))
+        .and_then(|id| id.parse::<i64>().ok())
+    else {
+        return Ok(());
+    };
+    let chat_id = message.chat().id;
+    let Some(promo) = PromoCode::find_by_id(promo_id).one(&db).await? else {
+        bot.send_message(chat_id, "Promo code not found").await?;
+        return Ok(());
+    };
+    if !can_manage(&db, q.from.id.0 as i64, promo.channel_id).await? {
+        bot.send_message(chat_id, "You are not allowed to change this promo code.")
+            .await?;
+        return Ok(());
+    }
+    promo_code::deactivate(&db, promo.id).await?;
+    bot.edit_message_text(chat_id, message.id(), format!("{}\n❌ Deactivated.", promo.code))
+        .await?;
+    Ok(())
+}
+
+pub(crate) fn schema() -> UpdateHandler<BotError> {
+    dptree::entry()
+        .branch(
+            Update::filter_message()
+                .enter_dialogue::<Message, ErasedStorage<GlobalState>, GlobalState>()
+                .filter_command::<Commands>()
+                .branch(dptree::case![Commands::NewPromo].endpoint(start_promo_dialogue))
+                .branch(dptree::case![Commands::PromoCodes].endpoint(list_promo_codes)),
+        )
+        .branch(
+            Update::filter_message()
+                .enter_dialogue::<Message, ErasedStorage<GlobalState>, GlobalState>()
+                .branch(
+                    dptree::case![GlobalState::Promo(x)].branch(
+                        dptree::case![State::DescribeCode { channel_id }].endpoint(handle_code_input),
+                    ),
+                ),
+        )
+        .branch(
+            Update::filter_callback_query()
+                .enter_dialogue::<CallbackQuery, ErasedStorage<GlobalState>, GlobalState>()
+                .branch(dptree::case![GlobalState::Promo(x)].branch(
+                    dptree::case![State::SelectChannel].endpoint(handle_channel_selection),
+                )),
+        )
+        .branch(
+            Update::filter_callback_query()
+                .filter(|q: CallbackQuery| {
+                    q.data.as_deref().is_some_and(|data| data.starts_with(DEACTIVATE_PREFIX))
+                })
+                .endpoint(handle_deactivate),
+        )
+}
@@ -0,0 +1,186 @@
+use super::{BotError, Commands};
+use chrono::Utc;
+use db::{Channel, Refund, RefundModel, channel, refund, settings};
+use sea_orm::{
+    ActiveModelTrait, ActiveValue::Set, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter,
+    QueryOrder, QuerySelect, TransactionTrait,
+};
+use teloxide::{
+    Bot,
+    dispatching::UpdateHandler,
+    prelude::*,
+    types::{InlineKeyboardButton, InlineKeyboardMarkup, Message},
+};
+
+const APPROVE_PREFIX: &str = "refund_approve_";
+const REJECT_PREFIX: &str = "refund_reject_";
+
+fn decision_keyboard(refund_id: i64) -> InlineKeyboardMarkup {
+    InlineKeyboardMarkup::new(vec![vec![
+        InlineKeyboardButton::callback("Approve", format!("{}{}", APPROVE_PREFIX, refund_id)),
+        InlineKeyboardButton::callback("Reject", format!("{}{}", REJECT_PREFIX, refund_id)),
+    ]])
+}
+
+fn describe(refund: &refund::Model) -> String {
+    format!(
+        "Refund #{} for transaction #{}: {} {} ({}) to {}",
+        refund.id,
+        refund.transaction_id,
+        refund.amount,
+        refund.currency,
+        refund.reason,
+        refund.destination_address
+    )
+}
+
+/// Admins decide on every refund, owners only on refunds for their channels. Refunds of
+/// merchant invoices without a channel are left to admins.
+async fn can_decide(
+    db: &DatabaseConnection,
+    telegram_id: i64,
+    refund: &refund::Model,
+) -> Result<bool, BotError> {
+    if settings::is_admin(db, telegram_id).await? {
+        return Ok(true);
+    }
+    let Some(channel_id) = refund.channel_id else {
+        return Ok(false);
+    };
+    Ok(Channel::find_by_id(channel_id)
+        .one(db)
+        .await?
+        .is_some_and(|channel| channel.owner_telegram_id == telegram_id))
+}
+
+pub(crate) async fn list_refunds(
+    bot: Bot,
+    msg: Message,
+    db: DatabaseConnection,
+) -> Result<(), BotError> {
+    let Some(user) = msg.from else {
+        return Ok(());
+    };
+    let telegram_id = user.id.0 as i64;
+    let mut query = Refund::find()
+        .filter(refund::Column::Status.eq("requested"))
+        .order_by_asc(refund::Column::Id);
+    if !settings::is_admin(&db, telegram_id).await? {
+        let channel_ids: Vec<i64> = Channel::find()
+            .select_only()
+            .column(channel::Column::ChannelId)
+            .filter(channel::Column::OwnerTelegramId.eq(telegram_id))
+            .into_tuple()
+            .all(&db)
+            .await?;
+        query = query.filter(refund::Column::ChannelId.is_in(channel_ids));
+    }
+    let refunds = query.all(&db).await?;
+    if refunds.is_empty() {
+        bot.send_message(msg.chat.id, "There are no refund requests waiting for a decision.")
+            .await?;
+        return Ok(());
+    }
+    for refund in refunds {
+        bot.send_message(msg.chat.id, describe(&refund))
+            .reply_markup(decision_keyboard(refund.id))
+            .await?;
+    }
+    Ok(())
+}
+
+pub(crate) async fn handle_refund_decision(
+    bot: Bot,
+    q: CallbackQuery,
+    db: DatabaseConnection,
+) -> Result<(), BotError> {
+    bot.answer_callback_query(q.id.clone()).await?;
+    let Some(message) = q.message else {
+        return Ok(());
+    };
+    let Some(data) = q.data else {
+        return Ok(());
+    };
+    let (approve, refund_id) = if let Some(id) = data.strip_prefix(APPROVE_PREFIX) {
+        (true, id)
+    } else if let Some(id) = data.strip_prefix(REJECT_PREFIX) {
+        (false, id)
+    } else {
+        return Ok(());
+    };
+    let Ok(refund_id) = refund_id.parse::<i64>() else {
+        return Ok(());
+    };
+    let telegram_id = q.from.id.0 as i64;
+    let chat_id = message.chat().id;
+
+    let txn = db.begin().await?;
+    let Some(refund) = Refund::find_by_id(refund_id)
+        .lock_exclusive()
+        .one(&txn)
+        .await?
+    else {
+        bot.send_message(chat_id, "Refund not found").await?;
+        return Ok(());
+    };
+    if !can_decide(&db, telegram_id, &refund).await? {
+        bot.send_message(chat_id, "You are not allowed to decide on this refund.")
+            .await?;
+        return Ok(());
+    }
+    if refund.status != "requested" {
+        bot.edit_message_text(
+            chat_id,
+            message.id(),
+            format!("{}\nAlready {}.", describe(&refund), refund.status),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let now = Utc::now();
+    let status = if approve { "approved" } else { "rejected" };
+    let mut model: RefundModel = refund.clone().into();
+    model.status = Set(status.to_string());
+    model.decided_by = Set(Some(telegram_id));
+    model.decided_at = Set(Some(now));
+    model.updated_at = Set(now);
+    model.update(&txn).await?;
+    if approve {
+        ledger::posting::post_refund_approved(
+            &txn,
+            refund.id,
+            refund.telegram_id,
+            &refund.currency,
+            refund.amount,
+        )
+        .await?;
+    }
+    txn.commit().await?;
+
+    bot.edit_message_text(
+        chat_id,
+        message.id(),
+        format!("{}\n{}.", describe(&refund), if approve { "✅ Approved" } else { "❌ Rejected" }),
+    )
+    .await?;
+    Ok(())
+}
+
+pub(crate) fn schema() -> UpdateHandler<BotError> {
+    dptree::entry()
+        .branch(
+            Update::filter_message()
+                .filter_command::<Commands>()
+                .branch(dptree::case![Commands::Refunds].endpoint(list_refunds)),
+        )
+        .branch(
+            Update::filter_callback_query()
+                .filter(|q: CallbackQuery| {
+                    q.data.as_deref().is_some_and(|data| {
+                        data.starts_with(APPROVE_PREFIX) || data.starts_with(REJECT_PREFIX)
+                    })
+                })
+                .endpoint(handle_refund_decision),
+        )
+}
@@ -0,0 +1,180 @@
+use super::{BotError, Commands, State as GlobalState, UserDialogue};
+use db::{Channel, ChannelModel, channel};
+use sea_orm::{
+    ActiveModelTrait, ActiveValue::Set, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter,
+};
+use teloxide::{
+    Bot,
+    dispatching::{UpdateHandler, dialogue::ErasedStorage},
+    prelude::*,
+    types::{InlineKeyboardButton, InlineKeyboardMarkup, Message},
+};
+
+#[derive(Clone, Default, Debug, serde::Serialize, serde::Deserialize)]
+pub enum State {
+    #[default]
+    Start,
+    SelectChannel,
+    EnterSlug {
+        channel_id: i64,
+    },
+}
+
+async fn start_slug_dialogue(
+    bot: Bot,
+    msg: Message,
+    db: DatabaseConnection,
+    dialogue: UserDialogue,
+) -> Result<(), BotError> {
+    let Some(owner) = msg.from else {
+        return Ok(());
+    };
+    let channels = Channel::find()
+        .filter(channel::Column::OwnerTelegramId.eq(owner.id.0))
+        .all(&db)
+        .await?;
+    if channels.is_empty() {
+        bot.send_message(msg.chat.id, "You don't own any channels.")
+            .await?;
+        dialogue.exit().await?;
+        return Ok(());
+    }
+    let buttons: Vec<Vec<InlineKeyboardButton>> = channels
+        .iter()
+        .map(|c| {
+            vec![InlineKeyboardButton::callback(
+                c.title.clone(),
+                format!("slug_channel_{}", c.channel_id),
+            )]
+        })
+        .collect();
+    bot.send_message(msg.chat.id, "Select a channel: ")
+        .reply_markup(InlineKeyboardMarkup::new(buttons))
+        .await?;
+    dialogue
+        .update(GlobalState::Slug(State::SelectChannel))
+        .await?;
+    Ok(())
+}
+
+async fn handle_channel_selection(
+    bot: Bot,
+    q: CallbackQuery,
+    dialogue: UserDialogue,
+    db: DatabaseConnection,
+) -> Result<(), BotError> {
+    bot.answer_callback_query(q.id).await?;
+    let Some(message) = q.message else {
+        return Ok(());
+    };
+    if let Some(channel_id) = q
+        .data
+        .as_deref()
+        .and_then(|data| data.strip_prefix("slug_channel_"))
+        .and_then(|id| id.parse::<i64>().ok())
+        && let Some(channel) = Channel::find_by_id(channel_id).one(&db).await?
+    {
+        if channel.owner_telegram_id != q.from.id.0 as i64 {
+            bot.send_message(q.from.id, "You are not the owner of this channel.")
+                .await?;
+            return Ok(());
+        }
+        let current = channel
+            .slug
+            .map(|slug| format!("Current name: {}\n\n", slug))
+            .unwrap_or_default();
+        bot.edit_message_text(
+            message.chat().id,
+            message.id(),
+            format!(
+                "{}Enter a public name for \"{}\": 3-32 lowercase latin letters, digits and dashes, starting with a letter:",
+                current, channel.title
+            ),
+        )
+        .reply_markup(InlineKeyboardMarkup::default())
+        .await?;
+        dialogue
+            .update(GlobalState::Slug(State::EnterSlug { channel_id }))
+            .await?;
+        return Ok(());
+    }
+
+    bot.send_message(
+        message.chat().id,
+        "Error processing your selection. Please try again.",
+    )
+    .await?;
+    dialogue.exit().await?;
+    Ok(())
+}
+
+async fn handle_slug_input(
+    bot: Bot,
+    msg: Message,
+    dialogue: UserDialogue,
+    channel_id: i64,
+    db: DatabaseConnection,
+) -> Result<(), BotError> {
+    let slug = msg.text().unwrap_or_default().trim().to_lowercase();
+    if !channel::is_valid_slug(&slug) {
+        bot.send_message(
+            msg.chat.id,
+            "Please enter 3-32 lowercase latin letters, digits and dashes, starting with a letter:",
+        )
+        .await?;
+        return Ok(());
+    }
+    let taken = Channel::find()
+        .filter(channel::Column::Slug.eq(&slug))
+        .filter(channel::Column::ChannelId.ne(channel_id))
+        .one(&db)
+        .await?;
+    if taken.is_some() {
+        bot.send_message(msg.chat.id, "This name is already taken. Please enter another one:")
+            .await?;
+        return Ok(());
+    }
+    let Some(channel) = Channel::find_by_id(channel_id).one(&db).await? else {
+        bot.send_message(msg.chat.id, "Channel not found in the database.")
+            .await?;
+        dialogue.exit().await?;
+        return Ok(());
+    };
+    let title = channel.title.clone();
+    let mut channel_model: ChannelModel = channel.into();
+    channel_model.slug = Set(Some(slug.clone()));
+    channel_model.update(&db).await?;
+    bot.send_message(
+        msg.chat.id,
+        format!("✅ Channel \"{}\" is now available as \"{}\".", title, slug),
+    )
+    .await?;
+    dialogue.exit().await?;
+    Ok(())
+}
+
+pub(crate) fn schema() -> UpdateHandler<BotError> {
+    dptree::entry()
+        .branch(
+            Update::filter_message()
+                .enter_dialogue::<Message, ErasedStorage<GlobalState>, GlobalState>()
+                .filter_command::<Commands>()
+                .branch(dptree::case![Commands::SetSlug].endpoint(start_slug_dialogue)),
+        )
+        .branch(
+            Update::filter_message()
+                .enter_dialogue::<Message, ErasedStorage<GlobalState>, GlobalState>()
+                .branch(
+                    dptree::case![GlobalState::Slug(x)].branch(
+                        dptree::case![State::EnterSlug { channel_id }].endpoint(handle_slug_input),
+                    ),
+                ),
+        )
+        .branch(
+            Update::filter_callback_query()
+                .enter_dialogue::<CallbackQuery, ErasedStorage<GlobalState>, GlobalState>()
+                .branch(dptree::case![GlobalState::Slug(x)].branch(
+                    dptree::case![State::SelectChannel].endpoint(handle_channel_selection),
+                )),
+        )
+}
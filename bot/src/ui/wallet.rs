@@ -0,0 +1,35 @@
+use super::{BotError, Commands, WalletLinkPage};
+use events::wallet_link::{WALLET_LINK_TTL_SECONDS, create_wallet_link};
+use redis::aio::MultiplexedConnection;
+use teloxide::{Bot, dispatching::UpdateHandler, prelude::*, types::Message};
+
+/// Hands out a one-time link to the web UI; after a TON Proof login there the API binds
+/// the wallet to this Telegram account.
+pub(crate) async fn handle_link_wallet(
+    bot: Bot,
+    msg: Message,
+    mut redis: MultiplexedConnection,
+    wallet_link_page: WalletLinkPage,
+) -> Result<(), BotError> {
+    let Some(user) = msg.from else {
+        return Ok(());
+    };
+    let token = create_wallet_link(&mut redis, user.id.0 as i64).await?;
+    bot.send_message(
+        msg.chat.id,
+        format!(
+            "Open {}/{} and connect your wallet to link it to your account. The link works once and expires in {} minutes.",
+            wallet_link_page,
+            token,
+            WALLET_LINK_TTL_SECONDS / 60
+        ),
+    )
+    .await?;
+    Ok(())
+}
+
+pub(crate) fn schema() -> UpdateHandler<BotError> {
+    Update::filter_message()
+        .filter_command::<Commands>()
+        .branch(dptree::case![Commands::LinkWallet].endpoint(handle_link_wallet))
+}
@@ -0,0 +1,58 @@
+//! Settings of every Krypton service, read in layers: defaults in code, then the TOML file
+//! named by `KRYPTON_CONFIG` (`krypton.toml` in the working directory if it exists), then
+//! the environment, `.env` included. Later layers win.
+//!
+//! A key like `redis.password` is looked up as
+//! - `[api.redis] password` and then `[redis] password` in the file, for the `api` service;
+//! - `API_REDIS_PASSWORD` (`TON_WATCHER_REDIS_PASSWORD` for `ton-watcher`), then
+//!   `REDIS_PASSWORD`, then its legacy names (`API_DRAGONFLY_PASSWORD`, `DRAGONFLY_PASSWORD`)
+//!   in the environment.
+//!
+//! Reading never stops at the first problem: [`load`] collects every missing or invalid
+//! key and reports them together.
+
+mod reader;
+mod sections;
+
+pub use reader::{ConfigError, Problem, Reader};
+pub use sections::{
+    DatabaseConfig, HealthConfig, LogConfig, PaymentPageConfig, RedisConfig, TelegramConfig,
+    TonConfig,
+};
+
+use std::{collections::HashMap, env, fs, path::PathBuf};
+
+const CONFIG_FILE_VAR: &str = "KRYPTON_CONFIG";
+const DEFAULT_CONFIG_FILE: &str = "krypton.toml";
+
+/// Loads the settings of `service` with `read`, which builds them from the [`Reader`].
+pub fn load<T>(service: &str, read: impl FnOnce(&mut Reader) -> T) -> Result<T, ConfigError> {
+    dotenv::dotenv().ok();
+    let env: HashMap<String, String> = env::vars().collect();
+    let file = match config_file(&env) {
+        Some(path) => fs::read_to_string(&path)
+            .map_err(|err| err.to_string())
+            .and_then(|text| text.parse::<toml::Table>().map_err(|err| err.to_string()))
+            .map_err(|err| ConfigError::file(&path, err))?,
+        None => toml::Table::new(),
+    };
+    Reader::new(service, file, env).finish(read)
+}
+
+/// [`load`] for `main`: prints every problem and exits when the settings are unusable.
+pub fn load_or_exit<T>(service: &str, read: impl FnOnce(&mut Reader) -> T) -> T {
+    load(service, read).unwrap_or_else(|err| {
+        eprintln!("❌ {}: {}", service, err);
+        std::process::exit(1)
+    })
+}
+
+fn config_file(env: &HashMap<String, String>) -> Option<PathBuf> {
+    match env.get(CONFIG_FILE_VAR) {
+        Some(path) => Some(PathBuf::from(path)),
+        None => {
+            let path = PathBuf::from(DEFAULT_CONFIG_FILE);
+            path.exists().then_some(path)
+        }
+    }
+}
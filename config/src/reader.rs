@@ -0,0 +1,202 @@
+use std::{collections::HashMap, fmt, path::Path, str::FromStr};
+
+/// Names keys had before the config crate, still read so existing `.env` files keep working.
+const LEGACY_ENV: &[(&str, &str)] = &[
+    ("redis.password", "DRAGONFLY_PASSWORD"),
+    ("redis.host", "HOST_URL"),
+    ("ton.gate_address", "GATE_CRYPTO_ADDRESS"),
+    ("telegram.bot_token", "BOT_TOKEN"),
+    ("payments.gateway_url", "PAYMENT_GATEWAY"),
+    ("payments.wallet_link_url", "WALLET_LINK_URL"),
+];
+
+/// What's wrong with one key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Problem {
+    pub key: String,
+    pub message: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.key, self.message)
+    }
+}
+
+#[derive(Debug)]
+pub struct ConfigError {
+    pub problems: Vec<Problem>,
+}
+
+impl ConfigError {
+    pub(crate) fn file(path: &Path, error: String) -> Self {
+        ConfigError {
+            problems: vec![Problem {
+                key: path.display().to_string(),
+                message: error,
+            }],
+        }
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "invalid configuration:")?;
+        for problem in &self.problems {
+            writeln!(f, "  - {}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Typed access to the merged layers. Getters never fail: a bad value is recorded as a
+/// [`Problem`] and replaced with a placeholder, which [`Reader::finish`] never lets out.
+pub struct Reader {
+    service: String,
+    file: toml::Table,
+    env: HashMap<String, String>,
+    problems: Vec<Problem>,
+}
+
+impl Reader {
+    pub fn new(service: &str, file: toml::Table, env: HashMap<String, String>) -> Self {
+        Reader {
+            service: service.to_string(),
+            file,
+            env,
+            problems: Vec::new(),
+        }
+    }
+
+    /// Runs `read` and returns its result, or every problem it ran into.
+    pub fn finish<T>(mut self, read: impl FnOnce(&mut Reader) -> T) -> Result<T, ConfigError> {
+        let value = read(&mut self);
+        if self.problems.is_empty() {
+            Ok(value)
+        } else {
+            Err(ConfigError {
+                problems: self.problems,
+            })
+        }
+    }
+
+    fn env_names(&self, key: &str) -> Vec<String> {
+        let name = key.replace('.', "_").to_uppercase();
+        let prefix = self.service.replace('-', "_").to_uppercase();
+        let mut names = vec![format!("{}_{}", prefix, name), name];
+        for (_, legacy) in LEGACY_ENV.iter().filter(|(legacy_key, _)| *legacy_key == key) {
+            names.push(format!("{}_{}", prefix, legacy));
+            names.push(legacy.to_string());
+        }
+        names
+    }
+
+    fn file_value(&self, path: &[&str]) -> Option<String> {
+        let (last, sections) = path.split_last()?;
+        let mut table = &self.file;
+        for section in sections {
+            table = table.get(*section)?.as_table()?;
+        }
+        match table.get(*last)? {
+            toml::Value::String(value) => Some(value.clone()),
+            value => Some(value.to_string()),
+        }
+    }
+
+    /// The raw value of `key` from the highest layer that has it.
+    pub fn raw(&self, key: &str) -> Option<String> {
+        let from_env = self
+            .env_names(key)
+            .into_iter()
+            .find_map(|name| self.env.get(&name).filter(|value| !value.is_empty()).cloned());
+        from_env.or_else(|| {
+            let path: Vec<&str> = key.split('.').collect();
+            let scoped: Vec<&str> = [self.service.as_str()].into_iter().chain(path.iter().copied()).collect();
+            self.file_value(&scoped).or_else(|| self.file_value(&path))
+        })
+    }
+
+    /// Records a problem with `key`, for checks beyond parsing.
+    pub fn invalid(&mut self, key: &str, message: impl Into<String>) {
+        self.problems.push(Problem {
+            key: key.to_string(),
+            message: message.into(),
+        });
+    }
+
+    pub fn optional<T: FromStr>(&mut self, key: &str) -> Option<T> {
+        let raw = self.raw(key)?;
+        match raw.trim().parse() {
+            Ok(value) => Some(value),
+            Err(_) => {
+                // Значение не выводим: это может быть секрет
+                self.invalid(key, format!("can't parse as {}", std::any::type_name::<T>()));
+                None
+            }
+        }
+    }
+
+    pub fn required<T: FromStr + Default>(&mut self, key: &str) -> T {
+        if self.raw(key).is_none() {
+            let names = self.env_names(key);
+            self.invalid(key, format!("missing, set it in the config file or {}", names[1]));
+            return T::default();
+        }
+        self.optional(key).unwrap_or_default()
+    }
+
+    pub fn or<T: FromStr>(&mut self, key: &str, default: T) -> T {
+        self.optional(key).unwrap_or(default)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reader(file: &str, env: &[(&str, &str)]) -> Reader {
+        Reader::new(
+            "api",
+            file.parse().unwrap(),
+            env.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+        )
+    }
+
+    #[test]
+    fn later_layers_win() {
+        let file = "[redis]\nhost = \"file\"\nport = 6380\n[api.redis]\nhost = \"scoped\"";
+        let mut r = reader(file, &[]);
+        assert_eq!(r.or("redis.host", "default".to_string()), "scoped");
+        assert_eq!(r.or("redis.port", 6379u16), 6380);
+        assert_eq!(r.or("redis.db", 0u8), 0);
+
+        let mut r = reader(file, &[("REDIS_HOST", "env"), ("API_REDIS_PORT", "7000"), ("REDIS_PORT", "1")]);
+        assert_eq!(r.or("redis.host", "default".to_string()), "env");
+        assert_eq!(r.or("redis.port", 6379u16), 7000);
+    }
+
+    #[test]
+    fn legacy_names_are_read_last() {
+        let mut r = reader("", &[("API_DRAGONFLY_PASSWORD", "legacy")]);
+        assert_eq!(r.optional::<String>("redis.password").as_deref(), Some("legacy"));
+        let mut r = reader("", &[("API_DRAGONFLY_PASSWORD", "legacy"), ("REDIS_PASSWORD", "new")]);
+        assert_eq!(r.optional::<String>("redis.password").as_deref(), Some("new"));
+    }
+
+    #[test]
+    fn reports_every_problem_at_once() {
+        let r = reader("", &[("API_PORT", "eighty"), ("DATABASE_URL", "")]);
+        let err = r
+            .finish(|r| {
+                let port: u16 = r.required("api.port");
+                let url: String = r.required("database.url");
+                (port, url)
+            })
+            .unwrap_err();
+        let keys: Vec<&str> = err.problems.iter().map(|p| p.key.as_str()).collect();
+        assert_eq!(keys, ["api.port", "database.url"]);
+        assert!(!err.to_string().contains("eighty"));
+    }
+}
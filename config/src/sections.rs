@@ -0,0 +1,142 @@
+//! Settings shared by several services.
+
+use crate::Reader;
+
+fn check_url(r: &mut Reader, key: &str, url: &str, schemes: &[&str]) {
+    let valid = url
+        .split_once("://")
+        .is_some_and(|(scheme, rest)| schemes.contains(&scheme) && !rest.is_empty());
+    if !url.is_empty() && !valid {
+        r.invalid(key, format!("must be a {} URL", schemes.join("/")));
+    }
+}
+
+/// `database.*`: the Postgres connection string and whether to migrate on boot.
+#[derive(Clone, Debug)]
+pub struct DatabaseConfig {
+    pub url: String,
+    /// Apply pending migrations before serving. Off by default: deploys run the migration
+    /// binary on their own.
+    pub migrate: bool,
+}
+
+impl DatabaseConfig {
+    pub fn read(r: &mut Reader) -> Self {
+        let url: String = r.required("database.url");
+        check_url(r, "database.url", &url, &["postgres", "postgresql"]);
+        DatabaseConfig {
+            url,
+            migrate: r.or("database.migrate", false),
+        }
+    }
+}
+
+/// `redis.*`: the Dragonfly instance holding sessions, queues and dialogues.
+#[derive(Clone, Debug)]
+pub struct RedisConfig {
+    pub host: String,
+    pub port: u16,
+    pub password: Option<String>,
+}
+
+impl RedisConfig {
+    pub fn read(r: &mut Reader) -> Self {
+        RedisConfig {
+            host: r.or("redis.host", "127.0.0.1".to_string()),
+            port: r.or("redis.port", 6379),
+            password: r.optional("redis.password"),
+        }
+    }
+
+    pub fn url(&self) -> String {
+        match &self.password {
+            Some(password) => format!("redis://:{}@{}:{}", password, self.host, self.port),
+            None => format!("redis://{}:{}", self.host, self.port),
+        }
+    }
+}
+
+/// `ton.*`: the Krypton gate address, its USDT jetton wallet and the toncenter key.
+#[derive(Clone, Debug)]
+pub struct TonConfig {
+    pub gate_address: String,
+    /// The gate's USDT jetton wallet, the only sender whose transfer notifications count as
+    /// USDT payments. Only `ton-watcher` needs it.
+    pub usdt_jetton_wallet: String,
+    /// Toncenter works without a key, at a lower rate limit.
+    pub api_key: String,
+}
+
+impl TonConfig {
+    pub fn read(r: &mut Reader) -> Self {
+        TonConfig {
+            gate_address: r.required("ton.gate_address"),
+            usdt_jetton_wallet: r.or("ton.usdt_jetton_wallet", String::new()),
+            api_key: r.or("ton.api_key", String::new()),
+        }
+    }
+}
+
+/// `telegram.bot_token`.
+#[derive(Clone, Debug)]
+pub struct TelegramConfig {
+    pub bot_token: String,
+}
+
+impl TelegramConfig {
+    pub fn read(r: &mut Reader) -> Self {
+        TelegramConfig {
+            bot_token: r.required("telegram.bot_token"),
+        }
+    }
+}
+
+/// `payments.*`: the web pages the bot and the API link to.
+#[derive(Clone, Debug)]
+pub struct PaymentPageConfig {
+    /// Payment page, links are `{gateway_url}/{public_id}`.
+    pub gateway_url: String,
+}
+
+impl PaymentPageConfig {
+    pub fn read(r: &mut Reader) -> Self {
+        let gateway_url: String = r.required("payments.gateway_url");
+        check_url(r, "payments.gateway_url", &gateway_url, &["http", "https"]);
+        PaymentPageConfig {
+            gateway_url: gateway_url.trim_end_matches('/').to_string(),
+        }
+    }
+}
+
+/// `health.port`: where a worker answers `/healthz`, `/readyz` and `/metrics`. Without it
+/// the worker serves none of them.
+#[derive(Clone, Debug)]
+pub struct HealthConfig {
+    pub port: Option<u16>,
+}
+
+impl HealthConfig {
+    pub fn read(r: &mut Reader) -> Self {
+        HealthConfig {
+            port: r.optional("health.port"),
+        }
+    }
+}
+
+/// `log.format`: `text` (the default) or `json`.
+#[derive(Clone, Debug)]
+pub struct LogConfig {
+    pub json: bool,
+}
+
+impl LogConfig {
+    pub fn read(r: &mut Reader) -> Self {
+        let format: String = r.or("log.format", "text".to_string());
+        if format != "text" && format != "json" {
+            r.invalid("log.format", "must be text or json");
+        }
+        LogConfig {
+            json: format == "json",
+        }
+    }
+}
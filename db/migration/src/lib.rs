@@ -1,10 +1,28 @@
 pub use sea_orm_migration::prelude::*;
+use sea_orm_migration::sea_orm::{DatabaseConnection, TransactionTrait};
+
+#[cfg(feature = "testing")]
+pub mod testing;
 
 mod m20220101_000001_create_table;
 mod m20250417_112923_add_wallet_address_to_payment;
 mod m20250417_121459_add_message_id;
 mod m20250418_122825_add_subscriptions_table;
 mod m20250429_130451_create_chat_id_field;
+mod m20261019_090000_add_promo_codes_table;
+mod m20261019_100000_add_platform_fee;
+mod m20261019_110000_add_ledger_tables;
+mod m20261019_120000_add_payouts_tables;
+mod m20261019_130000_add_refunds_table;
+mod m20261019_140000_add_user_wallets_table;
+mod m20261019_150000_add_transaction_public_id;
+mod m20261019_160000_add_channel_slug;
+mod m20261019_170000_add_channel_metrics_index;
+mod m20261019_180000_add_webhooks_tables;
+mod m20261019_190000_add_api_keys;
+mod m20261019_200000_add_transaction_correlation_id;
+mod m20261019_210000_add_query_indexes;
+mod m20261019_220000_add_message_valid_until;
 
 pub struct Migrator;
 
@@ -17,6 +35,33 @@ impl MigratorTrait for Migrator {
             Box::new(m20250417_121459_add_message_id::Migration),
             Box::new(m20250418_122825_add_subscriptions_table::Migration),
             Box::new(m20250429_130451_create_chat_id_field::Migration),
+            Box::new(m20261019_090000_add_promo_codes_table::Migration),
+            Box::new(m20261019_100000_add_platform_fee::Migration),
+            Box::new(m20261019_110000_add_ledger_tables::Migration),
+            Box::new(m20261019_120000_add_payouts_tables::Migration),
+            Box::new(m20261019_130000_add_refunds_table::Migration),
+            Box::new(m20261019_140000_add_user_wallets_table::Migration),
+            Box::new(m20261019_150000_add_transaction_public_id::Migration),
+            Box::new(m20261019_160000_add_channel_slug::Migration),
+            Box::new(m20261019_170000_add_channel_metrics_index::Migration),
+            Box::new(m20261019_180000_add_webhooks_tables::Migration),
+            Box::new(m20261019_190000_add_api_keys::Migration),
+            Box::new(m20261019_200000_add_transaction_correlation_id::Migration),
+            Box::new(m20261019_210000_add_query_indexes::Migration),
+            Box::new(m20261019_220000_add_message_valid_until::Migration),
         ]
     }
 }
+
+/// Key of the advisory lock held while a service migrates on boot.
+const BOOT_MIGRATION_LOCK: i64 = 0x006b_7279_7074_6f6e;
+
+/// Applies pending migrations for a service started with `database.migrate`. Replicas boot
+/// together, so the first one migrates under an advisory lock and the rest find nothing to do.
+pub async fn up_on_boot(db: &DatabaseConnection) -> Result<(), DbErr> {
+    let txn = db.begin().await?;
+    txn.execute_unprepared(&format!("SELECT pg_advisory_xact_lock({})", BOOT_MIGRATION_LOCK))
+        .await?;
+    Migrator::up(&txn, None).await?;
+    txn.commit().await
+}
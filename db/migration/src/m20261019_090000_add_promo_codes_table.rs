@@ -0,0 +1,150 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Таблица promo_codes
+        manager
+            .create_table(
+                Table::create()
+                    .table(PromoCodes::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(PromoCodes::Id)
+                            .big_integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(PromoCodes::Code).string().not_null())
+                    // null = глобальный промокод для всех каналов
+                    .col(ColumnDef::new(PromoCodes::ChannelId).big_integer().null())
+                    .col(ColumnDef::new(PromoCodes::DiscountType).string().not_null())
+                    .col(ColumnDef::new(PromoCodes::DiscountValue).decimal().not_null())
+                    .col(ColumnDef::new(PromoCodes::MaxUses).integer().null())
+                    .col(
+                        ColumnDef::new(PromoCodes::UsedCount)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .col(
+                        ColumnDef::new(PromoCodes::ValidFrom)
+                            .timestamp_with_time_zone()
+                            .null(),
+                    )
+                    .col(
+                        ColumnDef::new(PromoCodes::ValidUntil)
+                            .timestamp_with_time_zone()
+                            .null(),
+                    )
+                    .col(
+                        ColumnDef::new(PromoCodes::IsActive)
+                            .boolean()
+                            .not_null()
+                            .default(true),
+                    )
+                    .col(
+                        ColumnDef::new(PromoCodes::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::cust("now()")),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(PromoCodes::Table, PromoCodes::ChannelId)
+                            .to(Channels::Table, Channels::ChannelId),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // Один и тот же код может существовать у разных каналов, но только один раз на канал
+        // (и один раз среди глобальных).
+        manager
+            .get_connection()
+            .execute_unprepared(
+                "CREATE UNIQUE INDEX IF NOT EXISTS promo_codes_code_channel_idx \
+                 ON promo_codes (code, COALESCE(channel_id, 0))",
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(PaymentTransactions::Table)
+                    .add_column(
+                        ColumnDef::new(PaymentTransactions::PromoCodeId)
+                            .big_integer()
+                            .null(),
+                    )
+                    .add_column(
+                        ColumnDef::new(PaymentTransactions::OriginalPrice)
+                            .decimal()
+                            .null(),
+                    )
+                    .add_foreign_key(
+                        TableForeignKey::new()
+                            .name("fk_payment_transactions_promo_code_id")
+                            .from_tbl(PaymentTransactions::Table)
+                            .from_col(PaymentTransactions::PromoCodeId)
+                            .to_tbl(PromoCodes::Table)
+                            .to_col(PromoCodes::Id),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(PaymentTransactions::Table)
+                    .drop_foreign_key(Alias::new("fk_payment_transactions_promo_code_id"))
+                    .drop_column(PaymentTransactions::PromoCodeId)
+                    .drop_column(PaymentTransactions::OriginalPrice)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(PromoCodes::Table).to_owned())
+            .await?;
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum PromoCodes {
+    Table,
+    Id,
+    Code,
+    ChannelId,
+    DiscountType,
+    DiscountValue,
+    MaxUses,
+    UsedCount,
+    ValidFrom,
+    ValidUntil,
+    IsActive,
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum Channels {
+    Table,
+    ChannelId,
+}
+
+#[derive(DeriveIden)]
+enum PaymentTransactions {
+    Table,
+    PromoCodeId,
+    OriginalPrice,
+}
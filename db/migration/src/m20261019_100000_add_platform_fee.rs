@@ -0,0 +1,146 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(PaymentTransactions::Table)
+                    .add_column(
+                        ColumnDef::new(PaymentTransactions::PlatformFee)
+                            .decimal()
+                            .not_null()
+                            .default(0),
+                    )
+                    .add_column(
+                        ColumnDef::new(PaymentTransactions::OwnerAmount)
+                            .decimal()
+                            .not_null()
+                            .default(0),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // Старые транзакции шли напрямую владельцу без комиссии
+        manager
+            .get_connection()
+            .execute_unprepared("UPDATE payment_transactions SET owner_amount = price")
+            .await?;
+
+        // Таблица fee_ledger: разбиение каждой транзакции на комиссию Krypton и долю владельца
+        manager
+            .create_table(
+                Table::create()
+                    .table(FeeLedger::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(FeeLedger::Id)
+                            .big_integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(FeeLedger::TransactionId)
+                            .big_integer()
+                            .not_null()
+                            .unique_key(),
+                    )
+                    .col(ColumnDef::new(FeeLedger::ChannelId).big_integer().not_null())
+                    .col(
+                        ColumnDef::new(FeeLedger::OwnerTelegramId)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(FeeLedger::Currency).string().not_null())
+                    .col(ColumnDef::new(FeeLedger::GrossAmount).decimal().not_null())
+                    .col(ColumnDef::new(FeeLedger::FeeAmount).decimal().not_null())
+                    .col(ColumnDef::new(FeeLedger::OwnerAmount).decimal().not_null())
+                    .col(ColumnDef::new(FeeLedger::FeePercent).decimal().not_null())
+                    .col(ColumnDef::new(FeeLedger::FeeMinimum).decimal().not_null())
+                    .col(
+                        ColumnDef::new(FeeLedger::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::cust("now()")),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(FeeLedger::Table, FeeLedger::TransactionId)
+                            .to(PaymentTransactions::Table, PaymentTransactions::Id),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(FeeLedger::Table, FeeLedger::ChannelId)
+                            .to(Channels::Table, Channels::ChannelId),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(FeeLedger::Table, FeeLedger::OwnerTelegramId)
+                            .to(Users::Table, Users::TelegramId),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(FeeLedger::Table).to_owned())
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(PaymentTransactions::Table)
+                    .drop_column(PaymentTransactions::PlatformFee)
+                    .drop_column(PaymentTransactions::OwnerAmount)
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum FeeLedger {
+    Table,
+    Id,
+    TransactionId,
+    ChannelId,
+    OwnerTelegramId,
+    Currency,
+    GrossAmount,
+    FeeAmount,
+    OwnerAmount,
+    FeePercent,
+    FeeMinimum,
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum PaymentTransactions {
+    Table,
+    Id,
+    PlatformFee,
+    OwnerAmount,
+}
+
+#[derive(DeriveIden)]
+enum Channels {
+    Table,
+    ChannelId,
+}
+
+#[derive(DeriveIden)]
+enum Users {
+    Table,
+    TelegramId,
+}
@@ -0,0 +1,215 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Таблица ledger_accounts
+        manager
+            .create_table(
+                Table::create()
+                    .table(LedgerAccounts::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(LedgerAccounts::Id)
+                            .big_integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(LedgerAccounts::Kind).string().not_null())
+                    // владелец канала для owner_payable, плательщик для payer, иначе null
+                    .col(ColumnDef::new(LedgerAccounts::TelegramId).big_integer().null())
+                    .col(ColumnDef::new(LedgerAccounts::Currency).string().not_null())
+                    .col(
+                        ColumnDef::new(LedgerAccounts::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::cust("now()")),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .get_connection()
+            .execute_unprepared(
+                "CREATE UNIQUE INDEX IF NOT EXISTS ledger_accounts_kind_telegram_id_currency_idx \
+                 ON ledger_accounts (kind, COALESCE(telegram_id, 0), currency)",
+            )
+            .await?;
+
+        // Таблица ledger_journals: одна проводка = одно денежное событие
+        manager
+            .create_table(
+                Table::create()
+                    .table(LedgerJournals::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(LedgerJournals::Id)
+                            .big_integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(LedgerJournals::Kind).string().not_null())
+                    // id платежа, возврата или выплаты — в зависимости от kind
+                    .col(ColumnDef::new(LedgerJournals::ReferenceId).big_integer().not_null())
+                    .col(ColumnDef::new(LedgerJournals::Currency).string().not_null())
+                    .col(ColumnDef::new(LedgerJournals::Description).string().not_null())
+                    .col(
+                        ColumnDef::new(LedgerJournals::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::cust("now()")),
+                    )
+                    .index(
+                        Index::create()
+                            .name("ledger_journals_kind_reference_id_idx")
+                            .unique()
+                            .col(LedgerJournals::Kind)
+                            .col(LedgerJournals::ReferenceId),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // Таблица ledger_entries: amount > 0 — дебет, amount < 0 — кредит
+        manager
+            .create_table(
+                Table::create()
+                    .table(LedgerEntries::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(LedgerEntries::Id)
+                            .big_integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(LedgerEntries::JournalId).big_integer().not_null())
+                    .col(ColumnDef::new(LedgerEntries::AccountId).big_integer().not_null())
+                    .col(ColumnDef::new(LedgerEntries::Amount).decimal().not_null())
+                    .col(
+                        ColumnDef::new(LedgerEntries::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::cust("now()")),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(LedgerEntries::Table, LedgerEntries::JournalId)
+                            .to(LedgerJournals::Table, LedgerJournals::Id),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(LedgerEntries::Table, LedgerEntries::AccountId)
+                            .to(LedgerAccounts::Table, LedgerAccounts::Id),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("ledger_entries_account_id_idx")
+                    .table(LedgerEntries::Table)
+                    .col(LedgerEntries::AccountId)
+                    .to_owned(),
+            )
+            .await?;
+
+        // Проводки неизменяемы, а сумма по журналу должна быть нулевой на момент коммита
+        manager
+            .get_connection()
+            .execute_unprepared(
+                r#"
+                CREATE OR REPLACE FUNCTION ledger_immutable() RETURNS trigger AS $$
+                BEGIN
+                    RAISE EXCEPTION 'ledger rows are immutable (%)', TG_TABLE_NAME;
+                END;
+                $$ LANGUAGE plpgsql;
+
+                CREATE TRIGGER ledger_journals_immutable
+                    BEFORE UPDATE OR DELETE ON ledger_journals
+                    FOR EACH ROW EXECUTE FUNCTION ledger_immutable();
+
+                CREATE TRIGGER ledger_entries_immutable
+                    BEFORE UPDATE OR DELETE ON ledger_entries
+                    FOR EACH ROW EXECUTE FUNCTION ledger_immutable();
+
+                CREATE OR REPLACE FUNCTION ledger_journal_balanced() RETURNS trigger AS $$
+                BEGIN
+                    IF (SELECT COALESCE(SUM(amount), 0) FROM ledger_entries
+                        WHERE journal_id = NEW.journal_id) <> 0 THEN
+                        RAISE EXCEPTION 'ledger journal % is not balanced', NEW.journal_id;
+                    END IF;
+                    RETURN NULL;
+                END;
+                $$ LANGUAGE plpgsql;
+
+                CREATE CONSTRAINT TRIGGER ledger_entries_balanced
+                    AFTER INSERT ON ledger_entries
+                    DEFERRABLE INITIALLY DEFERRED
+                    FOR EACH ROW EXECUTE FUNCTION ledger_journal_balanced();
+                "#,
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(LedgerEntries::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(LedgerJournals::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(LedgerAccounts::Table).to_owned())
+            .await?;
+        manager
+            .get_connection()
+            .execute_unprepared(
+                "DROP FUNCTION IF EXISTS ledger_immutable(); \
+                 DROP FUNCTION IF EXISTS ledger_journal_balanced();",
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum LedgerAccounts {
+    Table,
+    Id,
+    Kind,
+    TelegramId,
+    Currency,
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum LedgerJournals {
+    Table,
+    Id,
+    Kind,
+    ReferenceId,
+    Currency,
+    Description,
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum LedgerEntries {
+    Table,
+    Id,
+    JournalId,
+    AccountId,
+    Amount,
+    CreatedAt,
+}
@@ -0,0 +1,157 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Таблица payout_batches: один подписанный перевод с несколькими сообщениями
+        manager
+            .create_table(
+                Table::create()
+                    .table(PayoutBatches::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(PayoutBatches::Id)
+                            .big_integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(PayoutBatches::Status).string().not_null())
+                    .col(ColumnDef::new(PayoutBatches::Currency).string().not_null())
+                    .col(ColumnDef::new(PayoutBatches::SignedBoc).text().null())
+                    .col(ColumnDef::new(PayoutBatches::MessageHash).string().null())
+                    .col(ColumnDef::new(PayoutBatches::Error).text().null())
+                    .col(
+                        ColumnDef::new(PayoutBatches::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::cust("now()")),
+                    )
+                    .col(
+                        ColumnDef::new(PayoutBatches::UpdatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::cust("now()")),
+                    )
+                    .col(
+                        ColumnDef::new(PayoutBatches::ConfirmedAt)
+                            .timestamp_with_time_zone()
+                            .null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // Таблица payouts: выплата одному владельцу внутри батча
+        manager
+            .create_table(
+                Table::create()
+                    .table(Payouts::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Payouts::Id)
+                            .big_integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Payouts::BatchId).big_integer().not_null())
+                    .col(
+                        ColumnDef::new(Payouts::OwnerTelegramId)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(Payouts::Currency).string().not_null())
+                    .col(ColumnDef::new(Payouts::Amount).decimal().not_null())
+                    .col(
+                        ColumnDef::new(Payouts::DestinationAddress)
+                            .string()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(Payouts::Status).string().not_null())
+                    .col(
+                        ColumnDef::new(Payouts::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::cust("now()")),
+                    )
+                    .col(
+                        ColumnDef::new(Payouts::ConfirmedAt)
+                            .timestamp_with_time_zone()
+                            .null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(Payouts::Table, Payouts::BatchId)
+                            .to(PayoutBatches::Table, PayoutBatches::Id),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(Payouts::Table, Payouts::OwnerTelegramId)
+                            .to(Users::Table, Users::TelegramId),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("payouts_owner_telegram_id_status_idx")
+                    .table(Payouts::Table)
+                    .col(Payouts::OwnerTelegramId)
+                    .col(Payouts::Status)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Payouts::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(PayoutBatches::Table).to_owned())
+            .await?;
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum PayoutBatches {
+    Table,
+    Id,
+    Status,
+    Currency,
+    SignedBoc,
+    MessageHash,
+    Error,
+    CreatedAt,
+    UpdatedAt,
+    ConfirmedAt,
+}
+
+#[derive(DeriveIden)]
+enum Payouts {
+    Table,
+    Id,
+    BatchId,
+    OwnerTelegramId,
+    Currency,
+    Amount,
+    DestinationAddress,
+    Status,
+    CreatedAt,
+    ConfirmedAt,
+}
+
+#[derive(DeriveIden)]
+enum Users {
+    Table,
+    TelegramId,
+}
@@ -0,0 +1,172 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(PaymentTransactions::Table)
+                    .add_column(
+                        ColumnDef::new(PaymentTransactions::ExpiresAt)
+                            .timestamp_with_time_zone()
+                            .null(),
+                    )
+                    .add_column(
+                        ColumnDef::new(PaymentTransactions::RefundedAmount)
+                            .decimal()
+                            .not_null()
+                            .default(0),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .get_connection()
+            .execute_unprepared(
+                "UPDATE payment_transactions SET expires_at = created_at + interval '1 hour'",
+            )
+            .await?;
+
+        // Таблица refunds
+        manager
+            .create_table(
+                Table::create()
+                    .table(Refunds::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Refunds::Id)
+                            .big_integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Refunds::TransactionId).big_integer().not_null())
+                    .col(ColumnDef::new(Refunds::TelegramId).big_integer().not_null())
+                    .col(ColumnDef::new(Refunds::ChannelId).big_integer().not_null())
+                    .col(ColumnDef::new(Refunds::Reason).string().not_null())
+                    .col(ColumnDef::new(Refunds::Amount).decimal().not_null())
+                    .col(ColumnDef::new(Refunds::Currency).string().not_null())
+                    .col(
+                        ColumnDef::new(Refunds::DestinationAddress)
+                            .string()
+                            .not_null(),
+                    )
+                    // хеш входящей транзакции в сети — защита от повторного создания возврата
+                    .col(
+                        ColumnDef::new(Refunds::SourceTxHash)
+                            .string()
+                            .not_null()
+                            .unique_key(),
+                    )
+                    .col(ColumnDef::new(Refunds::Status).string().not_null())
+                    .col(ColumnDef::new(Refunds::DecidedBy).big_integer().null())
+                    .col(
+                        ColumnDef::new(Refunds::DecidedAt)
+                            .timestamp_with_time_zone()
+                            .null(),
+                    )
+                    .col(ColumnDef::new(Refunds::SignedBoc).text().null())
+                    .col(ColumnDef::new(Refunds::MessageHash).string().null())
+                    .col(ColumnDef::new(Refunds::Error).text().null())
+                    .col(
+                        ColumnDef::new(Refunds::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::cust("now()")),
+                    )
+                    .col(
+                        ColumnDef::new(Refunds::UpdatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::cust("now()")),
+                    )
+                    .col(
+                        ColumnDef::new(Refunds::ConfirmedAt)
+                            .timestamp_with_time_zone()
+                            .null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(Refunds::Table, Refunds::TransactionId)
+                            .to(PaymentTransactions::Table, PaymentTransactions::Id),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(Refunds::Table, Refunds::ChannelId)
+                            .to(Channels::Table, Channels::ChannelId),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("refunds_status_idx")
+                    .table(Refunds::Table)
+                    .col(Refunds::Status)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Refunds::Table).to_owned())
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(PaymentTransactions::Table)
+                    .drop_column(PaymentTransactions::ExpiresAt)
+                    .drop_column(PaymentTransactions::RefundedAmount)
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum Refunds {
+    Table,
+    Id,
+    TransactionId,
+    TelegramId,
+    ChannelId,
+    Reason,
+    Amount,
+    Currency,
+    DestinationAddress,
+    SourceTxHash,
+    Status,
+    DecidedBy,
+    DecidedAt,
+    SignedBoc,
+    MessageHash,
+    Error,
+    CreatedAt,
+    UpdatedAt,
+    ConfirmedAt,
+}
+
+#[derive(DeriveIden)]
+enum PaymentTransactions {
+    Table,
+    Id,
+    ExpiresAt,
+    RefundedAmount,
+}
+
+#[derive(DeriveIden)]
+enum Channels {
+    Table,
+    ChannelId,
+}
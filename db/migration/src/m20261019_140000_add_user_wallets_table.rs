@@ -0,0 +1,85 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Таблица user_wallets: кошельки, владение которыми подтверждено через TON Proof
+        manager
+            .create_table(
+                Table::create()
+                    .table(UserWallets::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(UserWallets::Id)
+                            .big_integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(UserWallets::TelegramId).big_integer().not_null())
+                    // raw-адрес вида 0:<hex>, один кошелёк принадлежит одному пользователю
+                    .col(
+                        ColumnDef::new(UserWallets::Address)
+                            .string()
+                            .not_null()
+                            .unique_key(),
+                    )
+                    .col(
+                        ColumnDef::new(UserWallets::VerifiedAt)
+                            .timestamp_with_time_zone()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(UserWallets::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::cust("now()")),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(UserWallets::Table, UserWallets::TelegramId)
+                            .to(Users::Table, Users::TelegramId),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("user_wallets_telegram_id_idx")
+                    .table(UserWallets::Table)
+                    .col(UserWallets::TelegramId)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(UserWallets::Table).to_owned())
+            .await?;
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum UserWallets {
+    Table,
+    Id,
+    TelegramId,
+    Address,
+    VerifiedAt,
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum Users {
+    Table,
+    TelegramId,
+}
@@ -0,0 +1,44 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Внешний идентификатор транзакции: в ссылках и API вместо последовательного id
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(PaymentTransactions::Table)
+                    .add_column(
+                        ColumnDef::new(PaymentTransactions::PublicId)
+                            .uuid()
+                            .not_null()
+                            .default(Expr::cust("gen_random_uuid()"))
+                            .unique_key(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(PaymentTransactions::Table)
+                    .drop_column(PaymentTransactions::PublicId)
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum PaymentTransactions {
+    Table,
+    PublicId,
+}
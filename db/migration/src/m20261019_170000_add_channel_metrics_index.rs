@@ -0,0 +1,44 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Метрики владельца выбирают транзакции одного канала по статусу и дате оплаты;
+        // подписки уже покрыты первичным ключом, который начинается с channel_id
+        manager
+            .create_index(
+                Index::create()
+                    .name("payment_transactions_channel_status_idx")
+                    .table(PaymentTransactions::Table)
+                    .col(PaymentTransactions::ChannelId)
+                    .col(PaymentTransactions::Status)
+                    .col(PaymentTransactions::CompletedAt)
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("payment_transactions_channel_status_idx")
+                    .table(PaymentTransactions::Table)
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum PaymentTransactions {
+    Table,
+    ChannelId,
+    Status,
+    CompletedAt,
+}
@@ -0,0 +1,172 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Ключи, с которыми владельцы создают счета через API. Сам ключ не храним, только sha256
+        manager
+            .create_table(
+                Table::create()
+                    .table(ApiKeys::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ApiKeys::Id)
+                            .big_integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(ApiKeys::OwnerTelegramId).big_integer().not_null())
+                    .col(ColumnDef::new(ApiKeys::Name).text().not_null())
+                    .col(ColumnDef::new(ApiKeys::Prefix).text().not_null())
+                    .col(ColumnDef::new(ApiKeys::KeyHash).text().not_null().unique_key())
+                    .col(
+                        ColumnDef::new(ApiKeys::Scopes)
+                            .json()
+                            .not_null()
+                            .default(Expr::cust("'[]'")),
+                    )
+                    .col(
+                        ColumnDef::new(ApiKeys::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::cust("now()")),
+                    )
+                    .col(ColumnDef::new(ApiKeys::LastUsedAt).timestamp_with_time_zone().null())
+                    .col(ColumnDef::new(ApiKeys::RevokedAt).timestamp_with_time_zone().null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .from(ApiKeys::Table, ApiKeys::OwnerTelegramId)
+                            .to(Users::Table, Users::TelegramId),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // Счета из API могут быть без канала и без плательщика в Telegram
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(PaymentTransactions::Table)
+                    .modify_column(ColumnDef::new(PaymentTransactions::TelegramId).big_integer().null())
+                    .modify_column(ColumnDef::new(PaymentTransactions::ChannelId).big_integer().null())
+                    .modify_column(ColumnDef::new(PaymentTransactions::ChatId).big_integer().null())
+                    .modify_column(ColumnDef::new(PaymentTransactions::MessageId).big_integer().null())
+                    .add_column(ColumnDef::new(PaymentTransactions::Description).text().null())
+                    .add_column(ColumnDef::new(PaymentTransactions::ApiKeyId).big_integer().null())
+                    .add_foreign_key(
+                        TableForeignKey::new()
+                            .name("payment_transactions_api_key_id_fkey")
+                            .from_tbl(PaymentTransactions::Table)
+                            .from_col(PaymentTransactions::ApiKeyId)
+                            .to_tbl(ApiKeys::Table)
+                            .to_col(ApiKeys::Id),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(FeeLedger::Table)
+                    .modify_column(ColumnDef::new(FeeLedger::ChannelId).big_integer().null())
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Refunds::Table)
+                    .modify_column(ColumnDef::new(Refunds::TelegramId).big_integer().null())
+                    .modify_column(ColumnDef::new(Refunds::ChannelId).big_integer().null())
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Refunds::Table)
+                    .modify_column(ColumnDef::new(Refunds::TelegramId).big_integer().not_null())
+                    .modify_column(ColumnDef::new(Refunds::ChannelId).big_integer().not_null())
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(FeeLedger::Table)
+                    .modify_column(ColumnDef::new(FeeLedger::ChannelId).big_integer().not_null())
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(PaymentTransactions::Table)
+                    .drop_foreign_key(Alias::new("payment_transactions_api_key_id_fkey"))
+                    .drop_column(PaymentTransactions::ApiKeyId)
+                    .drop_column(PaymentTransactions::Description)
+                    .modify_column(ColumnDef::new(PaymentTransactions::TelegramId).big_integer().not_null())
+                    .modify_column(ColumnDef::new(PaymentTransactions::ChannelId).big_integer().not_null())
+                    .modify_column(ColumnDef::new(PaymentTransactions::ChatId).big_integer().not_null())
+                    .modify_column(ColumnDef::new(PaymentTransactions::MessageId).big_integer().not_null())
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .drop_table(Table::drop().table(ApiKeys::Table).to_owned())
+            .await?;
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum ApiKeys {
+    Table,
+    Id,
+    OwnerTelegramId,
+    Name,
+    Prefix,
+    KeyHash,
+    Scopes,
+    CreatedAt,
+    LastUsedAt,
+    RevokedAt,
+}
+
+#[derive(DeriveIden)]
+enum Users {
+    Table,
+    TelegramId,
+}
+
+#[derive(DeriveIden)]
+enum PaymentTransactions {
+    Table,
+    TelegramId,
+    ChannelId,
+    ChatId,
+    MessageId,
+    Description,
+    ApiKeyId,
+}
+
+#[derive(DeriveIden)]
+enum FeeLedger {
+    Table,
+    ChannelId,
+}
+
+#[derive(DeriveIden)]
+enum Refunds {
+    Table,
+    TelegramId,
+    ChannelId,
+}
@@ -0,0 +1,145 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Каналы владельца: /info, /price, /slug, выплаты и кабинет в API
+        manager
+            .create_index(
+                Index::create()
+                    .name("channels_owner_telegram_id_idx")
+                    .table(Channels::Table)
+                    .col(Channels::OwnerTelegramId)
+                    .to_owned(),
+            )
+            .await?;
+
+        // Бот ищет канал по началу названия, каталог API — по подстроке без учёта регистра;
+        // триграммный индекс обслуживает и LIKE, и ILIKE
+        manager
+            .get_connection()
+            .execute_unprepared(
+                r#"
+                CREATE EXTENSION IF NOT EXISTS pg_trgm;
+                CREATE INDEX channels_title_trgm_idx ON channels USING gin (title gin_trgm_ops);
+                CREATE INDEX channels_slug_trgm_idx ON channels USING gin (slug gin_trgm_ops);
+                "#,
+            )
+            .await?;
+
+        // Из старых дублей открытым остаётся самый новый, иначе уникальный индекс не создать
+        manager
+            .get_connection()
+            .execute_unprepared(
+                r#"
+                UPDATE payment_transactions SET status = 'expired'
+                WHERE status = 'active' AND EXISTS (
+                    SELECT 1 FROM payment_transactions newer
+                    WHERE newer.status = 'active'
+                      AND newer.telegram_id = payment_transactions.telegram_id
+                      AND newer.channel_id = payment_transactions.channel_id
+                      AND newer.id > payment_transactions.id
+                );
+                "#,
+            )
+            .await?;
+
+        // Одна открытая транзакция на пользователя и канал. Счета API без плательщика
+        // или канала под индекс не попадают: NULL не равен NULL
+        manager
+            .create_index(
+                Index::create()
+                    .name("payment_transactions_open_uniq")
+                    .table(PaymentTransactions::Table)
+                    .col(PaymentTransactions::TelegramId)
+                    .col(PaymentTransactions::ChannelId)
+                    .unique()
+                    .and_where(Expr::col(PaymentTransactions::Status).eq("active"))
+                    .to_owned(),
+            )
+            .await?;
+
+        // Канал без положительной цены и так не продаётся, цену просто сбрасываем.
+        // Старые транзакции — история: проверка действует для новых строк, а валидируется,
+        // только если в истории нет нарушений
+        manager
+            .get_connection()
+            .execute_unprepared(
+                r#"
+                UPDATE channels SET monthly_price = NULL WHERE monthly_price <= 0;
+                ALTER TABLE channels
+                    ADD CONSTRAINT channels_monthly_price_positive CHECK (monthly_price > 0);
+                ALTER TABLE payment_transactions
+                    ADD CONSTRAINT payment_transactions_price_positive CHECK (price > 0) NOT VALID,
+                    ADD CONSTRAINT payment_transactions_original_price_positive
+                        CHECK (original_price > 0) NOT VALID;
+                DO $$
+                BEGIN
+                    IF NOT EXISTS (SELECT 1 FROM payment_transactions WHERE price <= 0) THEN
+                        ALTER TABLE payment_transactions
+                            VALIDATE CONSTRAINT payment_transactions_price_positive;
+                    END IF;
+                    IF NOT EXISTS (SELECT 1 FROM payment_transactions WHERE original_price <= 0) THEN
+                        ALTER TABLE payment_transactions
+                            VALIDATE CONSTRAINT payment_transactions_original_price_positive;
+                    END IF;
+                END
+                $$;
+                "#,
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // pg_trgm оставляем: расширение могут использовать не только наши индексы
+        manager
+            .get_connection()
+            .execute_unprepared(
+                r#"
+                ALTER TABLE payment_transactions
+                    DROP CONSTRAINT payment_transactions_original_price_positive,
+                    DROP CONSTRAINT payment_transactions_price_positive;
+                ALTER TABLE channels DROP CONSTRAINT channels_monthly_price_positive;
+                DROP INDEX channels_slug_trgm_idx;
+                DROP INDEX channels_title_trgm_idx;
+                "#,
+            )
+            .await?;
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("payment_transactions_open_uniq")
+                    .table(PaymentTransactions::Table)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("channels_owner_telegram_id_idx")
+                    .table(Channels::Table)
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum Channels {
+    Table,
+    OwnerTelegramId,
+}
+
+#[derive(DeriveIden)]
+enum PaymentTransactions {
+    Table,
+    TelegramId,
+    ChannelId,
+    Status,
+}
@@ -0,0 +1,73 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // До какого момента сеть ещё может принять подписанное сообщение
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(PayoutBatches::Table)
+                    .add_column(ColumnDef::new(PayoutBatches::ValidUntil).timestamp_with_time_zone())
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Refunds::Table)
+                    .add_column(ColumnDef::new(Refunds::ValidUntil).timestamp_with_time_zone())
+                    .to_owned(),
+            )
+            .await?;
+
+        // Уже подписанные сообщения жили по прежним 15 минутам от подписи
+        manager
+            .get_connection()
+            .execute_unprepared(
+                r#"
+                UPDATE payout_batches SET valid_until = created_at + interval '15 minutes'
+                WHERE status IN ('signed', 'sent');
+                UPDATE refunds SET valid_until = updated_at + interval '15 minutes'
+                WHERE status IN ('signed', 'sent');
+                "#,
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Refunds::Table)
+                    .drop_column(Refunds::ValidUntil)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(PayoutBatches::Table)
+                    .drop_column(PayoutBatches::ValidUntil)
+                    .to_owned(),
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum PayoutBatches {
+    Table,
+    ValidUntil,
+}
+
+#[derive(DeriveIden)]
+enum Refunds {
+    Table,
+    ValidUntil,
+}
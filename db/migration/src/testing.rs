@@ -0,0 +1,62 @@
+//! Throwaway Postgres databases for tests, behind the `testing` feature. Tests using them
+//! are `#[ignore]`d and run with `TEST_DATABASE_URL` pointing to a connection allowed to
+//! create databases: `TEST_DATABASE_URL=postgres://postgres@localhost:5432/postgres
+//! cargo test -- --ignored`.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use sea_orm_migration::sea_orm::{ConnectionTrait, Database, DatabaseConnection};
+
+static NEXT: AtomicUsize = AtomicUsize::new(0);
+
+/// A database of its own for one test, with every migration applied.
+pub struct TestDatabase {
+    pub db: DatabaseConnection,
+    admin: DatabaseConnection,
+    name: String,
+}
+
+/// `url` pointing to the database `name` on the same server.
+fn with_database(url: &str, name: &str) -> String {
+    let (server, rest) = url
+        .rsplit_once('/')
+        .expect("TEST_DATABASE_URL has no database name");
+    match rest.split_once('?') {
+        Some((_, params)) => format!("{}/{}?{}", server, name, params),
+        None => format!("{}/{}", server, name),
+    }
+}
+
+impl TestDatabase {
+    pub async fn migrated() -> Self {
+        let url = std::env::var("TEST_DATABASE_URL")
+            .expect("TEST_DATABASE_URL must point to a Postgres server allowed to create databases");
+        let admin = Database::connect(&url).await.unwrap();
+        let name = format!(
+            "krypton_test_{}_{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        );
+        admin
+            .execute_unprepared(&format!("DROP DATABASE IF EXISTS {}", name))
+            .await
+            .unwrap();
+        admin
+            .execute_unprepared(&format!("CREATE DATABASE {}", name))
+            .await
+            .unwrap();
+        let db = Database::connect(with_database(&url, &name)).await.unwrap();
+        crate::up_on_boot(&db).await.expect("migrations failed");
+        TestDatabase { db, admin, name }
+    }
+
+    /// Drops the database. A test that panics before this leaves it behind, named after
+    /// the test process.
+    pub async fn drop(self) {
+        self.db.close().await.unwrap();
+        self.admin
+            .execute_unprepared(&format!("DROP DATABASE {}", self.name))
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,127 @@
+//! Applies every migration to a throwaway Postgres database and checks that each entity of
+//! the `db` crate matches the tables they produce: every column exists, with the type and
+//! nullability the entity declares. `text` and `varchar` count as the same type, Postgres
+//! handles them alike and the entities write every string column as `Text`.
+//!
+//! Needs a Postgres server, so it's ignored by default. Run it with `TEST_DATABASE_URL`
+//! pointing to a connection allowed to create databases:
+//! `TEST_DATABASE_URL=postgres://postgres@localhost:5432/postgres cargo test -- --ignored`.
+
+use std::collections::HashMap;
+
+use migration::testing::TestDatabase;
+use sea_orm::{
+    ColumnTrait, ColumnType, ConnectionTrait, DatabaseConnection, EntityTrait, FromQueryResult,
+    Iden, Iterable, Statement,
+};
+
+#[derive(FromQueryResult)]
+struct DbColumn {
+    column_name: String,
+    data_type: String,
+    is_nullable: String,
+}
+
+/// How Postgres reports `column_type` in `information_schema.columns.data_type`.
+fn postgres_type(column_type: &ColumnType) -> String {
+    match column_type {
+        ColumnType::Char(_) => "character",
+        ColumnType::String(_) => "character varying",
+        ColumnType::Text => "text",
+        ColumnType::SmallInteger => "smallint",
+        ColumnType::Integer => "integer",
+        ColumnType::BigInteger => "bigint",
+        ColumnType::Float => "real",
+        ColumnType::Double => "double precision",
+        ColumnType::Decimal(_) | ColumnType::Money(_) => "numeric",
+        ColumnType::DateTime | ColumnType::Timestamp => "timestamp without time zone",
+        ColumnType::TimestampWithTimeZone => "timestamp with time zone",
+        ColumnType::Date => "date",
+        ColumnType::Time => "time without time zone",
+        ColumnType::Boolean => "boolean",
+        ColumnType::Json => "json",
+        ColumnType::JsonBinary => "jsonb",
+        ColumnType::Uuid => "uuid",
+        ColumnType::Binary(_) | ColumnType::VarBinary(_) | ColumnType::Blob => "bytea",
+        other => return format!("{:?}", other),
+    }
+    .to_string()
+}
+
+async fn check<E: EntityTrait + Default>(db: &DatabaseConnection, problems: &mut Vec<String>) {
+    let table = E::default().table_name().to_string();
+    let columns: HashMap<String, DbColumn> = DbColumn::find_by_statement(Statement::from_sql_and_values(
+        db.get_database_backend(),
+        "SELECT column_name, data_type, is_nullable FROM information_schema.columns
+         WHERE table_schema = 'public' AND table_name = $1",
+        [table.clone().into()],
+    ))
+    .all(db)
+    .await
+    .unwrap()
+    .into_iter()
+    .map(|column| (column.column_name.clone(), column))
+    .collect();
+    if columns.is_empty() {
+        problems.push(format!("{}: no such table", table));
+        return;
+    }
+
+    for column in E::Column::iter() {
+        let name = column.to_string();
+        let def = column.def();
+        let Some(actual) = columns.get(&name) else {
+            problems.push(format!("{}.{}: no such column", table, name));
+            continue;
+        };
+        let expected = postgres_type(def.get_column_type());
+        let strings = ["text", "character varying"];
+        let same_string = strings.contains(&expected.as_str()) && strings.contains(&actual.data_type.as_str());
+        if actual.data_type != expected && !same_string {
+            problems.push(format!(
+                "{}.{}: entity says {}, schema has {}",
+                table, name, expected, actual.data_type
+            ));
+        }
+        let nullable = actual.is_nullable == "YES";
+        if def.is_null() != nullable {
+            problems.push(format!(
+                "{}.{}: entity says {}, schema has {}",
+                table,
+                name,
+                if def.is_null() { "nullable" } else { "not null" },
+                if nullable { "nullable" } else { "not null" },
+            ));
+        }
+    }
+}
+
+#[tokio::test]
+#[ignore = "needs Postgres, run with TEST_DATABASE_URL=... cargo test -- --ignored"]
+async fn entities_match_migrated_schema() {
+    let test_db = TestDatabase::migrated().await;
+    let db = &test_db.db;
+    let mut problems = Vec::new();
+    check::<db::User>(db, &mut problems).await;
+    check::<db::Channel>(db, &mut problems).await;
+    check::<db::Settings>(db, &mut problems).await;
+    check::<db::Membership>(db, &mut problems).await;
+    check::<db::Transaction>(db, &mut problems).await;
+    check::<db::invite_link::Entity>(db, &mut problems).await;
+    check::<db::subscriptions::Entity>(db, &mut problems).await;
+    check::<db::PromoCode>(db, &mut problems).await;
+    check::<db::FeeLedger>(db, &mut problems).await;
+    check::<db::LedgerAccount>(db, &mut problems).await;
+    check::<db::LedgerJournal>(db, &mut problems).await;
+    check::<db::LedgerEntry>(db, &mut problems).await;
+    check::<db::PayoutBatch>(db, &mut problems).await;
+    check::<db::Payout>(db, &mut problems).await;
+    check::<db::Refund>(db, &mut problems).await;
+    check::<db::UserWallet>(db, &mut problems).await;
+    check::<db::WebhookEndpoint>(db, &mut problems).await;
+    check::<db::WebhookDelivery>(db, &mut problems).await;
+    check::<db::ApiKey>(db, &mut problems).await;
+    test_db.drop().await;
+
+    assert!(problems.is_empty(), "entities diverge from migrations:\n{}", problems.join("\n"));
+}
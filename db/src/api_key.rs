@@ -0,0 +1,67 @@
+use chrono::{DateTime, Utc};
+use sea_orm::DeriveEntityModel;
+use sea_orm::entity::prelude::*;
+use serde_json::Value;
+
+/// Lets the key create invoices.
+pub const SCOPE_INVOICES_WRITE: &str = "invoices:write";
+/// Lets the key read invoices it or another key of the same owner created.
+pub const SCOPE_INVOICES_READ: &str = "invoices:read";
+pub const SCOPES: [&str; 2] = [SCOPE_INVOICES_WRITE, SCOPE_INVOICES_READ];
+
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
+#[sea_orm(table_name = "api_keys")]
+pub struct Model {
+    #[sea_orm(primary_key, column_type = "BigInteger")]
+    pub id: i64,
+    #[sea_orm(column_type = "BigInteger")]
+    pub owner_telegram_id: i64,
+    #[sea_orm(column_type = "Text")]
+    pub name: String,
+    // first characters of the key, to tell keys apart in lists
+    #[sea_orm(column_type = "Text")]
+    pub prefix: String,
+    // hex sha256 of the key, the key itself is shown to the owner once
+    #[sea_orm(column_type = "Text", unique)]
+    pub key_hash: String,
+    #[sea_orm(column_type = "Json", default_value = "[]")]
+    pub scopes: Value,
+    #[sea_orm(column_type = "TimestampWithTimeZone", default_value = "now()")]
+    pub created_at: DateTime<Utc>,
+    #[sea_orm(column_type = "TimestampWithTimeZone")]
+    pub last_used_at: Option<DateTime<Utc>>,
+    #[sea_orm(column_type = "TimestampWithTimeZone")]
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl Model {
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes
+            .as_array()
+            .is_some_and(|scopes| scopes.iter().any(|s| s.as_str() == Some(scope)))
+    }
+}
+
+#[derive(Copy, Clone, Debug, EnumIter)]
+pub enum Relation {
+    User,
+}
+
+impl RelationTrait for Relation {
+    fn def(&self) -> RelationDef {
+        match self {
+            Self::User => Entity::belongs_to(super::user::Entity)
+                .from(Column::OwnerTelegramId)
+                .to(super::user::Column::TelegramId)
+                .into(),
+        }
+    }
+}
+
+impl Related<super::User> for Entity {
+    fn to() -> RelationDef {
+        Relation::User.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
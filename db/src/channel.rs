@@ -19,18 +19,37 @@ pub struct Model {
     pub description: Option<String>,
     #[sea_orm(column_type = "Decimal(None)")]
     pub monthly_price: Option<Decimal>,
-    #[sea_orm(column_type = "Timestamp")]
+    #[sea_orm(column_type = "TimestampWithTimeZone")]
     pub bot_added_at: DateTime<Utc>,
-    #[sea_orm(column_type = "Timestamp", default_value = "now()")]
+    #[sea_orm(column_type = "TimestampWithTimeZone", default_value = "now()")]
     pub created_at: DateTime<Utc>,
     #[sea_orm(column_type = "Json", default_value = "{}")]
     pub settings: Value,
     #[sea_orm(column_type = "Boolean", default_value = "true")]
     pub is_active: bool,
-    #[sea_orm(column_type = "Timestamp", default_value = "now()")]
+    #[sea_orm(column_type = "TimestampWithTimeZone", default_value = "now()")]
     pub last_check_date: DateTime<Utc>,
     #[sea_orm(column_type = "Text")]
-    pub crypto_address: Option<String>
+    pub crypto_address: Option<String>,
+    // public name for the channel page, see is_valid_slug
+    #[sea_orm(column_type = "Text", unique)]
+    pub slug: Option<String>
+}
+
+/// The only plan a channel sells: `monthly_price` for this many days of access.
+pub const SUBSCRIPTION_PERIOD_DAYS: i64 = 30;
+/// Currency subscriptions are priced and paid in.
+pub const SUBSCRIPTION_CURRENCY: &str = "USDT";
+
+/// Slugs are 3-32 lowercase latin letters, digits and dashes, start with a letter and
+/// can't end with a dash, so they never collide with numeric channel ids in URLs.
+pub fn is_valid_slug(slug: &str) -> bool {
+    (3..=32).contains(&slug.len())
+        && slug.starts_with(|c: char| c.is_ascii_lowercase())
+        && !slug.ends_with('-')
+        && slug
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
 }
 
 #[derive(Copy, Clone, Debug, EnumIter)]
@@ -0,0 +1,78 @@
+use chrono::{DateTime, Utc};
+use sea_orm::DeriveEntityModel;
+use sea_orm::entity::prelude::*;
+
+// One row per payment transaction: how the gross amount is split between Krypton and the owner
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
+#[sea_orm(table_name = "fee_ledger")]
+pub struct Model {
+    #[sea_orm(primary_key, column_type = "BigInteger")]
+    pub id: i64,
+    #[sea_orm(column_type = "BigInteger", unique)]
+    pub transaction_id: i64,
+    #[sea_orm(column_type = "BigInteger")]
+    pub channel_id: Option<i64>,
+    #[sea_orm(column_type = "BigInteger")]
+    pub owner_telegram_id: i64,
+    #[sea_orm(column_type = "Text")]
+    pub currency: String,
+    #[sea_orm(column_type = "Decimal(None)")]
+    pub gross_amount: Decimal,
+    #[sea_orm(column_type = "Decimal(None)")]
+    pub fee_amount: Decimal,
+    #[sea_orm(column_type = "Decimal(None)")]
+    pub owner_amount: Decimal,
+    // fee settings at the moment of the split
+    #[sea_orm(column_type = "Decimal(None)")]
+    pub fee_percent: Decimal,
+    #[sea_orm(column_type = "Decimal(None)")]
+    pub fee_minimum: Decimal,
+    #[sea_orm(column_type = "TimestampWithTimeZone", default_value = "now()")]
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter)]
+pub enum Relation {
+    Transaction,
+    Channel,
+    User,
+}
+
+impl RelationTrait for Relation {
+    fn def(&self) -> RelationDef {
+        match self {
+            Self::Transaction => Entity::belongs_to(super::transaction::Entity)
+                .from(Column::TransactionId)
+                .to(super::transaction::Column::Id)
+                .into(),
+            Self::Channel => Entity::belongs_to(super::channel::Entity)
+                .from(Column::ChannelId)
+                .to(super::channel::Column::ChannelId)
+                .into(),
+            Self::User => Entity::belongs_to(super::user::Entity)
+                .from(Column::OwnerTelegramId)
+                .to(super::user::Column::TelegramId)
+                .into(),
+        }
+    }
+}
+
+impl Related<super::Transaction> for Entity {
+    fn to() -> RelationDef {
+        Relation::Transaction.def()
+    }
+}
+
+impl Related<super::Channel> for Entity {
+    fn to() -> RelationDef {
+        Relation::Channel.def()
+    }
+}
+
+impl Related<super::User> for Entity {
+    fn to() -> RelationDef {
+        Relation::User.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -11,7 +11,7 @@ pub struct Model {
     pub user_id: i64,
     #[sea_orm(column_type = "BigInteger")]
     pub channel_id: i64,
-    #[sea_orm(column_type = "Timestamp")]
+    #[sea_orm(column_type = "TimestampWithTimeZone")]
     pub expires_at: DateTime<Utc>,
     #[sea_orm(column_type = "Boolean")]
     pub used: bool
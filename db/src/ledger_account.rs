@@ -0,0 +1,41 @@
+use chrono::{DateTime, Utc};
+use sea_orm::DeriveEntityModel;
+use sea_orm::entity::prelude::*;
+
+// kind = gateway, platform_fees, owner_payable, payer, refunds
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
+#[sea_orm(table_name = "ledger_accounts")]
+pub struct Model {
+    #[sea_orm(primary_key, column_type = "BigInteger")]
+    pub id: i64,
+    #[sea_orm(column_type = "Text")]
+    pub kind: String,
+    // channel owner for owner_payable, payer for payer, None for platform-wide accounts
+    #[sea_orm(column_type = "BigInteger")]
+    pub telegram_id: Option<i64>,
+    #[sea_orm(column_type = "Text")]
+    pub currency: String,
+    #[sea_orm(column_type = "TimestampWithTimeZone", default_value = "now()")]
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter)]
+pub enum Relation {
+    Entry,
+}
+
+impl RelationTrait for Relation {
+    fn def(&self) -> RelationDef {
+        match self {
+            Self::Entry => Entity::has_many(super::ledger_entry::Entity).into(),
+        }
+    }
+}
+
+impl Related<super::LedgerEntry> for Entity {
+    fn to() -> RelationDef {
+        Relation::Entry.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
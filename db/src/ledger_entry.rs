@@ -0,0 +1,54 @@
+use chrono::{DateTime, Utc};
+use sea_orm::DeriveEntityModel;
+use sea_orm::entity::prelude::*;
+
+// amount > 0 is a debit, amount < 0 is a credit; entries of one journal sum up to zero
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
+#[sea_orm(table_name = "ledger_entries")]
+pub struct Model {
+    #[sea_orm(primary_key, column_type = "BigInteger")]
+    pub id: i64,
+    #[sea_orm(column_type = "BigInteger")]
+    pub journal_id: i64,
+    #[sea_orm(column_type = "BigInteger")]
+    pub account_id: i64,
+    #[sea_orm(column_type = "Decimal(None)")]
+    pub amount: Decimal,
+    #[sea_orm(column_type = "TimestampWithTimeZone", default_value = "now()")]
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter)]
+pub enum Relation {
+    Journal,
+    Account,
+}
+
+impl RelationTrait for Relation {
+    fn def(&self) -> RelationDef {
+        match self {
+            Self::Journal => Entity::belongs_to(super::ledger_journal::Entity)
+                .from(Column::JournalId)
+                .to(super::ledger_journal::Column::Id)
+                .into(),
+            Self::Account => Entity::belongs_to(super::ledger_account::Entity)
+                .from(Column::AccountId)
+                .to(super::ledger_account::Column::Id)
+                .into(),
+        }
+    }
+}
+
+impl Related<super::LedgerJournal> for Entity {
+    fn to() -> RelationDef {
+        Relation::Journal.def()
+    }
+}
+
+impl Related<super::LedgerAccount> for Entity {
+    fn to() -> RelationDef {
+        Relation::Account.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,43 @@
+use chrono::{DateTime, Utc};
+use sea_orm::DeriveEntityModel;
+use sea_orm::entity::prelude::*;
+
+// kind = payment_confirmed, payment_unapplied, refund_approved, refund_sent, payout_sent
+// reference_id points to payment_transactions, refunds or payouts depending on kind
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
+#[sea_orm(table_name = "ledger_journals")]
+pub struct Model {
+    #[sea_orm(primary_key, column_type = "BigInteger")]
+    pub id: i64,
+    #[sea_orm(column_type = "Text")]
+    pub kind: String,
+    #[sea_orm(column_type = "BigInteger")]
+    pub reference_id: i64,
+    #[sea_orm(column_type = "Text")]
+    pub currency: String,
+    #[sea_orm(column_type = "Text")]
+    pub description: String,
+    #[sea_orm(column_type = "TimestampWithTimeZone", default_value = "now()")]
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter)]
+pub enum Relation {
+    Entry,
+}
+
+impl RelationTrait for Relation {
+    fn def(&self) -> RelationDef {
+        match self {
+            Self::Entry => Entity::has_many(super::ledger_entry::Entity).into(),
+        }
+    }
+}
+
+impl Related<super::LedgerEntry> for Entity {
+    fn to() -> RelationDef {
+        Relation::Entry.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -5,6 +5,18 @@ pub mod membership;
 pub mod transaction;
 pub mod invite_link;
 pub mod subscriptions;
+pub mod promo_code;
+pub mod fee_ledger;
+pub mod ledger_account;
+pub mod ledger_journal;
+pub mod ledger_entry;
+pub mod payout_batch;
+pub mod payout;
+pub mod refund;
+pub mod user_wallet;
+pub mod webhook_endpoint;
+pub mod webhook_delivery;
+pub mod api_key;
 
 pub use user::Entity as User;
 pub use user::ActiveModel as UserModel; 
@@ -15,4 +27,25 @@ pub use transaction::ActiveModel as TransactionModel;
 
 pub use settings::Entity as Settings;
 pub use membership::Entity as Membership;
-pub use membership::ActiveModel as MembershipModel;
\ No newline at end of file
+pub use membership::ActiveModel as MembershipModel;
+pub use promo_code::Entity as PromoCode;
+pub use promo_code::ActiveModel as PromoCodeModel;
+pub use fee_ledger::Entity as FeeLedger;
+pub use fee_ledger::ActiveModel as FeeLedgerModel;
+pub use ledger_account::Entity as LedgerAccount;
+pub use ledger_journal::Entity as LedgerJournal;
+pub use ledger_entry::Entity as LedgerEntry;
+pub use payout_batch::Entity as PayoutBatch;
+pub use payout_batch::ActiveModel as PayoutBatchModel;
+pub use payout::Entity as Payout;
+pub use payout::ActiveModel as PayoutModel;
+pub use refund::Entity as Refund;
+pub use refund::ActiveModel as RefundModel;
+pub use user_wallet::Entity as UserWallet;
+pub use user_wallet::ActiveModel as UserWalletModel;
+pub use webhook_endpoint::Entity as WebhookEndpoint;
+pub use webhook_endpoint::ActiveModel as WebhookEndpointModel;
+pub use webhook_delivery::Entity as WebhookDelivery;
+pub use webhook_delivery::ActiveModel as WebhookDeliveryModel;
+pub use api_key::Entity as ApiKey;
+pub use api_key::ActiveModel as ApiKeyModel;
@@ -10,9 +10,9 @@ pub struct Model {
     pub channel_id: i64,
     #[sea_orm(primary_key, column_type = "BigInteger" )]
     pub telegram_id: i64,
-    #[sea_orm(column_type = "Timestamp")]
+    #[sea_orm(column_type = "TimestampWithTimeZone")]
     pub subscription_start: DateTime<Utc>,
-    #[sea_orm(column_type = "Timestamp")]
+    #[sea_orm(column_type = "TimestampWithTimeZone")]
     pub subscription_end: DateTime<Utc>,
     #[sea_orm(column_type = "Json", default_value="[]")]
     pub payment_history: Value,
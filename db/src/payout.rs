@@ -0,0 +1,62 @@
+use chrono::{DateTime, Utc};
+use sea_orm::DeriveEntityModel;
+use sea_orm::entity::prelude::*;
+
+// status = pending, confirmed, failed (follows the batch)
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
+#[sea_orm(table_name = "payouts")]
+pub struct Model {
+    #[sea_orm(primary_key, column_type = "BigInteger")]
+    pub id: i64,
+    #[sea_orm(column_type = "BigInteger")]
+    pub batch_id: i64,
+    #[sea_orm(column_type = "BigInteger")]
+    pub owner_telegram_id: i64,
+    #[sea_orm(column_type = "Text")]
+    pub currency: String,
+    #[sea_orm(column_type = "Decimal(None)")]
+    pub amount: Decimal,
+    #[sea_orm(column_type = "Text")]
+    pub destination_address: String,
+    #[sea_orm(column_type = "Text")]
+    pub status: String,
+    #[sea_orm(column_type = "TimestampWithTimeZone", default_value = "now()")]
+    pub created_at: DateTime<Utc>,
+    #[sea_orm(column_type = "TimestampWithTimeZone")]
+    pub confirmed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter)]
+pub enum Relation {
+    Batch,
+    User,
+}
+
+impl RelationTrait for Relation {
+    fn def(&self) -> RelationDef {
+        match self {
+            Self::Batch => Entity::belongs_to(super::payout_batch::Entity)
+                .from(Column::BatchId)
+                .to(super::payout_batch::Column::Id)
+                .into(),
+            Self::User => Entity::belongs_to(super::user::Entity)
+                .from(Column::OwnerTelegramId)
+                .to(super::user::Column::TelegramId)
+                .into(),
+        }
+    }
+}
+
+impl Related<super::PayoutBatch> for Entity {
+    fn to() -> RelationDef {
+        Relation::Batch.def()
+    }
+}
+
+impl Related<super::User> for Entity {
+    fn to() -> RelationDef {
+        Relation::User.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,52 @@
+use chrono::{DateTime, Utc};
+use sea_orm::DeriveEntityModel;
+use sea_orm::entity::prelude::*;
+
+// status = pending, signed, sent, confirmed, failed
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
+#[sea_orm(table_name = "payout_batches")]
+pub struct Model {
+    #[sea_orm(primary_key, column_type = "BigInteger")]
+    pub id: i64,
+    #[sea_orm(column_type = "Text")]
+    pub status: String,
+    #[sea_orm(column_type = "Text")]
+    pub currency: String,
+    // base64 BOC of the external message produced by the wallet signer
+    #[sea_orm(column_type = "Text")]
+    pub signed_boc: Option<String>,
+    #[sea_orm(column_type = "Text")]
+    pub message_hash: Option<String>,
+    // the signed message can't be accepted by the network after this moment
+    #[sea_orm(column_type = "TimestampWithTimeZone")]
+    pub valid_until: Option<DateTime<Utc>>,
+    #[sea_orm(column_type = "Text")]
+    pub error: Option<String>,
+    #[sea_orm(column_type = "TimestampWithTimeZone", default_value = "now()")]
+    pub created_at: DateTime<Utc>,
+    #[sea_orm(column_type = "TimestampWithTimeZone", default_value = "now()")]
+    pub updated_at: DateTime<Utc>,
+    #[sea_orm(column_type = "TimestampWithTimeZone")]
+    pub confirmed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter)]
+pub enum Relation {
+    Payout,
+}
+
+impl RelationTrait for Relation {
+    fn def(&self) -> RelationDef {
+        match self {
+            Self::Payout => Entity::has_many(super::payout::Entity).into(),
+        }
+    }
+}
+
+impl Related<super::Payout> for Entity {
+    fn to() -> RelationDef {
+        Relation::Payout.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
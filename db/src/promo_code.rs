@@ -0,0 +1,427 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use sea_orm::ActiveValue::Set;
+use sea_orm::entity::prelude::*;
+use sea_orm::sea_query::Expr;
+use sea_orm::{Condition, DeriveEntityModel, QueryOrder};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+// discount_type = percent, fixed
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
+#[sea_orm(table_name = "promo_codes")]
+pub struct Model {
+    #[sea_orm(primary_key, column_type = "BigInteger")]
+    pub id: i64,
+    #[sea_orm(column_type = "Text")]
+    pub code: String,
+    // None = global code, valid for every channel
+    #[sea_orm(column_type = "BigInteger")]
+    pub channel_id: Option<i64>,
+    #[sea_orm(column_type = "Text")]
+    pub discount_type: String,
+    #[sea_orm(column_type = "Decimal(None)")]
+    pub discount_value: Decimal,
+    #[sea_orm(column_type = "Integer")]
+    pub max_uses: Option<i32>,
+    #[sea_orm(column_type = "Integer", default_value = "0")]
+    pub used_count: i32,
+    #[sea_orm(column_type = "TimestampWithTimeZone")]
+    pub valid_from: Option<DateTime<Utc>>,
+    #[sea_orm(column_type = "TimestampWithTimeZone")]
+    pub valid_until: Option<DateTime<Utc>>,
+    #[sea_orm(column_type = "Boolean", default_value = "true")]
+    pub is_active: bool,
+    #[sea_orm(column_type = "TimestampWithTimeZone", default_value = "now()")]
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter)]
+pub enum Relation {
+    Channel,
+}
+
+impl RelationTrait for Relation {
+    fn def(&self) -> RelationDef {
+        match self {
+            Self::Channel => Entity::belongs_to(super::channel::Entity)
+                .from(Column::ChannelId)
+                .to(super::channel::Column::ChannelId)
+                .into(),
+        }
+    }
+}
+
+impl Related<super::Channel> for Entity {
+    fn to() -> RelationDef {
+        Relation::Channel.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+/// Codes are stored upper-cased, so user input is normalized the same way before lookup.
+pub fn normalize(code: &str) -> String {
+    code.trim().to_uppercase()
+}
+
+impl Model {
+    /// Whether the code can be redeemed at `now`. Usage limit is only a hint here,
+    /// the authoritative check happens atomically in [`redeem`].
+    pub fn is_redeemable_at(&self, now: DateTime<Utc>) -> bool {
+        self.is_active
+            && self.valid_from.is_none_or(|from| from <= now)
+            && self.valid_until.is_none_or(|until| now < until)
+            && self.max_uses.is_none_or(|max| self.used_count < max)
+    }
+
+    /// Price after discount, rounded to cents. `None` if the discount type is unknown
+    /// or the discount would make the price non-positive.
+    pub fn apply(&self, price: Decimal) -> Option<Decimal> {
+        discounted(&self.discount_type, self.discount_value, price)
+    }
+}
+
+fn discounted(discount_type: &str, discount_value: Decimal, price: Decimal) -> Option<Decimal> {
+    let discounted = match discount_type {
+        "percent" => price - price * discount_value / Decimal::ONE_HUNDRED,
+        "fixed" => price - discount_value,
+        _ => return None,
+    }
+    .round_dp(2);
+    (discounted > Decimal::ZERO).then_some(discounted)
+}
+
+/// How owners write a new code: `CODE percent|fixed VALUE [uses=N] [from=YYYY-MM-DD]
+/// [until=YYYY-MM-DD]`. Dates are UTC midnights, `until` is the first day the code
+/// doesn't work anymore.
+pub const DRAFT_FORMAT: &str = "CODE percent|fixed VALUE [uses=N] [from=YYYY-MM-DD] [until=YYYY-MM-DD]";
+
+/// A new promo code as an owner described it, validated and ready to be saved.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Draft {
+    pub code: String,
+    pub discount_type: String,
+    pub discount_value: Decimal,
+    pub max_uses: Option<i32>,
+    pub valid_from: Option<DateTime<Utc>>,
+    pub valid_until: Option<DateTime<Utc>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DraftError {
+    /// The input doesn't follow [`DRAFT_FORMAT`].
+    Format,
+    Code,
+    DiscountType,
+    DiscountValue,
+    Uses,
+    Date(String),
+    /// `until` isn't after `from`, or is already past.
+    Window,
+}
+
+impl Display for DraftError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DraftError::Format => write!(f, "Please use the format: {}", DRAFT_FORMAT),
+            DraftError::Code => write!(f, "A code is 3-32 latin letters, digits, dashes or underscores."),
+            DraftError::DiscountType => write!(f, "The discount is either percent or fixed."),
+            DraftError::DiscountValue => write!(
+                f,
+                "The discount is a positive number with at most 2 decimals, a percent one is below 100."
+            ),
+            DraftError::Uses => write!(f, "The usage limit is a positive whole number."),
+            DraftError::Date(date) => write!(f, "{} is not a date, please use YYYY-MM-DD.", date),
+            DraftError::Window => write!(f, "The code has to end after it starts and in the future."),
+        }
+    }
+}
+
+fn date(value: &str) -> Result<DateTime<Utc>, DraftError> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map(|date| date.and_time(Default::default()).and_utc())
+        .map_err(|_| DraftError::Date(value.to_string()))
+}
+
+impl Draft {
+    pub fn parse(input: &str, now: DateTime<Utc>) -> Result<Self, DraftError> {
+        let mut words = input.split_whitespace();
+        let (Some(code), Some(discount_type), Some(discount_value)) = (words.next(), words.next(), words.next())
+        else {
+            return Err(DraftError::Format);
+        };
+        let code = normalize(code);
+        if !(3..=32).contains(&code.len())
+            || !code.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '-' || c == '_')
+        {
+            return Err(DraftError::Code);
+        }
+        let discount_type = discount_type.to_lowercase();
+        if discount_type != "percent" && discount_type != "fixed" {
+            return Err(DraftError::DiscountType);
+        }
+        let discount_value = Decimal::from_str(discount_value)
+            .ok()
+            .filter(|value| *value > Decimal::ZERO && value.normalize().scale() <= 2)
+            .filter(|value| discount_type != "percent" || *value < Decimal::ONE_HUNDRED)
+            .ok_or(DraftError::DiscountValue)?;
+
+        let mut draft = Draft {
+            code,
+            discount_type,
+            discount_value,
+            max_uses: None,
+            valid_from: None,
+            valid_until: None,
+        };
+        for option in words {
+            match option.split_once('=') {
+                Some(("uses", uses)) if draft.max_uses.is_none() => {
+                    draft.max_uses = Some(uses.parse().ok().filter(|uses| *uses > 0).ok_or(DraftError::Uses)?);
+                }
+                Some(("from", from)) if draft.valid_from.is_none() => draft.valid_from = Some(date(from)?),
+                Some(("until", until)) if draft.valid_until.is_none() => draft.valid_until = Some(date(until)?),
+                _ => return Err(DraftError::Format),
+            }
+        }
+        if let Some(until) = draft.valid_until
+            && (until <= now || draft.valid_from.is_some_and(|from| until <= from))
+        {
+            return Err(DraftError::Window);
+        }
+        Ok(draft)
+    }
+
+    /// Same as [`Model::apply`], to check the code against a channel's price before saving it.
+    pub fn apply(&self, price: Decimal) -> Option<Decimal> {
+        discounted(&self.discount_type, self.discount_value, price)
+    }
+
+    /// The row to insert: for `channel_id`, or global when `None`.
+    pub fn into_active_model(self, channel_id: Option<i64>) -> ActiveModel {
+        ActiveModel {
+            code: Set(self.code),
+            channel_id: Set(channel_id),
+            discount_type: Set(self.discount_type),
+            discount_value: Set(self.discount_value),
+            max_uses: Set(self.max_uses),
+            valid_from: Set(self.valid_from),
+            valid_until: Set(self.valid_until),
+            ..Default::default()
+        }
+    }
+}
+
+/// Finds a code usable for `channel_id`: a channel-specific code wins over a global one.
+pub async fn find_for_channel<C: ConnectionTrait>(
+    db: &C,
+    code: &str,
+    channel_id: i64,
+) -> Result<Option<Model>, DbErr> {
+    Entity::find()
+        .filter(Column::Code.eq(normalize(code)))
+        .filter(
+            Condition::any()
+                .add(Column::ChannelId.eq(channel_id))
+                .add(Column::ChannelId.is_null()),
+        )
+        .order_by_asc(Expr::col(Column::ChannelId).is_null())
+        .one(db)
+        .await
+}
+
+/// Atomically takes one usage of the code. Returns `false` if the code was exhausted,
+/// deactivated or is outside its validity window at `now`, so two concurrent redemptions
+/// of the last usage can't both succeed. Call it inside the transaction that creates the
+/// payment; the usage is given back with [`release`] if the payment expires or is
+/// cancelled unpaid.
+pub async fn redeem<C: ConnectionTrait>(db: &C, id: i64, now: DateTime<Utc>) -> Result<bool, DbErr> {
+    let result = Entity::update_many()
+        .col_expr(Column::UsedCount, Expr::col(Column::UsedCount).add(1))
+        .filter(Column::Id.eq(id))
+        .filter(Column::IsActive.eq(true))
+        // Окно действия проверяется тем же запросом, что и остаток использований
+        .filter(Condition::any().add(Column::ValidFrom.is_null()).add(Column::ValidFrom.lte(now)))
+        .filter(Condition::any().add(Column::ValidUntil.is_null()).add(Column::ValidUntil.gt(now)))
+        .filter(
+            Condition::any()
+                .add(Column::MaxUses.is_null())
+                .add(Expr::col(Column::UsedCount).lt(Expr::col(Column::MaxUses))),
+        )
+        .exec(db)
+        .await?;
+    Ok(result.rows_affected == 1)
+}
+
+/// Gives back the usage [`redeem`] took for a payment that expired or was cancelled
+/// unpaid. Call it inside the transaction that changes the payment's status, guarded so
+/// that the status changes only once.
+pub async fn release<C: ConnectionTrait>(db: &C, id: i64) -> Result<(), DbErr> {
+    Entity::update_many()
+        .col_expr(Column::UsedCount, Expr::col(Column::UsedCount).sub(1))
+        .filter(Column::Id.eq(id))
+        .filter(Column::UsedCount.gt(0))
+        .exec(db)
+        .await?;
+    Ok(())
+}
+
+/// Stops the code from being redeemed again. Payments it was already applied to keep it.
+pub async fn deactivate<C: ConnectionTrait>(db: &C, id: i64) -> Result<(), DbErr> {
+    Entity::update_many()
+        .col_expr(Column::IsActive, Expr::value(false))
+        .filter(Column::Id.eq(id))
+        .exec(db)
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(seconds: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(1760000000 + seconds, 0).unwrap()
+    }
+
+    fn code(discount_type: &str, discount_value: Decimal) -> Model {
+        Model {
+            id: 1,
+            code: "SPRING".to_string(),
+            channel_id: None,
+            discount_type: discount_type.to_string(),
+            discount_value,
+            max_uses: None,
+            used_count: 0,
+            valid_from: None,
+            valid_until: None,
+            is_active: true,
+            created_at: at(0),
+        }
+    }
+
+    fn usd(cents: i64) -> Decimal {
+        Decimal::new(cents, 2)
+    }
+
+    #[test]
+    fn percent_discount() {
+        let promo = code("percent", Decimal::new(20, 0));
+        assert_eq!(promo.apply(usd(1000)), Some(usd(800)));
+        // 9.99 - 15% = 8.4915, до центов
+        let promo = code("percent", Decimal::new(15, 0));
+        assert_eq!(promo.apply(usd(999)), Some(usd(849)));
+        let promo = code("percent", Decimal::new(125, 1));
+        assert_eq!(promo.apply(usd(999)), Some(usd(874)));
+        // ровно полцента округляется к чётному
+        let promo = code("percent", Decimal::new(50, 0));
+        assert_eq!(promo.apply(usd(1005)), Some(usd(502)));
+        assert_eq!(promo.apply(usd(1015)), Some(usd(508)));
+    }
+
+    #[test]
+    fn fixed_discount() {
+        let promo = code("fixed", usd(250));
+        assert_eq!(promo.apply(usd(1000)), Some(usd(750)));
+        let promo = code("fixed", Decimal::new(3333, 3));
+        assert_eq!(promo.apply(usd(1000)), Some(usd(667)));
+    }
+
+    #[test]
+    fn price_stays_positive() {
+        assert_eq!(code("percent", Decimal::ONE_HUNDRED).apply(usd(1000)), None);
+        assert_eq!(code("percent", Decimal::new(150, 0)).apply(usd(1000)), None);
+        assert_eq!(code("fixed", usd(1000)).apply(usd(1000)), None);
+        assert_eq!(code("fixed", usd(999)).apply(usd(1000)), Some(usd(1)));
+        // скидка, округлённая до нуля, тоже не проходит
+        assert_eq!(code("fixed", Decimal::new(99996, 4)).apply(usd(1000)), None);
+    }
+
+    #[test]
+    fn unknown_discount_type() {
+        assert_eq!(code("bogo", Decimal::ONE).apply(usd(1000)), None);
+    }
+
+    #[test]
+    fn redeemable_within_window() {
+        let promo = Model {
+            valid_from: Some(at(100)),
+            valid_until: Some(at(200)),
+            ..code("fixed", usd(100))
+        };
+        assert!(!promo.is_redeemable_at(at(99)));
+        assert!(promo.is_redeemable_at(at(100)));
+        assert!(promo.is_redeemable_at(at(199)));
+        assert!(!promo.is_redeemable_at(at(200)));
+        assert!(code("fixed", usd(100)).is_redeemable_at(at(0)));
+    }
+
+    #[test]
+    fn not_redeemable_when_used_up_or_inactive() {
+        let limited = Model {
+            max_uses: Some(3),
+            used_count: 2,
+            ..code("fixed", usd(100))
+        };
+        assert!(limited.is_redeemable_at(at(0)));
+        assert!(!Model { used_count: 3, ..limited.clone() }.is_redeemable_at(at(0)));
+        assert!(!Model { max_uses: Some(0), used_count: 0, ..limited }.is_redeemable_at(at(0)));
+        let inactive = Model {
+            is_active: false,
+            ..code("fixed", usd(100))
+        };
+        assert!(!inactive.is_redeemable_at(at(0)));
+    }
+
+    fn draft(input: &str) -> Result<Draft, DraftError> {
+        Draft::parse(input, at(0))
+    }
+
+    #[test]
+    fn parses_draft() {
+        assert_eq!(
+            draft(" spring-25  PERCENT 12.5 uses=100 from=2025-10-01 until=2025-11-01"),
+            Ok(Draft {
+                code: "SPRING-25".to_string(),
+                discount_type: "percent".to_string(),
+                discount_value: Decimal::new(125, 1),
+                max_uses: Some(100),
+                valid_from: Some(DateTime::from_timestamp(1759276800, 0).unwrap()),
+                valid_until: Some(DateTime::from_timestamp(1761955200, 0).unwrap()),
+            })
+        );
+        let fixed = draft("VIP fixed 2").unwrap();
+        assert_eq!(fixed.discount_value, usd(200));
+        assert_eq!((fixed.max_uses, fixed.valid_from, fixed.valid_until), (None, None, None));
+    }
+
+    #[test]
+    fn rejects_bad_drafts() {
+        assert_eq!(draft("SPRING percent"), Err(DraftError::Format));
+        assert_eq!(draft("SPRING percent 10 uses=1 uses=2"), Err(DraftError::Format));
+        assert_eq!(draft("SPRING percent 10 limit=5"), Err(DraftError::Format));
+        assert_eq!(draft("AB percent 10"), Err(DraftError::Code));
+        assert_eq!(draft("SPRING!! percent 10"), Err(DraftError::Code));
+        assert_eq!(draft("SPRING half 10"), Err(DraftError::DiscountType));
+        assert_eq!(draft("SPRING percent 0"), Err(DraftError::DiscountValue));
+        assert_eq!(draft("SPRING percent 100"), Err(DraftError::DiscountValue));
+        assert_eq!(draft("SPRING fixed -1"), Err(DraftError::DiscountValue));
+        assert_eq!(draft("SPRING fixed 1.005"), Err(DraftError::DiscountValue));
+        assert_eq!(draft("SPRING fixed lots"), Err(DraftError::DiscountValue));
+        assert_eq!(draft("SPRING fixed 1 uses=0"), Err(DraftError::Uses));
+        assert_eq!(draft("SPRING fixed 1 uses=many"), Err(DraftError::Uses));
+        assert_eq!(draft("SPRING fixed 1 until=01.11.2025"), Err(DraftError::Date("01.11.2025".to_string())));
+    }
+
+    #[test]
+    fn rejects_empty_or_past_window() {
+        // at(0) — 2025-10-09
+        assert_eq!(draft("SPRING fixed 1 until=2025-10-01"), Err(DraftError::Window));
+        assert_eq!(
+            draft("SPRING fixed 1 from=2025-12-01 until=2025-12-01"),
+            Err(DraftError::Window)
+        );
+        assert!(draft("SPRING fixed 1 from=2025-12-01 until=2025-12-02").is_ok());
+        assert!(draft("SPRING fixed 1 from=2025-01-01").is_ok());
+    }
+}
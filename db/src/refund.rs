@@ -0,0 +1,86 @@
+use chrono::{DateTime, Utc};
+use sea_orm::DeriveEntityModel;
+use sea_orm::entity::prelude::*;
+
+// reason = overpaid, underpaid, duplicate, expired
+// status = requested, approved, rejected, signed, sent, confirmed, failed
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
+#[sea_orm(table_name = "refunds")]
+pub struct Model {
+    #[sea_orm(primary_key, column_type = "BigInteger")]
+    pub id: i64,
+    #[sea_orm(column_type = "BigInteger")]
+    pub transaction_id: i64,
+    // payer, unknown for merchant invoices
+    #[sea_orm(column_type = "BigInteger")]
+    pub telegram_id: Option<i64>,
+    #[sea_orm(column_type = "BigInteger")]
+    pub channel_id: Option<i64>,
+    #[sea_orm(column_type = "Text")]
+    pub reason: String,
+    #[sea_orm(column_type = "Decimal(None)")]
+    pub amount: Decimal,
+    #[sea_orm(column_type = "Text")]
+    pub currency: String,
+    // the address the money came from
+    #[sea_orm(column_type = "Text")]
+    pub destination_address: String,
+    #[sea_orm(column_type = "Text", unique)]
+    pub source_tx_hash: String,
+    #[sea_orm(column_type = "Text")]
+    pub status: String,
+    #[sea_orm(column_type = "BigInteger")]
+    pub decided_by: Option<i64>,
+    #[sea_orm(column_type = "TimestampWithTimeZone")]
+    pub decided_at: Option<DateTime<Utc>>,
+    #[sea_orm(column_type = "Text")]
+    pub signed_boc: Option<String>,
+    #[sea_orm(column_type = "Text")]
+    pub message_hash: Option<String>,
+    // the signed message can't be accepted by the network after this moment
+    #[sea_orm(column_type = "TimestampWithTimeZone")]
+    pub valid_until: Option<DateTime<Utc>>,
+    #[sea_orm(column_type = "Text")]
+    pub error: Option<String>,
+    #[sea_orm(column_type = "TimestampWithTimeZone", default_value = "now()")]
+    pub created_at: DateTime<Utc>,
+    #[sea_orm(column_type = "TimestampWithTimeZone", default_value = "now()")]
+    pub updated_at: DateTime<Utc>,
+    #[sea_orm(column_type = "TimestampWithTimeZone")]
+    pub confirmed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter)]
+pub enum Relation {
+    Transaction,
+    Channel,
+}
+
+impl RelationTrait for Relation {
+    fn def(&self) -> RelationDef {
+        match self {
+            Self::Transaction => Entity::belongs_to(super::transaction::Entity)
+                .from(Column::TransactionId)
+                .to(super::transaction::Column::Id)
+                .into(),
+            Self::Channel => Entity::belongs_to(super::channel::Entity)
+                .from(Column::ChannelId)
+                .to(super::channel::Column::ChannelId)
+                .into(),
+        }
+    }
+}
+
+impl Related<super::Transaction> for Entity {
+    fn to() -> RelationDef {
+        Relation::Transaction.def()
+    }
+}
+
+impl Related<super::Channel> for Entity {
+    fn to() -> RelationDef {
+        Relation::Channel.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
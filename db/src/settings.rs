@@ -1,6 +1,7 @@
 use sea_orm::entity::prelude::*;
 use sea_orm::DeriveEntityModel;
 use serde_json::Value;
+use std::str::FromStr;
 
 #[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
 #[sea_orm(table_name = "settings")]
@@ -17,3 +18,158 @@ pub enum Relation {
 }
 
 impl ActiveModelBehavior for ActiveModel {}
+
+pub const MAIN: &str = "main";
+
+/// Loads the `settings` json stored under `key`, `Value::Null` if there is no such row.
+pub async fn load<C: ConnectionTrait>(db: &C, key: &str) -> Result<Value, DbErr> {
+    Ok(Entity::find_by_id(key)
+        .one(db)
+        .await?
+        .map(|model| model.settings)
+        .unwrap_or(Value::Null))
+}
+
+/// Reads a decimal stored either as a json string ("2.5") or a json number (2.5).
+pub fn decimal(value: &Value) -> Option<Decimal> {
+    match value {
+        Value::String(s) => Decimal::from_str(s).ok(),
+        Value::Number(n) => Decimal::from_str(&n.to_string()).ok(),
+        _ => None,
+    }
+}
+
+/// Krypton admins, configured in the "main" settings: `{"admin_telegram_ids": [1, 2]}`.
+pub async fn is_admin<C: ConnectionTrait>(db: &C, telegram_id: i64) -> Result<bool, DbErr> {
+    let settings = load(db, MAIN).await?;
+    Ok(settings["admin_telegram_ids"]
+        .as_array()
+        .is_some_and(|ids| ids.iter().any(|id| id.as_i64() == Some(telegram_id))))
+}
+
+/// Platform fee taken from every payment, configured in the "main" settings:
+/// `{"platform_fee": {"percent": "2.5", "minimum": "0.1"}}`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PlatformFee {
+    pub percent: Decimal,
+    pub minimum: Decimal,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FeeSplit {
+    pub fee: Decimal,
+    pub owner_amount: Decimal,
+}
+
+impl PlatformFee {
+    /// No `platform_fee` at all means no fee. A `platform_fee` without a valid percent
+    /// (0 to 100) and minimum (0 or more) is an error rather than a silent zero fee.
+    pub fn from_settings(settings: &Value) -> Result<Self, DbErr> {
+        let fee = &settings["platform_fee"];
+        if fee.is_null() {
+            return Ok(Self::default());
+        }
+        let invalid = |field: &str| {
+            DbErr::Custom(format!("settings.platform_fee.{} is missing or malformed: {}", field, fee))
+        };
+        let percent = decimal(&fee["percent"])
+            .filter(|percent| (Decimal::ZERO..=Decimal::ONE_HUNDRED).contains(percent))
+            .ok_or_else(|| invalid("percent"))?;
+        let minimum = decimal(&fee["minimum"])
+            .filter(|minimum| *minimum >= Decimal::ZERO)
+            .ok_or_else(|| invalid("minimum"))?;
+        Ok(Self { percent, minimum })
+    }
+
+    pub async fn load<C: ConnectionTrait>(db: &C) -> Result<Self, DbErr> {
+        Self::from_settings(&load(db, MAIN).await?)
+    }
+
+    /// `max(price * percent / 100, minimum)`, rounded to cents and never more than the price itself.
+    pub fn split(&self, price: Decimal) -> FeeSplit {
+        let fee = (price * self.percent / Decimal::ONE_HUNDRED)
+            .max(self.minimum)
+            .round_dp(2)
+            .clamp(Decimal::ZERO, price);
+        FeeSplit {
+            fee,
+            owner_amount: price - fee,
+        }
+    }
+}
+
+/// Owner payouts, configured in the "main" settings:
+/// `{"payouts": {"min_amount": "10", "batch_size": 4}}`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PayoutSettings {
+    /// Owners are paid only once Krypton owes them at least this much.
+    pub min_amount: Decimal,
+    /// How many transfers go into one signed wallet message.
+    pub batch_size: usize,
+}
+
+impl Default for PayoutSettings {
+    fn default() -> Self {
+        Self {
+            min_amount: Decimal::TEN,
+            batch_size: 4,
+        }
+    }
+}
+
+impl PayoutSettings {
+    pub fn from_settings(settings: &Value) -> Self {
+        let payouts = &settings["payouts"];
+        let default = Self::default();
+        Self {
+            min_amount: decimal(&payouts["min_amount"]).unwrap_or(default.min_amount),
+            batch_size: payouts["batch_size"]
+                .as_u64()
+                .and_then(|size| usize::try_from(size).ok())
+                .filter(|size| *size > 0)
+                .unwrap_or(default.batch_size),
+        }
+    }
+
+    pub async fn load<C: ConnectionTrait>(db: &C) -> Result<Self, DbErr> {
+        Ok(Self::from_settings(&load(db, MAIN).await?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn platform_fee_is_read_from_strings_and_numbers() {
+        let settings = json!({"platform_fee": {"percent": "2.5", "minimum": 0.1}});
+        assert_eq!(
+            PlatformFee::from_settings(&settings).unwrap(),
+            PlatformFee {
+                percent: Decimal::new(25, 1),
+                minimum: Decimal::new(1, 1),
+            }
+        );
+    }
+
+    #[test]
+    fn no_platform_fee_means_no_fee() {
+        assert_eq!(PlatformFee::from_settings(&json!({})).unwrap(), PlatformFee::default());
+        assert_eq!(PlatformFee::from_settings(&Value::Null).unwrap(), PlatformFee::default());
+    }
+
+    #[test]
+    fn malformed_platform_fee_is_an_error() {
+        for fee in [
+            json!({"percent": "2,5", "minimum": "0.1"}),
+            json!({"percent": "2.5"}),
+            json!({"percent": "150", "minimum": "0"}),
+            json!({"percent": "2.5", "minimum": "-1"}),
+            json!("2.5"),
+        ] {
+            let settings = json!({"platform_fee": fee});
+            assert!(PlatformFee::from_settings(&settings).is_err(), "{}", settings);
+        }
+    }
+}
@@ -1,6 +1,8 @@
 use chrono::{DateTime, Utc};
 use sea_orm::DeriveEntityModel;
 use sea_orm::entity::prelude::*;
+
+// status = active, expired
 #[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
 #[sea_orm(table_name = "subscriptions")]
 pub struct Model {
@@ -12,14 +14,14 @@ pub struct Model {
     pub transaction_id: i64,
     #[sea_orm(column_type = "Text")]
     pub status: String,
-    #[sea_orm(column_type = "Timestamp")]
+    #[sea_orm(column_type = "TimestampWithTimeZone")]
     pub time_from: DateTime<Utc>,
-    #[sea_orm(column_type = "Timestamp")]
+    #[sea_orm(column_type = "TimestampWithTimeZone")]
     pub time_to: DateTime<Utc>,
-    #[sea_orm(column_type = "Timestamp", default_value = "now()")]
+    #[sea_orm(column_type = "TimestampWithTimeZone", default_value = "now()")]
     pub created_at: DateTime<Utc>,
     #[sea_orm(
-        column_type = "Timestamp",
+        column_type = "TimestampWithTimeZone",
         default_value = "now()",
         on_update = "now()"
     )]
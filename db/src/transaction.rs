@@ -3,39 +3,71 @@ use sea_orm::DeriveEntityModel;
 use sea_orm::entity::prelude::*;
 use serde_json::Value;
 
-// status = active, failed, completed
+// status = active, failed, completed, expired, cancelled (superseded by a newer transaction)
 #[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
 #[sea_orm(table_name = "payment_transactions")]
 pub struct Model {
     #[sea_orm(primary_key, column_type = "BigInteger")]
     pub id: i64,
+    // payer, unknown for invoices created through the merchant API
     #[sea_orm(column_type = "BigInteger")]
-    pub telegram_id: i64,
+    pub telegram_id: Option<i64>,
+    // channel the payment is for, none for merchant invoices that aren't tied to one
     #[sea_orm(column_type = "BigInteger")]
-    pub channel_id: i64,
+    pub channel_id: Option<i64>,
+    // bot chat the payment was requested from
     #[sea_orm(column_type = "BigInteger")]
-    pub chat_id: i64,
+    pub chat_id: Option<i64>,
     #[sea_orm(column_type = "Decimal(None)")]
     pub price: Decimal,
     #[sea_orm(column_type = "Text")]
     pub currency: String,
     #[sea_orm(column_type = "Text")]
     pub status: String,
-    #[sea_orm(column_type = "Timestamp", default_value = "now()")]
+    #[sea_orm(column_type = "TimestampWithTimeZone", default_value = "now()")]
     pub created_at: DateTime<Utc>,
-    #[sea_orm(column_type = "Timestamp")]
+    #[sea_orm(column_type = "TimestampWithTimeZone")]
     pub completed_at: Option<DateTime<Utc>>,
     #[sea_orm(column_type = "Json", default_value = "{}")]
     pub transaction_data: Value,
     #[sea_orm(column_type = "Text")]
     pub wallet_address: String,
     #[sea_orm(column_type = "BigInteger")]
-    pub message_id: i64
+    pub message_id: Option<i64>,
+    #[sea_orm(column_type = "BigInteger")]
+    pub promo_code_id: Option<i64>,
+    // price before the promo code discount, `price` holds the amount to pay
+    #[sea_orm(column_type = "Decimal(None)")]
+    pub original_price: Option<Decimal>,
+    // Krypton's part of `price`, the rest (`owner_amount`) is owed to the channel owner
+    #[sea_orm(column_type = "Decimal(None)", default_value = "0")]
+    pub platform_fee: Decimal,
+    #[sea_orm(column_type = "Decimal(None)", default_value = "0")]
+    pub owner_amount: Decimal,
+    // an active transaction isn't paid anymore after this moment
+    #[sea_orm(column_type = "TimestampWithTimeZone")]
+    pub expires_at: Option<DateTime<Utc>>,
+    // sum of confirmed refunds made against this transaction
+    #[sea_orm(column_type = "Decimal(None)", default_value = "0")]
+    pub refunded_amount: Decimal,
+    // unguessable id used in payment links and the API
+    #[sea_orm(unique)]
+    pub public_id: Uuid,
+    // what the payer is paying for, shown on the payment page
+    #[sea_orm(column_type = "Text")]
+    pub description: Option<String>,
+    // merchant API key the invoice was created with
+    #[sea_orm(column_type = "BigInteger")]
+    pub api_key_id: Option<i64>,
+    // generated with the transaction and attached to every log line about it
+    pub correlation_id: Uuid,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter)]
 pub enum Relation {
-    Channel
+    Channel,
+    PromoCode,
+    ApiKey,
 }
 
 impl RelationTrait for Relation {
@@ -45,6 +77,14 @@ impl RelationTrait for Relation {
                 .from(Column::ChannelId)
                 .to(super::channel::Column::ChannelId)
                 .into(),
+            Self::PromoCode => Entity::belongs_to(super::promo_code::Entity)
+                .from(Column::PromoCodeId)
+                .to(super::promo_code::Column::Id)
+                .into(),
+            Self::ApiKey => Entity::belongs_to(super::api_key::Entity)
+                .from(Column::ApiKeyId)
+                .to(super::api_key::Column::Id)
+                .into(),
         }
     }
 }
@@ -55,4 +95,16 @@ impl Related<super::Channel> for Entity {
     }
 }
 
+impl Related<super::PromoCode> for Entity {
+    fn to() -> RelationDef {
+        Relation::PromoCode.def()
+    }
+}
+
+impl Related<super::ApiKey> for Entity {
+    fn to() -> RelationDef {
+        Relation::ApiKey.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
@@ -17,10 +17,10 @@ pub struct Model {
     #[sea_orm(column_type = "Text", nullable)]
     pub last_name: Option<String>,
     
-    #[sea_orm(column_type = "Timestamp", default_value = "now()")]
+    #[sea_orm(column_type = "TimestampWithTimeZone", default_value = "now()")]
     pub created_at: DateTime<Utc>,
     
-    #[sea_orm(column_type = "Timestamp", default_value = "now()")]
+    #[sea_orm(column_type = "TimestampWithTimeZone", default_value = "now()")]
     pub last_active_at: DateTime<Utc>,
 }
 
@@ -0,0 +1,58 @@
+use chrono::{DateTime, Utc};
+use sea_orm::DeriveEntityModel;
+use sea_orm::entity::prelude::*;
+use serde_json::Value;
+
+// status = pending, delivered, failed
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
+#[sea_orm(table_name = "webhook_deliveries")]
+pub struct Model {
+    #[sea_orm(primary_key, column_type = "BigInteger")]
+    pub id: i64,
+    #[sea_orm(column_type = "BigInteger")]
+    pub endpoint_id: i64,
+    // sent as X-Krypton-Event-Id, the same on every retry and replay
+    pub event_id: Uuid,
+    #[sea_orm(column_type = "Text")]
+    pub event_type: String,
+    #[sea_orm(column_type = "Json")]
+    pub payload: Value,
+    #[sea_orm(column_type = "Text", default_value = "pending")]
+    pub status: String,
+    #[sea_orm(default_value = "0")]
+    pub attempts: i32,
+    #[sea_orm(column_type = "TimestampWithTimeZone", default_value = "now()")]
+    pub next_attempt_at: DateTime<Utc>,
+    // HTTP status of the last attempt, none if it didn't get a response
+    pub response_status: Option<i32>,
+    #[sea_orm(column_type = "Text")]
+    pub error: Option<String>,
+    #[sea_orm(column_type = "TimestampWithTimeZone", default_value = "now()")]
+    pub created_at: DateTime<Utc>,
+    #[sea_orm(column_type = "TimestampWithTimeZone")]
+    pub delivered_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter)]
+pub enum Relation {
+    Endpoint,
+}
+
+impl RelationTrait for Relation {
+    fn def(&self) -> RelationDef {
+        match self {
+            Self::Endpoint => Entity::belongs_to(super::webhook_endpoint::Entity)
+                .from(Column::EndpointId)
+                .to(super::webhook_endpoint::Column::Id)
+                .into(),
+        }
+    }
+}
+
+impl Related<super::webhook_endpoint::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Endpoint.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
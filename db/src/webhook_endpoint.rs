@@ -0,0 +1,49 @@
+use chrono::{DateTime, Utc};
+use sea_orm::DeriveEntityModel;
+use sea_orm::entity::prelude::*;
+use serde_json::Value;
+
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel)]
+#[sea_orm(table_name = "webhook_endpoints")]
+pub struct Model {
+    #[sea_orm(primary_key, column_type = "BigInteger")]
+    pub id: i64,
+    #[sea_orm(column_type = "BigInteger")]
+    pub channel_id: i64,
+    #[sea_orm(column_type = "Text")]
+    pub url: String,
+    // HMAC key of the X-Krypton-Signature header, shown to the owner once
+    #[sea_orm(column_type = "Text")]
+    pub secret: String,
+    // event types to deliver, an empty array means all of them
+    #[sea_orm(column_type = "Json", default_value = "[]")]
+    pub events: Value,
+    #[sea_orm(column_type = "Boolean", default_value = "true")]
+    pub is_active: bool,
+    #[sea_orm(column_type = "TimestampWithTimeZone", default_value = "now()")]
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter)]
+pub enum Relation {
+    Channel,
+}
+
+impl RelationTrait for Relation {
+    fn def(&self) -> RelationDef {
+        match self {
+            Self::Channel => Entity::belongs_to(super::channel::Entity)
+                .from(Column::ChannelId)
+                .to(super::channel::Column::ChannelId)
+                .into(),
+        }
+    }
+}
+
+impl Related<super::Channel> for Entity {
+    fn to() -> RelationDef {
+        Relation::Channel.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
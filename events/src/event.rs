@@ -1,37 +1,124 @@
-use redis::AsyncCommands;
 use redis::aio::MultiplexedConnection;
+use redis::{AsyncCommands, ExistenceCheck, SetExpiry, SetOptions};
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use serde_json;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct PaymentEvent {
     pub transaction_id: i64,
-    pub telegram_id: i64,
-    pub channel_id: i64,
-    pub chat_id: i64,
+    pub telegram_id: Option<i64>,
+    pub channel_id: Option<i64>,
+    pub chat_id: Option<i64>,
     pub price: Decimal,
-    pub wallet_address: String
+    pub wallet_address: String,
+    /// `correlation_id` of the transaction, for the logs of whoever handles the event.
+    /// Events queued before it existed have none.
+    #[serde(default)]
+    pub correlation_id: Option<String>,
 }
 
+/// Fresh events, oldest first.
+const QUEUE: &str = "pending_payments";
+/// Events waiting for their next check, scored by when it's due (unix milliseconds).
+const RECHECKS: &str = "pending_payments:recheck";
+/// While a transaction is queued or waiting for a recheck its marker exists, so opening
+/// the payment page again doesn't queue it twice. Every recheck extends the marker; if
+/// ton-watcher stops following the transaction without finishing it, the marker runs out
+/// and the next page visit queues it again.
+const MARKER_TTL_SECONDS: u64 = 10 * 60;
+
+fn marker(transaction_id: i64) -> String {
+    format!("pending_payments:queued:{}", transaction_id)
+}
+
+/// Queues `event` for ton-watcher unless its transaction is already followed.
+/// Returns whether the event was queued.
 pub async fn send_payment_event(
     event: &PaymentEvent,
     con: &mut MultiplexedConnection,
+) -> redis::RedisResult<bool> {
+    let options = SetOptions::default()
+        .conditional_set(ExistenceCheck::NX)
+        .with_expiration(SetExpiry::EX(MARKER_TTL_SECONDS));
+    let marked: Option<String> = con.set_options(marker(event.transaction_id), 1, options).await?;
+    if marked.is_none() {
+        return Ok(false);
+    }
+    let payload = serde_json::to_string(event).unwrap();
+    let _: i64 = con.lpush(QUEUE, payload).await?;
+    Ok(true)
+}
+
+/// Puts `event` aside to be checked again in `delay`, leaving the queue to other events.
+pub async fn schedule_recheck(
+    event: &PaymentEvent,
+    con: &mut MultiplexedConnection,
+    delay: Duration,
 ) -> redis::RedisResult<()> {
+    let due = now_ms() + delay.as_millis() as i64;
     let payload = serde_json::to_string(event).unwrap();
-    let _: i64 = con.lpush("pending_payments", payload).await?;
+    let _: i64 = con.zadd(RECHECKS, payload, due).await?;
+    let _: bool = con.expire(marker(event.transaction_id), MARKER_TTL_SECONDS as i64).await?;
     Ok(())
 }
 
+/// ton-watcher is done with the transaction: it's paid, expired or gone.
+pub async fn finish_payment_event(
+    event: &PaymentEvent,
+    con: &mut MultiplexedConnection,
+) -> redis::RedisResult<()> {
+    let _: i64 = con.del(marker(event.transaction_id)).await?;
+    Ok(())
+}
+
+/// Takes up to `limit` events whose recheck is due. An event is removed from the set by
+/// whoever takes it, so two watchers never check the same one. If Redis fails halfway, the
+/// events already taken are still returned: they are no longer in the set.
+pub async fn due_rechecks(
+    con: &mut MultiplexedConnection,
+    limit: isize,
+) -> redis::RedisResult<Vec<PaymentEvent>> {
+    let due: Vec<String> = con.zrangebyscore_limit(RECHECKS, "-inf", now_ms(), 0, limit).await?;
+    let mut events = Vec::with_capacity(due.len());
+    for json in due {
+        let taken: i64 = match con.zrem(RECHECKS, &json).await {
+            Ok(taken) => taken,
+            Err(err) if !events.is_empty() => {
+                tracing::error!("❌ Не удалось забрать повторную проверку: {}", err);
+                break;
+            }
+            Err(err) => return Err(err),
+        };
+        if taken == 0 {
+            continue;
+        }
+        match serde_json::from_str(&json) {
+            Ok(event) => events.push(event),
+            Err(err) => tracing::error!("Не удалось распарсить PaymentEvent: {err:?}, исходная строка: {json}"),
+        }
+    }
+    Ok(events)
+}
+
+fn now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_millis() as i64)
+}
+
+/// Waits up to `timeout` seconds for the next event, `0.0` waits forever.
 pub async fn pop_payment_event(
     con: &mut MultiplexedConnection,
+    timeout: f64,
 ) -> redis::RedisResult<Option<PaymentEvent>> {
-    let result: Option<(String, String)> = con.brpop("pending_payments", 0.0).await?;
+    let result: Option<(String, String)> = con.brpop(QUEUE, timeout).await?;
     if let Some((_queue, json)) = result {
         match serde_json::from_str(&json) {
             Ok(event) => Ok(Some(event)),
             Err(err) => {
-                eprintln!("Не удалось распарсить PaymentEvent: {err:?}, исходная строка: {json}");
+                tracing::error!("Не удалось распарсить PaymentEvent: {err:?}, исходная строка: {json}");
                 Ok(None)
             }
         }
@@ -40,6 +127,13 @@ pub async fn pop_payment_event(
     }
 }
 
+/// Events waiting for ton-watcher, fresh and scheduled for a recheck.
+pub async fn pending_payment_count(con: &mut MultiplexedConnection) -> redis::RedisResult<u64> {
+    let queued: u64 = con.llen(QUEUE).await?;
+    let rechecks: u64 = con.zcard(RECHECKS).await?;
+    Ok(queued + rechecks)
+}
+
 // Проверка на идемпотентность и установка флага "обработано"
 pub async fn process_payment_event(
     con: &mut MultiplexedConnection,
@@ -49,7 +143,7 @@ pub async fn process_payment_event(
     let is_new: bool = con.sadd(set_key, event.transaction_id).await?;
 
     if !is_new {
-        println!(
+        tracing::info!(
             "Событие с transaction_id={} уже обработано, пропускаем",
             event.transaction_id
         );
@@ -57,7 +151,7 @@ pub async fn process_payment_event(
     }
 
     // Здесь основная логика обработки платежа!
-    println!("Обрабатываем событие: {:?}", event);
+    tracing::info!("Обрабатываем событие: {:?}", event);
 
     // После успешной обработки можешь оставить transaction_id в сете (навсегда или с TTL)
     // Если хочешь, можешь поставить TTL:
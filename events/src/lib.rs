@@ -1 +1,3 @@
 pub mod event;
+pub mod wallet_link;
+pub mod payment_status;
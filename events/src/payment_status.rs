@@ -0,0 +1,81 @@
+use redis::AsyncCommands;
+use redis::aio::MultiplexedConnection;
+use redis::streams::{StreamMaxlen, StreamRangeReply, StreamReadOptions, StreamReadReply};
+use serde::{Deserialize, Serialize};
+
+/// How long the history of a transaction is kept after its last status change.
+pub const PAYMENT_STATUS_TTL_SECONDS: i64 = 24 * 60 * 60;
+/// History longer than this is trimmed, a transaction never gets near it.
+const PAYMENT_STATUS_MAX_LEN: usize = 100;
+const FIELD: &str = "event";
+
+/// A change of a payment transaction, published by ton-watcher as it happens, or by the
+/// bot when a new payment link cancels the old one.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PaymentStatusEvent {
+    /// Status of the transaction after the change: active, completed, expired or cancelled.
+    pub status: String,
+    /// What ton-watcher made of a transfer (exact, overpaid, underpaid, duplicate,
+    /// expired), `None` when the status changed without one.
+    pub verdict: Option<String>,
+    /// No more changes will follow.
+    pub terminal: bool,
+}
+
+fn key(transaction_id: i64) -> String {
+    format!("payment_status:{}", transaction_id)
+}
+
+/// Appends `event` to the transaction's history. Returns the stream id of the event.
+pub async fn publish_payment_status(
+    con: &mut MultiplexedConnection,
+    transaction_id: i64,
+    event: &PaymentStatusEvent,
+) -> redis::RedisResult<String> {
+    let payload = serde_json::to_string(event).unwrap();
+    let key = key(transaction_id);
+    let id: String = con
+        .xadd_maxlen(&key, StreamMaxlen::Approx(PAYMENT_STATUS_MAX_LEN), "*", &[(FIELD, payload)])
+        .await?;
+    let _: bool = con.expire(&key, PAYMENT_STATUS_TTL_SECONDS).await?;
+    Ok(id)
+}
+
+/// Id of the latest event of the transaction, `0-0` if there were none.
+pub async fn last_payment_status_id(
+    con: &mut MultiplexedConnection,
+    transaction_id: i64,
+) -> redis::RedisResult<String> {
+    let reply: StreamRangeReply = con.xrevrange_count(key(transaction_id), "+", "-", 1).await?;
+    Ok(reply
+        .ids
+        .into_iter()
+        .next()
+        .map(|entry| entry.id)
+        .unwrap_or_else(|| "0-0".to_string()))
+}
+
+/// Events published after `after`, waiting up to `block_ms` for the first one.
+/// Blocks the connection, so give it one that nothing else uses.
+pub async fn read_payment_status(
+    con: &mut MultiplexedConnection,
+    transaction_id: i64,
+    after: &str,
+    block_ms: usize,
+) -> redis::RedisResult<Vec<(String, PaymentStatusEvent)>> {
+    let options = StreamReadOptions::default().block(block_ms);
+    let reply: Option<StreamReadReply> = con
+        .xread_options(&[key(transaction_id)], &[after], &options)
+        .await?;
+    let mut events = Vec::new();
+    for entry in reply.into_iter().flat_map(|reply| reply.keys).flat_map(|key| key.ids) {
+        let Some(payload) = entry.get::<String>(FIELD) else {
+            continue;
+        };
+        match serde_json::from_str(&payload) {
+            Ok(event) => events.push((entry.id, event)),
+            Err(err) => tracing::error!("Не удалось распарсить PaymentStatusEvent: {err:?}, исходная строка: {payload}"),
+        }
+    }
+    Ok(events)
+}
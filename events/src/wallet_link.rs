@@ -0,0 +1,31 @@
+use redis::AsyncCommands;
+use redis::aio::MultiplexedConnection;
+
+/// A link token is good for one TON Proof login within this many seconds.
+pub const WALLET_LINK_TTL_SECONDS: u64 = 10 * 60;
+
+fn key(token: &str) -> String {
+    format!("wallet_link:{}", token)
+}
+
+// Бот выдаёт одноразовый токен, API обменивает его на telegram_id после TON Proof
+pub async fn create_wallet_link(
+    con: &mut MultiplexedConnection,
+    telegram_id: i64,
+) -> redis::RedisResult<String> {
+    let token: String = rand::random::<[u8; 16]>()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    let _: () = con
+        .set_ex(key(&token), telegram_id, WALLET_LINK_TTL_SECONDS)
+        .await?;
+    Ok(token)
+}
+
+pub async fn take_wallet_link(
+    con: &mut MultiplexedConnection,
+    token: &str,
+) -> redis::RedisResult<Option<i64>> {
+    con.get_del(key(token)).await
+}
@@ -0,0 +1,118 @@
+use db::ledger_account;
+use sea_orm::{
+    ColumnTrait, ConnectionTrait, DbBackend, DbErr, EntityTrait, QueryFilter, Statement,
+};
+
+/// Accounts of the Krypton books. `Gateway` is money physically held on the gate address,
+/// everything else says whom that money belongs to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AccountKind {
+    /// Funds received on the Krypton gate address (asset, debit-normal).
+    Gateway,
+    /// Krypton's earned fees (credit-normal).
+    PlatformFees,
+    /// What Krypton owes to a channel owner (credit-normal).
+    OwnerPayable,
+    /// Money received from a payer that wasn't applied to a payment, e.g. an overpayment (credit-normal).
+    /// Payers of merchant invoices aren't known to the bot and share one account per currency.
+    Payer,
+    /// Approved refunds waiting to be sent back (credit-normal).
+    Refunds,
+}
+
+impl AccountKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AccountKind::Gateway => "gateway",
+            AccountKind::PlatformFees => "platform_fees",
+            AccountKind::OwnerPayable => "owner_payable",
+            AccountKind::Payer => "payer",
+            AccountKind::Refunds => "refunds",
+        }
+    }
+
+    /// +1 for debit-normal accounts, -1 for credit-normal ones: multiplying the raw sum
+    /// of entries by it gives a positive balance in the usual case.
+    pub fn normal_sign(&self) -> i64 {
+        match self {
+            AccountKind::Gateway => 1,
+            _ => -1,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct AccountKey {
+    pub kind: AccountKind,
+    pub telegram_id: Option<i64>,
+    pub currency: String,
+}
+
+impl AccountKey {
+    pub fn gateway(currency: &str) -> Self {
+        Self::new(AccountKind::Gateway, None, currency)
+    }
+
+    pub fn platform_fees(currency: &str) -> Self {
+        Self::new(AccountKind::PlatformFees, None, currency)
+    }
+
+    pub fn owner_payable(owner_telegram_id: i64, currency: &str) -> Self {
+        Self::new(AccountKind::OwnerPayable, Some(owner_telegram_id), currency)
+    }
+
+    pub fn payer(telegram_id: Option<i64>, currency: &str) -> Self {
+        Self::new(AccountKind::Payer, telegram_id, currency)
+    }
+
+    pub fn refunds(currency: &str) -> Self {
+        Self::new(AccountKind::Refunds, None, currency)
+    }
+
+    fn new(kind: AccountKind, telegram_id: Option<i64>, currency: &str) -> Self {
+        Self {
+            kind,
+            telegram_id,
+            currency: currency.to_string(),
+        }
+    }
+}
+
+/// Accounts are created lazily on the first posting. `ON CONFLICT DO NOTHING` keeps two
+/// concurrent postings from failing on the unique index.
+pub(crate) async fn find_or_create<C: ConnectionTrait>(
+    db: &C,
+    key: &AccountKey,
+) -> Result<ledger_account::Model, DbErr> {
+    if let Some(account) = find(db, key).await? {
+        return Ok(account);
+    }
+    db.execute(Statement::from_sql_and_values(
+        DbBackend::Postgres,
+        "INSERT INTO ledger_accounts (kind, telegram_id, currency) VALUES ($1, $2, $3) \
+         ON CONFLICT DO NOTHING",
+        [
+            key.kind.as_str().into(),
+            key.telegram_id.into(),
+            key.currency.clone().into(),
+        ],
+    ))
+    .await?;
+    find(db, key)
+        .await?
+        .ok_or_else(|| DbErr::RecordNotFound(format!("ledger account {:?}", key)))
+}
+
+async fn find<C: ConnectionTrait>(
+    db: &C,
+    key: &AccountKey,
+) -> Result<Option<ledger_account::Model>, DbErr> {
+    let query = db::LedgerAccount::find()
+        .filter(ledger_account::Column::Kind.eq(key.kind.as_str()))
+        .filter(ledger_account::Column::Currency.eq(key.currency.as_str()));
+    let query = match key.telegram_id {
+        Some(telegram_id) => query.filter(ledger_account::Column::TelegramId.eq(telegram_id)),
+        None => query.filter(ledger_account::Column::TelegramId.is_null()),
+    };
+    query.one(db).await
+}
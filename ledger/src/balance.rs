@@ -0,0 +1,168 @@
+use crate::account::{AccountKey, AccountKind};
+use sea_orm::prelude::Decimal;
+use sea_orm::{ConnectionTrait, DbBackend, DbErr, FromQueryResult, Statement, Value};
+
+/// Balance of an account in its normal direction: what Krypton holds for `Gateway`,
+/// what it owes (or earned) for every other kind.
+#[derive(Clone, Debug, PartialEq, Eq, FromQueryResult)]
+pub struct Balance {
+    pub telegram_id: Option<i64>,
+    pub currency: String,
+    pub amount: Decimal,
+}
+
+impl Balance {
+    fn normalized(mut self, kind: AccountKind) -> Self {
+        self.amount *= Decimal::from(kind.normal_sign());
+        self
+    }
+}
+
+const BALANCES_SQL: &str = "SELECT a.telegram_id, a.currency, COALESCE(SUM(e.amount), 0) AS amount \
+     FROM ledger_accounts a \
+     LEFT JOIN ledger_entries e ON e.account_id = a.id \
+     WHERE a.kind = $1";
+
+async fn query<C: ConnectionTrait>(
+    db: &C,
+    kind: AccountKind,
+    filter: &str,
+    mut values: Vec<Value>,
+) -> Result<Vec<Balance>, DbErr> {
+    values.insert(0, kind.as_str().into());
+    let sql = format!(
+        "{} {} GROUP BY a.telegram_id, a.currency ORDER BY a.currency, a.telegram_id",
+        BALANCES_SQL, filter
+    );
+    let balances =
+        Balance::find_by_statement(Statement::from_sql_and_values(DbBackend::Postgres, sql, values))
+            .all(db)
+            .await?;
+    Ok(balances.into_iter().map(|b| b.normalized(kind)).collect())
+}
+
+/// Balance of a single account, zero if nothing was ever posted to it.
+pub async fn balance<C: ConnectionTrait>(db: &C, key: &AccountKey) -> Result<Decimal, DbErr> {
+    let (filter, mut values) = match key.telegram_id {
+        Some(telegram_id) => ("AND a.telegram_id = $2 AND a.currency = $3", vec![telegram_id.into()]),
+        None => ("AND a.telegram_id IS NULL AND a.currency = $2", vec![]),
+    };
+    values.push(key.currency.clone().into());
+    Ok(query(db, key.kind, filter, values)
+        .await?
+        .first()
+        .map(|b| b.amount)
+        .unwrap_or_default())
+}
+
+/// How much Krypton owes the owner, one row per currency.
+pub async fn owner_balances<C: ConnectionTrait>(
+    db: &C,
+    owner_telegram_id: i64,
+) -> Result<Vec<Balance>, DbErr> {
+    query(
+        db,
+        AccountKind::OwnerPayable,
+        "AND a.telegram_id = $2",
+        vec![owner_telegram_id.into()],
+    )
+    .await
+}
+
+/// Balances of every account of `kind` in `currency`, e.g. all owners Krypton owes USDT to.
+pub async fn balances_by_currency<C: ConnectionTrait>(
+    db: &C,
+    kind: AccountKind,
+    currency: &str,
+) -> Result<Vec<Balance>, DbErr> {
+    query(db, kind, "AND a.currency = $2", vec![currency.into()]).await
+}
+
+/// Totals of `kind` per currency, summed over all holders (e.g. platform fees earned).
+pub async fn totals<C: ConnectionTrait>(
+    db: &C,
+    kind: AccountKind,
+) -> Result<Vec<Balance>, DbErr> {
+    let mut totals: Vec<Balance> = Vec::new();
+    for balance in query(db, kind, "", vec![]).await? {
+        match totals.last_mut() {
+            Some(last) if last.currency == balance.currency => last.amount += balance.amount,
+            _ => totals.push(Balance {
+                telegram_id: None,
+                ..balance
+            }),
+        }
+    }
+    Ok(totals)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::posting::{self, JournalKind, post_payout};
+    use migration::testing::TestDatabase;
+    use sea_orm::{DatabaseConnection, TransactionTrait};
+
+    /// A payment of `amount` to `owner`, fee-free to keep the numbers plain.
+    async fn paid(db: &DatabaseConnection, reference_id: i64, owner: i64, currency: &str, amount: i64) {
+        let txn = db.begin().await.unwrap();
+        posting::post(
+            &txn,
+            JournalKind::PaymentConfirmed,
+            reference_id,
+            String::new(),
+            &[
+                (AccountKey::gateway(currency), Decimal::from(amount)),
+                (AccountKey::owner_payable(owner, currency), Decimal::from(-amount)),
+            ],
+        )
+        .await
+        .unwrap();
+        txn.commit().await.unwrap();
+    }
+
+    fn owed(telegram_id: Option<i64>, currency: &str, amount: i64) -> Balance {
+        Balance {
+            telegram_id,
+            currency: currency.to_string(),
+            amount: Decimal::from(amount),
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Postgres, run with TEST_DATABASE_URL=... cargo test -- --ignored"]
+    async fn balances_per_owner_and_currency() {
+        let test_db = TestDatabase::migrated().await;
+        let db = &test_db.db;
+        paid(db, 1, 1, "USDT", 20).await;
+        paid(db, 2, 1, "USDT", 5).await;
+        paid(db, 3, 1, "TON", 3).await;
+        paid(db, 4, 2, "USDT", 7).await;
+        let txn = db.begin().await.unwrap();
+        post_payout(&txn, 1, 1, "USDT", Decimal::from(10)).await.unwrap();
+        txn.commit().await.unwrap();
+
+        assert_eq!(
+            owner_balances(db, 1).await.unwrap(),
+            vec![owed(Some(1), "TON", 3), owed(Some(1), "USDT", 15)]
+        );
+        assert_eq!(owner_balances(db, 2).await.unwrap(), vec![owed(Some(2), "USDT", 7)]);
+        assert!(owner_balances(db, 3).await.unwrap().is_empty());
+
+        assert_eq!(
+            balances_by_currency(db, AccountKind::OwnerPayable, "USDT").await.unwrap(),
+            vec![owed(Some(1), "USDT", 15), owed(Some(2), "USDT", 7)]
+        );
+        assert_eq!(
+            totals(db, AccountKind::OwnerPayable).await.unwrap(),
+            vec![owed(None, "TON", 3), owed(None, "USDT", 22)]
+        );
+        // шлюз дебетовый: получено 32 USDT, выплачено 10
+        assert_eq!(
+            totals(db, AccountKind::Gateway).await.unwrap(),
+            vec![owed(None, "TON", 3), owed(None, "USDT", 22)]
+        );
+        assert_eq!(balance(db, &AccountKey::owner_payable(2, "TON")).await.unwrap(), Decimal::ZERO);
+        test_db.drop().await;
+    }
+}
@@ -0,0 +1,7 @@
+pub mod account;
+pub mod posting;
+pub mod balance;
+
+pub use account::{AccountKey, AccountKind};
+pub use balance::Balance;
+pub use posting::{JournalKind, LedgerError};
@@ -0,0 +1,421 @@
+use crate::account::{self, AccountKey};
+use db::{ledger_entry, ledger_journal, transaction};
+use sea_orm::prelude::Decimal;
+use sea_orm::{
+    ActiveModelTrait, ActiveValue::Set, ColumnTrait, ConnectionTrait, DbErr, EntityTrait,
+    QueryFilter,
+};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum LedgerError {
+    #[error("Database error: {0}")]
+    Database(#[from] DbErr),
+
+    #[error("Journal {0} is not balanced: entries sum up to {1}")]
+    Unbalanced(String, Decimal),
+
+    #[error("Journal {0} mixes currencies")]
+    CurrencyMismatch(String),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JournalKind {
+    /// reference_id = payment_transactions.id
+    PaymentConfirmed,
+    /// reference_id = refunds.id
+    PaymentUnapplied,
+    /// reference_id = refunds.id
+    RefundApproved,
+    /// reference_id = refunds.id
+    RefundSent,
+    /// reference_id = payouts.id
+    PayoutSent,
+}
+
+impl JournalKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JournalKind::PaymentConfirmed => "payment_confirmed",
+            JournalKind::PaymentUnapplied => "payment_unapplied",
+            JournalKind::RefundApproved => "refund_approved",
+            JournalKind::RefundSent => "refund_sent",
+            JournalKind::PayoutSent => "payout_sent",
+        }
+    }
+}
+
+/// Writes one balanced journal. Postings with a zero amount are skipped.
+///
+/// Posting is idempotent per `(kind, reference_id)`: if the journal already exists nothing is
+/// written and `None` is returned, so a redelivered event can't book the same money twice.
+/// Must be called inside a db transaction: the balance trigger is checked on commit.
+pub async fn post<C: ConnectionTrait>(
+    db: &C,
+    kind: JournalKind,
+    reference_id: i64,
+    description: String,
+    postings: &[(AccountKey, Decimal)],
+) -> Result<Option<ledger_journal::Model>, LedgerError> {
+    let name = format!("{}:{}", kind.as_str(), reference_id);
+    let total: Decimal = postings.iter().map(|(_, amount)| *amount).sum();
+    if !total.is_zero() {
+        return Err(LedgerError::Unbalanced(name, total));
+    }
+    let Some(currency) = postings.first().map(|(key, _)| key.currency.clone()) else {
+        return Ok(None);
+    };
+    if postings.iter().any(|(key, _)| key.currency != currency) {
+        return Err(LedgerError::CurrencyMismatch(name));
+    }
+
+    let exists = db::LedgerJournal::find()
+        .filter(ledger_journal::Column::Kind.eq(kind.as_str()))
+        .filter(ledger_journal::Column::ReferenceId.eq(reference_id))
+        .one(db)
+        .await?;
+    if exists.is_some() {
+        return Ok(None);
+    }
+
+    let journal = ledger_journal::ActiveModel {
+        kind: Set(kind.as_str().to_string()),
+        reference_id: Set(reference_id),
+        currency: Set(currency),
+        description: Set(description),
+        ..Default::default()
+    }
+    .insert(db)
+    .await?;
+
+    for (key, amount) in postings.iter().filter(|(_, amount)| !amount.is_zero()) {
+        let account = account::find_or_create(db, key).await?;
+        ledger_entry::ActiveModel {
+            journal_id: Set(journal.id),
+            account_id: Set(account.id),
+            amount: Set(*amount),
+            ..Default::default()
+        }
+        .insert(db)
+        .await?;
+    }
+    Ok(Some(journal))
+}
+
+fn payer(telegram_id: Option<i64>) -> String {
+    telegram_id.map_or_else(|| "anonymous payer".to_string(), |id| id.to_string())
+}
+
+/// Payment landed on the gate address: the price is split between the owner and Krypton
+/// according to the fee computed when the transaction was created. Anything received on top
+/// of the price is owed back to the payer.
+pub async fn post_payment_confirmation<C: ConnectionTrait>(
+    db: &C,
+    tx: &transaction::Model,
+    owner_telegram_id: i64,
+    received: Decimal,
+) -> Result<Option<ledger_journal::Model>, LedgerError> {
+    let currency = tx.currency.as_str();
+    post(
+        db,
+        JournalKind::PaymentConfirmed,
+        tx.id,
+        match tx.channel_id {
+            Some(channel_id) => format!("Payment for channel {} by {}", channel_id, payer(tx.telegram_id)),
+            None => format!("Invoice {} paid by {}", tx.public_id, payer(tx.telegram_id)),
+        },
+        &[
+            (AccountKey::gateway(currency), received),
+            (
+                AccountKey::owner_payable(owner_telegram_id, currency),
+                -tx.owner_amount,
+            ),
+            (AccountKey::platform_fees(currency), -tx.platform_fee),
+            (
+                AccountKey::payer(tx.telegram_id, currency),
+                tx.price - received,
+            ),
+        ],
+    )
+    .await
+}
+
+/// Money landed on the gate address but couldn't be applied to a transaction (underpaid,
+/// duplicate or late): all of it is owed back to the payer.
+pub async fn post_payment_unapplied<C: ConnectionTrait>(
+    db: &C,
+    refund_id: i64,
+    payer_telegram_id: Option<i64>,
+    currency: &str,
+    amount: Decimal,
+) -> Result<Option<ledger_journal::Model>, LedgerError> {
+    post(
+        db,
+        JournalKind::PaymentUnapplied,
+        refund_id,
+        format!("Unapplied payment from {}, refund {}", payer(payer_telegram_id), refund_id),
+        &[
+            (AccountKey::gateway(currency), amount),
+            (AccountKey::payer(payer_telegram_id, currency), -amount),
+        ],
+    )
+    .await
+}
+
+/// A refund was approved: money owed to the payer moves to the refunds queue.
+pub async fn post_refund_approved<C: ConnectionTrait>(
+    db: &C,
+    refund_id: i64,
+    payer_telegram_id: Option<i64>,
+    currency: &str,
+    amount: Decimal,
+) -> Result<Option<ledger_journal::Model>, LedgerError> {
+    post(
+        db,
+        JournalKind::RefundApproved,
+        refund_id,
+        format!("Refund {} approved for {}", refund_id, payer(payer_telegram_id)),
+        &[
+            (AccountKey::payer(payer_telegram_id, currency), amount),
+            (AccountKey::refunds(currency), -amount),
+        ],
+    )
+    .await
+}
+
+/// A refund left the gate address.
+pub async fn post_refund_sent<C: ConnectionTrait>(
+    db: &C,
+    refund_id: i64,
+    currency: &str,
+    amount: Decimal,
+) -> Result<Option<ledger_journal::Model>, LedgerError> {
+    post(
+        db,
+        JournalKind::RefundSent,
+        refund_id,
+        format!("Refund {} sent", refund_id),
+        &[
+            (AccountKey::refunds(currency), amount),
+            (AccountKey::gateway(currency), -amount),
+        ],
+    )
+    .await
+}
+
+/// A payout to a channel owner left the gate address.
+pub async fn post_payout<C: ConnectionTrait>(
+    db: &C,
+    payout_id: i64,
+    owner_telegram_id: i64,
+    currency: &str,
+    amount: Decimal,
+) -> Result<Option<ledger_journal::Model>, LedgerError> {
+    post(
+        db,
+        JournalKind::PayoutSent,
+        payout_id,
+        format!("Payout {} to {}", payout_id, owner_telegram_id),
+        &[
+            (AccountKey::owner_payable(owner_telegram_id, currency), amount),
+            (AccountKey::gateway(currency), -amount),
+        ],
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::balance;
+    use migration::testing::TestDatabase;
+    use sea_orm::prelude::{DateTimeUtc, Json, Uuid};
+    use sea_orm::{DatabaseConnection, TransactionTrait};
+
+    fn usdt(amount: i64) -> Decimal {
+        Decimal::new(amount, 2)
+    }
+
+    /// A 12.50 USDT payment for channel 7 of owner 1: 11.25 to the owner, 1.25 fee.
+    fn transaction() -> transaction::Model {
+        transaction::Model {
+            id: 42,
+            telegram_id: Some(279058397),
+            channel_id: Some(7),
+            chat_id: None,
+            price: usdt(1250),
+            currency: "USDT".to_string(),
+            status: "completed".to_string(),
+            created_at: DateTimeUtc::default(),
+            completed_at: None,
+            transaction_data: Json::Null,
+            wallet_address: String::new(),
+            message_id: None,
+            promo_code_id: None,
+            original_price: None,
+            platform_fee: usdt(125),
+            owner_amount: usdt(1125),
+            expires_at: None,
+            refunded_amount: Decimal::ZERO,
+            public_id: Uuid::nil(),
+            description: None,
+            api_key_id: None,
+            correlation_id: Uuid::nil(),
+        }
+    }
+
+    // Проверки до первого запроса: база не нужна
+    #[tokio::test]
+    async fn rejects_unbalanced_journal() {
+        let db = DatabaseConnection::Disconnected;
+        let result = post(
+            &db,
+            JournalKind::PayoutSent,
+            1,
+            String::new(),
+            &[(AccountKey::gateway("USDT"), usdt(100)), (AccountKey::platform_fees("USDT"), usdt(-99))],
+        )
+        .await;
+        assert!(matches!(
+            result,
+            Err(LedgerError::Unbalanced(name, total)) if name == "payout_sent:1" && total == usdt(1)
+        ));
+    }
+
+    #[tokio::test]
+    async fn rejects_mixed_currencies() {
+        let db = DatabaseConnection::Disconnected;
+        let result = post(
+            &db,
+            JournalKind::PayoutSent,
+            1,
+            String::new(),
+            &[(AccountKey::gateway("USDT"), usdt(100)), (AccountKey::platform_fees("TON"), usdt(-100))],
+        )
+        .await;
+        assert!(matches!(result, Err(LedgerError::CurrencyMismatch(_))));
+    }
+
+    #[tokio::test]
+    async fn nothing_to_post_writes_nothing() {
+        let db = DatabaseConnection::Disconnected;
+        let result = post(&db, JournalKind::PayoutSent, 1, String::new(), &[]).await;
+        assert!(matches!(result, Ok(None)));
+    }
+
+    /// Entries of `journal` by account, as the database stored them.
+    async fn entries<C: ConnectionTrait>(db: &C, journal: &ledger_journal::Model) -> Vec<Decimal> {
+        db::LedgerEntry::find()
+            .filter(ledger_entry::Column::JournalId.eq(journal.id))
+            .all(db)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.amount)
+            .collect()
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Postgres, run with TEST_DATABASE_URL=... cargo test -- --ignored"]
+    async fn posting_twice_books_once() {
+        let test_db = TestDatabase::migrated().await;
+        let db = &test_db.db;
+        for _ in 0..2 {
+            let txn = db.begin().await.unwrap();
+            post_payment_confirmation(&txn, &transaction(), 1, usdt(1250)).await.unwrap();
+            txn.commit().await.unwrap();
+        }
+        let journals = db::LedgerJournal::find().all(db).await.unwrap();
+        assert_eq!(journals.len(), 1);
+        assert_eq!(journals[0].kind, "payment_confirmed");
+        assert_eq!(journals[0].reference_id, 42);
+        assert_eq!(balance::balance(db, &AccountKey::owner_payable(1, "USDT")).await.unwrap(), usdt(1125));
+
+        // та же ссылка, но другой вид проводки — это другое событие
+        let txn = db.begin().await.unwrap();
+        let refund = post_refund_approved(&txn, 42, Some(279058397), "USDT", usdt(100)).await.unwrap();
+        txn.commit().await.unwrap();
+        assert!(refund.is_some());
+        test_db.drop().await;
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Postgres, run with TEST_DATABASE_URL=... cargo test -- --ignored"]
+    async fn payment_confirmation_balances() {
+        let test_db = TestDatabase::migrated().await;
+        let db = &test_db.db;
+        // 13.00 при цене 12.50: 0.50 сверху причитается плательщику
+        let txn = db.begin().await.unwrap();
+        let journal = post_payment_confirmation(&txn, &transaction(), 1, usdt(1300)).await.unwrap().unwrap();
+        let amounts = entries(&txn, &journal).await;
+        txn.commit().await.unwrap();
+        assert_eq!(amounts.len(), 4);
+        assert_eq!(amounts.iter().sum::<Decimal>(), Decimal::ZERO);
+
+        assert_eq!(balance::balance(db, &AccountKey::gateway("USDT")).await.unwrap(), usdt(1300));
+        assert_eq!(balance::balance(db, &AccountKey::owner_payable(1, "USDT")).await.unwrap(), usdt(1125));
+        assert_eq!(balance::balance(db, &AccountKey::platform_fees("USDT")).await.unwrap(), usdt(125));
+        assert_eq!(
+            balance::balance(db, &AccountKey::payer(Some(279058397), "USDT")).await.unwrap(),
+            usdt(50)
+        );
+
+        // ровно по цене строка плательщика нулевая и не пишется
+        let txn = db.begin().await.unwrap();
+        let exact = transaction::Model { id: 43, ..transaction() };
+        let journal = post_payment_confirmation(&txn, &exact, 1, usdt(1250)).await.unwrap().unwrap();
+        let amounts = entries(&txn, &journal).await;
+        txn.commit().await.unwrap();
+        assert_eq!(amounts.len(), 3);
+        assert_eq!(amounts.iter().sum::<Decimal>(), Decimal::ZERO);
+        test_db.drop().await;
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Postgres, run with TEST_DATABASE_URL=... cargo test -- --ignored"]
+    async fn unapplied_payment_balances() {
+        let test_db = TestDatabase::migrated().await;
+        let db = &test_db.db;
+        let txn = db.begin().await.unwrap();
+        let journal = post_payment_unapplied(&txn, 5, None, "TON", Decimal::new(3, 0)).await.unwrap().unwrap();
+        let amounts = entries(&txn, &journal).await;
+        txn.commit().await.unwrap();
+        assert_eq!(journal.currency, "TON");
+        assert_eq!(amounts.iter().sum::<Decimal>(), Decimal::ZERO);
+        assert_eq!(balance::balance(db, &AccountKey::gateway("TON")).await.unwrap(), Decimal::new(3, 0));
+        assert_eq!(balance::balance(db, &AccountKey::payer(None, "TON")).await.unwrap(), Decimal::new(3, 0));
+        test_db.drop().await;
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Postgres, run with TEST_DATABASE_URL=... cargo test -- --ignored"]
+    async fn database_refuses_unbalanced_journal() {
+        let test_db = TestDatabase::migrated().await;
+        let db = &test_db.db;
+        // мимо post: триггер в базе — последний рубеж
+        let txn = db.begin().await.unwrap();
+        let journal = ledger_journal::ActiveModel {
+            kind: Set("payout_sent".to_string()),
+            reference_id: Set(1),
+            currency: Set("USDT".to_string()),
+            description: Set(String::new()),
+            ..Default::default()
+        }
+        .insert(&txn)
+        .await
+        .unwrap();
+        let account = account::find_or_create(&txn, &AccountKey::gateway("USDT")).await.unwrap();
+        ledger_entry::ActiveModel {
+            journal_id: Set(journal.id),
+            account_id: Set(account.id),
+            amount: Set(usdt(100)),
+            ..Default::default()
+        }
+        .insert(&txn)
+        .await
+        .unwrap();
+        assert!(txn.commit().await.is_err());
+        assert!(db::LedgerJournal::find().all(db).await.unwrap().is_empty());
+        test_db.drop().await;
+    }
+}
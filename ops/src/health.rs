@@ -0,0 +1,179 @@
+//! `/healthz` answers while the process is alive; `/readyz` answers 200 only while every
+//! dependency of the service responds and no shutdown is under way, so orchestrators stop
+//! routing to an instance before it exits.
+//!
+//! The same router serves `/metrics`. The API mounts [`router`] next to its own routes,
+//! the workers run [`serve`] on their `health.port`.
+
+use std::{sync::Arc, time::Duration};
+
+use axum::{Json, Router, extract::State, http::StatusCode, routing::get};
+use redis::aio::MultiplexedConnection;
+use sea_orm::DatabaseConnection;
+use serde_json::{Map, Value, json};
+
+use crate::Shutdown;
+
+/// Longest wait for one dependency before it counts as down.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+const TONCENTER_PROBE_URL: &str = "https://toncenter.com/api/v2/getMasterchainInfo";
+
+/// A dependency the service can't work without.
+#[derive(Clone)]
+pub enum Check {
+    Database(DatabaseConnection),
+    Redis(MultiplexedConnection),
+    /// Toncenter, with the API key the service uses.
+    Indexer(String),
+}
+
+impl Check {
+    fn name(&self) -> &'static str {
+        match self {
+            Check::Database(_) => "database",
+            Check::Redis(_) => "redis",
+            Check::Indexer(_) => "indexer",
+        }
+    }
+
+    async fn probe(&self, http: &reqwest::Client) -> Result<(), String> {
+        match self {
+            Check::Database(db) => db.ping().await.map_err(|err| err.to_string()),
+            Check::Redis(redis) => redis::cmd("PING")
+                .query_async::<String>(&mut redis.clone())
+                .await
+                .map(|_| ())
+                .map_err(|err| err.to_string()),
+            Check::Indexer(api_key) => http
+                .get(TONCENTER_PROBE_URL)
+                .header("X-API-Key", api_key)
+                .send()
+                .await
+                .and_then(|response| response.error_for_status())
+                .map(|_| ())
+                .map_err(|err| err.to_string()),
+        }
+    }
+}
+
+/// The checks behind `/readyz` of one service.
+#[derive(Clone)]
+pub struct Health {
+    checks: Arc<Vec<Check>>,
+    shutdown: Shutdown,
+    http: reqwest::Client,
+}
+
+impl Health {
+    pub fn new(checks: Vec<Check>, shutdown: Shutdown) -> Self {
+        Health {
+            checks: Arc::new(checks),
+            shutdown,
+            http: reqwest::Client::builder()
+                .timeout(CHECK_TIMEOUT)
+                .build()
+                .expect("HTTP client can't be built"),
+        }
+    }
+
+    async fn readiness(&self) -> (StatusCode, Value) {
+        let mut checks = Map::new();
+        let mut ready = !self.shutdown.requested();
+        for check in self.checks.iter() {
+            let result = match tokio::time::timeout(CHECK_TIMEOUT, check.probe(&self.http)).await {
+                Ok(result) => result,
+                Err(_) => Err("timed out".to_string()),
+            };
+            ready &= result.is_ok();
+            checks.insert(
+                check.name().to_string(),
+                Value::String(result.err().unwrap_or_else(|| "ok".to_string())),
+            );
+        }
+        let status = match (ready, self.shutdown.requested()) {
+            (_, true) => "shutting_down",
+            (true, false) => "ready",
+            (false, false) => "unavailable",
+        };
+        let code = if ready {
+            StatusCode::OK
+        } else {
+            StatusCode::SERVICE_UNAVAILABLE
+        };
+        (code, json!({ "status": status, "checks": checks }))
+    }
+}
+
+async fn healthz() -> Json<Value> {
+    Json(json!({ "status": "ok" }))
+}
+
+async fn readyz(State(health): State<Health>) -> (StatusCode, Json<Value>) {
+    let (code, body) = health.readiness().await;
+    (code, Json(body))
+}
+
+async fn metrics() -> String {
+    crate::metrics::render()
+}
+
+/// `/healthz`, `/readyz` and `/metrics`, mountable into any router.
+pub fn router<S>(health: Health) -> Router<S> {
+    Router::new()
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .route("/metrics", get(metrics))
+        .with_state(health)
+}
+
+/// Serves [`router`] on `port` until shutdown.
+pub async fn serve(port: u16, health: Health) -> std::io::Result<()> {
+    let shutdown = health.shutdown.wait();
+    let listener = tokio::net::TcpListener::bind(("0.0.0.0", port)).await?;
+    axum::serve(listener, router::<()>(health))
+        .with_graceful_shutdown(shutdown)
+        .await
+}
+
+/// Runs [`serve`] in the background when the worker has a `health.port`.
+pub fn spawn(port: Option<u16>, health: Health) {
+    let Some(port) = port else {
+        return;
+    };
+    tokio::spawn(async move {
+        if let Err(err) = serve(port, health).await {
+            tracing::error!(
+                "❌ Не удалось запустить проверки состояния на порту {}: {}",
+                port,
+                err
+            );
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn readiness_drops_on_shutdown() {
+        let (trigger, shutdown) = Shutdown::manual();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(
+            axum::serve(listener, router::<()>(Health::new(Vec::new(), shutdown))).into_future(),
+        );
+
+        let client = reqwest::Client::new();
+        let ready = client.get(format!("{}/readyz", url)).send().await.unwrap();
+        assert_eq!(ready.status(), reqwest::StatusCode::OK);
+
+        trigger.send(true).unwrap();
+        let draining = client.get(format!("{}/readyz", url)).send().await.unwrap();
+        assert_eq!(draining.status(), reqwest::StatusCode::SERVICE_UNAVAILABLE);
+        let body: Value = draining.json().await.unwrap();
+        assert_eq!(body["status"], "shutting_down");
+        let alive = client.get(format!("{}/healthz", url)).send().await.unwrap();
+        assert_eq!(alive.status(), reqwest::StatusCode::OK);
+    }
+}
@@ -0,0 +1,11 @@
+//! What every Krypton service needs to run under an orchestrator: stopping cleanly on
+//! SIGTERM ([`Shutdown`]), answering liveness and readiness probes ([`health`]),
+//! exposing Prometheus [`metrics`] and [`logging`] in one format.
+
+pub mod health;
+pub mod logging;
+pub mod metrics;
+mod shutdown;
+
+pub use health::{Check, Health};
+pub use shutdown::Shutdown;
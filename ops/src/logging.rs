@@ -0,0 +1,27 @@
+//! Log output of every service: `tracing` events printed as text or, with `log.format = "json"`,
+//! one JSON object per line. Fields of the spans an event happens in, like the
+//! `correlation_id` of a payment, are printed with it. `RUST_LOG` overrides the levels.
+//! Records of the `log` crate, teloxide and sqlx among them, go the same way.
+
+use tracing_subscriber::EnvFilter;
+
+/// Levels when `RUST_LOG` isn't set. sqlx logs every statement at info.
+const DEFAULT_FILTER: &str = "info,sqlx=warn";
+
+pub fn init(json: bool) {
+    let filter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_FILTER));
+    let output = tracing_subscriber::fmt().with_env_filter(filter);
+    let installed = if json {
+        output
+            .json()
+            .with_current_span(false)
+            .with_span_list(true)
+            .try_init()
+    } else {
+        output.try_init()
+    };
+    if let Err(err) = installed {
+        eprintln!("❌ Не удалось настроить логирование: {}", err);
+    }
+}
@@ -0,0 +1,60 @@
+use std::future::Future;
+
+use tokio::sync::watch;
+
+/// Set once SIGTERM or Ctrl+C arrives. Loops check [`Shutdown::requested`] between units of
+/// work, servers and sleeps wait on [`Shutdown::wait`], so whatever is in flight finishes.
+#[derive(Clone)]
+pub struct Shutdown {
+    requested: watch::Receiver<bool>,
+}
+
+impl Shutdown {
+    /// Starts listening for the signals. Needs a Tokio runtime.
+    pub fn listen() -> Self {
+        let (sender, requested) = watch::channel(false);
+        tokio::spawn(async move {
+            signal().await;
+            tracing::info!("🛑 Получен сигнал остановки, завершаем начатое");
+            let _ = sender.send(true);
+        });
+        Shutdown { requested }
+    }
+
+    /// A shutdown requested by sending `true` instead of a signal.
+    #[cfg(test)]
+    pub(crate) fn manual() -> (watch::Sender<bool>, Self) {
+        let (sender, requested) = watch::channel(false);
+        (sender, Shutdown { requested })
+    }
+
+    pub fn requested(&self) -> bool {
+        *self.requested.borrow()
+    }
+
+    /// Resolves once shutdown is requested. Independent of `self`, so it can be handed to
+    /// servers and streams that outlive the borrow.
+    pub fn wait(&self) -> impl Future<Output = ()> + Send + use<> {
+        let mut requested = self.requested.clone();
+        async move {
+            let _ = requested.wait_for(|requested| *requested).await;
+        }
+    }
+}
+
+#[cfg(unix)]
+async fn signal() {
+    use tokio::signal::unix::{SignalKind, signal};
+
+    let mut terminate =
+        signal(SignalKind::terminate()).expect("SIGTERM handler can't be installed");
+    tokio::select! {
+        _ = terminate.recv() => {}
+        _ = tokio::signal::ctrl_c() => {}
+    }
+}
+
+#[cfg(not(unix))]
+async fn signal() {
+    let _ = tokio::signal::ctrl_c().await;
+}
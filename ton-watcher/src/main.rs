@@ -1,87 +1,251 @@
-use std::env;
-use dotenv::dotenv;
+mod payment;
+mod toncenter;
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
 use redis::Client;
+use redis::aio::MultiplexedConnection;
 use sea_orm::{ Database, DatabaseConnection };
-use base64::engine::general_purpose::STANDARD;
-use base64::Engine;
-use events::event::{pop_payment_event, PaymentEvent};
-use serde::Deserialize;
+use events::event::{
+    PaymentEvent, due_rechecks, finish_payment_event, pending_payment_count, pop_payment_event, schedule_recheck,
+};
+use events::payment_status::{PaymentStatusEvent, publish_payment_status};
+use payment::Verdict;
+use toncenter::IncomingPayment;
+use tracing::Instrument;
+const PENDING_RECHECK_DELAY: Duration = Duration::from_secs(5);
+/// How long the loop waits for a fresh event before looking at due rechecks again.
+const QUEUE_POLL_TIMEOUT: Duration = Duration::from_secs(1);
+/// Rechecks handled per turn of the loop, so fresh events aren't starved.
+const RECHECK_BATCH: isize = 20;
+/// Late and duplicate transfers arrive with no event behind them, so the gate address is
+/// also scanned on its own every so often.
+const GATE_SCAN_INTERVAL: Duration = Duration::from_secs(60);
+/// Pause after a Redis failure, so a Redis outage doesn't turn the loop into a busy one.
+const REDIS_BACKOFF: Duration = Duration::from_secs(5);
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    dotenv().ok();
-    let dragonfly_password = env::var("DRAGONFLY_PASSWORD").expect("DRAGONFLY_PASSWORD must be set");
-    let connection_string = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
+    let (database, redis, ton, health, log) = config::load_or_exit("ton-watcher", |r| {
+        (
+            config::DatabaseConfig::read(r),
+            config::RedisConfig::read(r),
+            {
+                let ton = config::TonConfig::read(r);
+                if ton.usdt_jetton_wallet.is_empty() {
+                    r.invalid("ton.usdt_jetton_wallet", "missing, USDT payments can't be recognized without it");
+                }
+                ton
+            },
+            config::HealthConfig::read(r),
+            config::LogConfig::read(r),
+        )
+    });
+    ops::logging::init(log.json);
+    let shutdown = ops::Shutdown::listen();
+    ops::metrics::install();
 
-    let db: DatabaseConnection = Database::connect(connection_string).await?;
-    let client = Client::open(format!("redis://:{}@127.0.0.1:6379", dragonfly_password))?;
+    let db: DatabaseConnection = Database::connect(database.url).await?;
+    if database.migrate {
+        tracing::info!("Applying pending migrations");
+        migration::up_on_boot(&db).await?;
+    }
+    let client = Client::open(redis.url())?;
     let mut manager = client.get_multiplexed_tokio_connection().await?;
-    loop {
-        if let Some(event) = pop_payment_event(&mut manager).await? {
-            println!("\n💰 Получено событие: {:?}", event);
+    ops::health::spawn(
+        health.port,
+        ops::Health::new(
+            vec![
+                ops::Check::Database(db.clone()),
+                ops::Check::Redis(manager.clone()),
+                ops::Check::Indexer(ton.api_key.clone()),
+            ],
+            shutdown.clone(),
+        ),
+    );
+    // Когда какой адрес последний раз запрашивали у индексатора
+    let mut scanned: HashMap<String, Instant> = HashMap::new();
+    // События, которые уже забраны из Redis, но не вернулись туда из-за ошибки
+    let mut carried: Vec<PaymentEvent> = Vec::new();
+    // Остановка только между событиями: начатая обработка платежа доводится до конца
+    while !shutdown.requested() {
+        match pending_payment_count(&mut manager).await {
+            Ok(depth) => metrics::gauge!("watcher_pending_payments").set(depth as f64),
+            Err(err) => tracing::error!("❌ Не удалось узнать длину очереди платежей: {}", err),
+        }
+        let mut redis_failed = false;
+        let mut events = std::mem::take(&mut carried);
+        match due_rechecks(&mut manager, RECHECK_BATCH).await {
+            Ok(due) => events.extend(due),
+            Err(err) => {
+                tracing::error!("❌ Не удалось получить повторные проверки: {}", err);
+                redis_failed = true;
+            }
+        }
+        let fresh = match pop_payment_event(&mut manager, QUEUE_POLL_TIMEOUT.as_secs_f64()).await {
+            Ok(event) => event,
+            Err(err) => {
+                tracing::error!("❌ Не удалось получить событие из очереди: {}", err);
+                redis_failed = true;
+                None
+            }
+        };
 
-            let status = check_transaction_status(&event).await;
-            println!("🔍 Статус транзакции: {}", status);
+        // Свежее событие проверяем сразу, повторные проверки довольствуются недавним сканом адреса
+        let mut due: Vec<(&str, Duration)> = events
+            .iter()
+            .map(|event| (event.wallet_address.as_str(), PENDING_RECHECK_DELAY))
+            .collect();
+        due.extend(fresh.iter().map(|event| (event.wallet_address.as_str(), Duration::ZERO)));
+        due.push((ton.gate_address.as_str(), GATE_SCAN_INTERVAL));
+        due.sort_unstable();
+        due.dedup_by_key(|(address, _)| *address);
+        for (address, interval) in due {
+            if scanned.get(address).is_none_or(|at| at.elapsed() >= interval) {
+                scan_gate(&db, &mut manager, &ton, address).await;
+                scanned.insert(address.to_string(), Instant::now());
+            }
+        }
 
-            // update_transaction_status(&db, event.transaction_id, &status).await?;
-            // notify_user(event.telegram_id, &status).await;
+        for event in events.into_iter().chain(fresh) {
+            let span = tracing::info_span!(
+                "payment_event",
+                transaction_id = event.transaction_id,
+                correlation_id = event.correlation_id.as_deref(),
+            );
+            if let Err(err) = handle_event(&db, &mut manager, &event).instrument(span.clone()).await {
+                let _entered = span.enter();
+                tracing::error!("❌ Не удалось обработать событие: {}", err);
+                redis_failed = true;
+                // Событие уже убрано из очереди, без повторной проверки платёж никто не закроет
+                if let Err(err) = schedule_recheck(&event, &mut manager, PENDING_RECHECK_DELAY).await {
+                    tracing::error!("❌ Не удалось отложить событие: {}", err);
+                    carried.push(event);
+                }
+            }
+        }
+        if redis_failed {
+            tokio::time::sleep(REDIS_BACKOFF).await;
         }
     }
+    // Что не удалось вернуть в Redis за время работы, пробуем вернуть напоследок
+    for event in &carried {
+        if let Err(err) = schedule_recheck(event, &mut manager, PENDING_RECHECK_DELAY).await {
+            tracing::error!("❌ Событие транзакции {} потеряно: {}", event.transaction_id, err);
+        }
+    }
+    tracing::info!("👋 ton-watcher остановлен");
+    Ok(())
 }
 
-#[derive(Debug, Deserialize)]
-struct TonTransaction {
-    in_msg: InMsg,
-}
+/// Schedules another check of the payment the page is waiting on until the transaction is
+/// paid or expires. The gate is scanned for it by the loop, not here.
+async fn handle_event(
+    db: &DatabaseConnection,
+    redis: &mut MultiplexedConnection,
+    event: &PaymentEvent,
+) -> redis::RedisResult<()> {
+    tracing::info!("💰 Получено событие: {:?}", event);
 
-#[derive(Debug, Deserialize)]
-struct InMsg {
-    value: String,
-    source: String,
-    payload: Option<String>,
+    match payment::expire_if_overdue(db, event.transaction_id).await {
+        // Платёж ещё не пришёл — проверим позже, не задерживая очередь
+        Ok(Some(status)) if status == "active" => {
+            schedule_recheck(event, redis, PENDING_RECHECK_DELAY).await?;
+            return Ok(());
+        }
+        Ok(Some(status)) if status == "expired" => {
+            tracing::info!("⌛ Транзакция просрочена");
+            publish_status(redis, event.transaction_id, &status, None).await;
+        }
+        Ok(status) => tracing::info!("🔍 Статус транзакции: {:?}", status),
+        // Ошибка базы не повод бросать транзакцию
+        Err(err) => {
+            tracing::error!("❌ Не удалось проверить транзакцию: {}", err);
+            schedule_recheck(event, redis, PENDING_RECHECK_DELAY).await?;
+            return Ok(());
+        }
+    }
+    finish_payment_event(event, redis).await?;
+    // notify_user(event.telegram_id, &status).await;
+    Ok(())
 }
 
-#[derive(Debug, Deserialize)]
-struct TonApiResponse {
-    result: Vec<TonTransaction>,
+/// Tells the payment page what happened; the payment itself is already saved, so a failure
+/// here is only logged.
+async fn publish_status(
+    redis: &mut MultiplexedConnection,
+    transaction_id: i64,
+    status: &str,
+    verdict: Option<Verdict>,
+) {
+    let event = PaymentStatusEvent {
+        status: status.to_string(),
+        verdict: verdict.map(|verdict| verdict.as_str().to_string()),
+        terminal: status != "active",
+    };
+    if let Err(err) = publish_payment_status(redis, transaction_id, &event).await {
+        tracing::error!("❌ Не удалось опубликовать статус транзакции {}: {}", transaction_id, err);
+    }
 }
 
-async fn check_transaction_status(event: &PaymentEvent) -> String {
-    let address = event.wallet_address.clone();
-    let url = format!(
-        "https://toncenter.com/api/v2/getTransactions?address={}&limit=15&api_key={}",
-        address,
-        env::var("TON_API_KEY").unwrap_or_default()
-    );
-
-    let res = match reqwest::get(&url).await {
-        Ok(resp) => resp,
-        Err(_) => return "error".to_string(),
+/// Applies every recent Krypton transfer to the gate address.
+async fn scan_gate(
+    db: &DatabaseConnection,
+    redis: &mut MultiplexedConnection,
+    ton: &config::TonConfig,
+    address: &str,
+) {
+    let started = Instant::now();
+    let fetched = toncenter::fetch_incoming(&ton.api_key, address, &ton.usdt_jetton_wallet).await;
+    metrics::histogram!("watcher_poll_duration_seconds").record(started.elapsed());
+    let payments = match fetched {
+        Ok(payments) => payments,
+        Err(err) => {
+            metrics::counter!("watcher_indexer_errors_total").increment(1);
+            tracing::error!("❌ Не удалось получить транзакции {}: {}", address, err);
+            return;
+        }
     };
+    for incoming in payments {
+        // correlation_id становится известен, когда payment::process_incoming найдёт транзакцию
+        let span = tracing::info_span!(
+            "payment",
+            hash = %incoming.hash,
+            transaction_id = tracing::field::Empty,
+            correlation_id = tracing::field::Empty,
+        );
+        apply_incoming(db, redis, &incoming).instrument(span).await;
+    }
+}
 
-    let data: TonApiResponse = match res.json().await {
-        Ok(json) => json,
-        Err(_) => return "error".to_string(),
+async fn apply_incoming(db: &DatabaseConnection, redis: &mut MultiplexedConnection, incoming: &IncomingPayment) {
+    let transaction_id = match incoming.transaction_id {
+        Some(transaction_id) => transaction_id,
+        None => match payment::attribute(db, incoming).await {
+            Ok(Some(transaction_id)) => transaction_id,
+            Ok(None) => return,
+            Err(err) => {
+                tracing::error!("❌ Не удалось сопоставить платёж: {}", err);
+                return;
+            }
+        },
     };
-
-    for tx in data.result {
-        if let Some(payload_base64) = tx.in_msg.payload {
-            if let Some(decoded_tx_id) = decode_transaction_id_from_payload(&payload_base64) {
-                if decoded_tx_id == event.transaction_id {
-                    return "success".to_string();
-                }
+    tracing::Span::current().record("transaction_id", transaction_id);
+    match payment::process_incoming(db, incoming, transaction_id).await {
+        Ok(Some(Verdict::Applied)) | Ok(None) => {}
+        Ok(Some(verdict)) => {
+            if verdict == Verdict::Exact {
+                tracing::info!("✅ Транзакция подтверждена");
+            } else {
+                tracing::info!("↩️ Платёж {:?}, создан запрос на возврат", verdict);
+            }
+            // Опоздавший платёж заодно закрывает транзакцию, если её ещё не просрочили
+            match payment::expire_if_overdue(db, transaction_id).await {
+                Ok(Some(status)) => publish_status(redis, transaction_id, &status, Some(verdict)).await,
+                Ok(None) => {}
+                Err(err) => tracing::error!("❌ Не удалось проверить транзакцию: {}", err),
             }
         }
+        Err(err) => tracing::error!("❌ Не удалось обработать платёж: {}", err),
     }
-
-    "pending".to_string()
 }
-
-fn decode_transaction_id_from_payload(payload_base64: &str) -> Option<i64> {
-    let decoded = STANDARD.decode(payload_base64).ok()?;
-    let text = String::from_utf8(decoded).ok()?;
-    if let Some(tx_str) = text.strip_prefix("transaction_id=") {
-        tx_str.parse::<i64>().ok()
-    } else {
-        None
-    }
-}
\ No newline at end of file
@@ -0,0 +1,437 @@
+use crate::toncenter::{IncomingPayment, raw_address};
+use chrono::{DateTime, Duration, Utc};
+use db::{
+    Channel, FeeLedger, Refund, RefundModel, Transaction, UserWallet, channel::SUBSCRIPTION_PERIOD_DAYS,
+    fee_ledger, promo_code, refund, subscriptions, transaction, user_wallet,
+};
+use ledger::LedgerError;
+use sea_orm::prelude::Decimal;
+use sea_orm::sea_query::Expr;
+use sea_orm::{
+    ActiveModelTrait, ActiveValue::Set, ColumnTrait, ConnectionTrait, DatabaseConnection, DbErr,
+    EntityTrait, QueryFilter, QueryOrder, QuerySelect, TransactionTrait,
+};
+use webhooks::WebhookEvent;
+use serde_json::json;
+
+/// What a transfer to the gate address means for the transaction it names.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Verdict {
+    /// This very transfer already completed the transaction.
+    Applied,
+    Exact,
+    /// The transaction is paid, the excess goes back to the payer.
+    Overpaid(Decimal),
+    /// Not enough to pay: the transaction stays open and the whole amount goes back.
+    Underpaid,
+    /// The transaction was already paid by another transfer.
+    Duplicate,
+    /// The transfer arrived after the transaction stopped accepting payments.
+    Expired,
+}
+
+impl Verdict {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Verdict::Applied => "applied",
+            Verdict::Exact => "exact",
+            Verdict::Overpaid(_) => "overpaid",
+            Verdict::Underpaid => "underpaid",
+            Verdict::Duplicate => "duplicate",
+            Verdict::Expired => "expired",
+        }
+    }
+}
+
+impl Verdict {
+    /// Reason stored on the refund this verdict leads to.
+    fn refund_reason(&self) -> Option<&'static str> {
+        match self {
+            Verdict::Overpaid(_) => Some("overpaid"),
+            Verdict::Underpaid => Some("underpaid"),
+            Verdict::Duplicate => Some("duplicate"),
+            Verdict::Expired => Some("expired"),
+            Verdict::Applied | Verdict::Exact => None,
+        }
+    }
+}
+
+pub fn judge(tx: &transaction::Model, hash: &str, received: Decimal, paid_at: DateTime<Utc>) -> Verdict {
+    match tx.status.as_str() {
+        "completed" if tx.transaction_data["chain_tx_hash"].as_str() == Some(hash) => Verdict::Applied,
+        "completed" => Verdict::Duplicate,
+        "active" if tx.expires_at.is_none_or(|expires_at| paid_at <= expires_at) => {
+            if received == tx.price {
+                Verdict::Exact
+            } else if received > tx.price {
+                Verdict::Overpaid(received - tx.price)
+            } else {
+                Verdict::Underpaid
+            }
+        }
+        _ => Verdict::Expired,
+    }
+}
+
+/// Applies one incoming transfer: completes the transaction if it pays it and opens a refund
+/// request for whatever can't be kept. Every transfer is handled once — a refund is unique
+/// per on-chain hash and a completed transaction remembers the hash that paid it — so the
+/// same transfer seen by several scans is harmless. Returns `None` for unknown transactions.
+pub async fn process_incoming(
+    db: &DatabaseConnection,
+    payment: &IncomingPayment,
+    transaction_id: i64,
+) -> Result<Option<Verdict>, LedgerError> {
+    let txn = db.begin().await?;
+    let Some(tx) = Transaction::find_by_id(transaction_id)
+        .lock_exclusive()
+        .one(&txn)
+        .await?
+    else {
+        return Ok(None);
+    };
+    let refunded = Refund::find()
+        .filter(refund::Column::SourceTxHash.eq(&payment.hash))
+        .one(&txn)
+        .await?;
+    tracing::Span::current().record("correlation_id", tracing::field::display(tx.correlation_id));
+    let Some(received) = payment.amount(&tx.currency) else {
+        return Ok(None);
+    };
+    let verdict = judge(&tx, &payment.hash, received, payment.paid_at);
+    if refunded.is_some() || verdict == Verdict::Applied {
+        return Ok(Some(Verdict::Applied));
+    }
+
+    if let Verdict::Exact | Verdict::Overpaid(_) = verdict {
+        confirm_payment(&txn, &tx, payment, received).await?;
+    }
+    if let Some(reason) = verdict.refund_reason() {
+        let amount = match verdict {
+            Verdict::Overpaid(excess) => excess,
+            _ => received,
+        };
+        let now = Utc::now();
+        let refund = RefundModel {
+            transaction_id: Set(tx.id),
+            telegram_id: Set(tx.telegram_id),
+            channel_id: Set(tx.channel_id),
+            reason: Set(reason.to_string()),
+            amount: Set(amount),
+            currency: Set(tx.currency.clone()),
+            destination_address: Set(payment.source.clone()),
+            source_tx_hash: Set(payment.hash.clone()),
+            status: Set("requested".to_string()),
+            created_at: Set(now),
+            updated_at: Set(now),
+            ..Default::default()
+        }
+        .insert(&txn)
+        .await?;
+        // Переплата уже учтена при подтверждении, остальное целиком принадлежит плательщику
+        if !matches!(verdict, Verdict::Overpaid(_)) {
+            ledger::posting::post_payment_unapplied(&txn, refund.id, tx.telegram_id, &tx.currency, amount)
+                .await?;
+        }
+    }
+
+    txn.commit().await?;
+    if let Verdict::Exact | Verdict::Overpaid(_) = verdict {
+        metrics::counter!("payments_confirmed_total", "currency" => tx.currency.clone()).increment(1);
+    }
+    Ok(Some(verdict))
+}
+
+/// A transfer without the Krypton comment is matched to the sender's open transaction when
+/// the sender wallet was linked to a Telegram account. A transfer that was already applied
+/// or refunded keeps pointing to its transaction, so it can't pay a second one.
+pub async fn attribute(
+    db: &DatabaseConnection,
+    payment: &IncomingPayment,
+) -> Result<Option<i64>, DbErr> {
+    let applied = Transaction::find()
+        .filter(Expr::cust_with_values(
+            "transaction_data->>'chain_tx_hash' = $1",
+            [payment.hash.clone()],
+        ))
+        .one(db)
+        .await?;
+    if let Some(tx) = applied {
+        return Ok(Some(tx.id));
+    }
+    let refunded = Refund::find()
+        .filter(refund::Column::SourceTxHash.eq(&payment.hash))
+        .one(db)
+        .await?;
+    if let Some(refund) = refunded {
+        return Ok(Some(refund.transaction_id));
+    }
+
+    let Some(address) = raw_address(&payment.source) else {
+        return Ok(None);
+    };
+    let Some(wallet) = UserWallet::find()
+        .filter(user_wallet::Column::Address.eq(address))
+        .one(db)
+        .await?
+    else {
+        return Ok(None);
+    };
+    Ok(Transaction::find()
+        .filter(transaction::Column::TelegramId.eq(wallet.telegram_id))
+        .filter(transaction::Column::Status.eq("active"))
+        .filter(transaction::Column::ExpiresAt.gte(payment.paid_at))
+        .order_by_desc(transaction::Column::CreatedAt)
+        .one(db)
+        .await?
+        .map(|tx| tx.id))
+}
+
+/// Marks the transaction completed, extends the payer's subscription if it pays for a channel
+/// from the bot and books the payment in the ledger.
+async fn confirm_payment<C: ConnectionTrait>(
+    txn: &C,
+    tx: &transaction::Model,
+    payment: &IncomingPayment,
+    received: Decimal,
+) -> Result<(), LedgerError> {
+    let now = Utc::now();
+    Transaction::update_many()
+        .col_expr(transaction::Column::Status, Expr::value("completed"))
+        .col_expr(transaction::Column::CompletedAt, Expr::value(now))
+        .col_expr(
+            transaction::Column::TransactionData,
+            Expr::value(json!({
+                "chain_tx_hash": payment.hash,
+                "source": payment.source,
+                "received": received,
+            })),
+        )
+        .filter(transaction::Column::Id.eq(tx.id))
+        .exec(txn)
+        .await?;
+
+    if let Some(channel_id) = tx.channel_id {
+        webhooks::enqueue(
+            txn,
+            channel_id,
+            WebhookEvent::PaymentConfirmed,
+            json!({
+                "transaction_id": tx.public_id,
+                "channel_id": channel_id,
+                "telegram_id": tx.telegram_id,
+                "description": tx.description,
+                "price": tx.price,
+                "currency": tx.currency,
+                "received": received,
+                "chain_tx_hash": payment.hash,
+            }),
+        )
+        .await?;
+    }
+
+    // Подписку даёт только оплата канала из бота: у счёта из API плательщик неизвестен
+    if let (Some(telegram_id), Some(channel_id)) = (tx.telegram_id, tx.channel_id) {
+        // Продление: новая подписка начинается с конца текущей, если она ещё действует
+        let current = subscriptions::Entity::find()
+            .filter(subscriptions::Column::TelegramId.eq(telegram_id))
+            .filter(subscriptions::Column::ChannelId.eq(channel_id))
+            .filter(subscriptions::Column::Status.eq("active"))
+            .order_by_desc(subscriptions::Column::TimeTo)
+            .one(txn)
+            .await?;
+        let time_from = current
+            .map(|s| s.time_to)
+            .filter(|time_to| *time_to > now)
+            .unwrap_or(now);
+        let subscription = subscriptions::ActiveModel {
+            telegram_id: Set(telegram_id),
+            channel_id: Set(channel_id),
+            transaction_id: Set(tx.id),
+            status: Set("active".to_string()),
+            time_from: Set(time_from),
+            time_to: Set(time_from + Duration::days(SUBSCRIPTION_PERIOD_DAYS)),
+            created_at: Set(now),
+            updated_at: Set(now),
+        }
+        .insert(txn)
+        .await?;
+        webhooks::enqueue(
+            txn,
+            channel_id,
+            WebhookEvent::SubscriptionActivated,
+            json!({
+                "transaction_id": tx.public_id,
+                "channel_id": channel_id,
+                "telegram_id": telegram_id,
+                "time_from": subscription.time_from,
+                "time_to": subscription.time_to,
+            }),
+        )
+        .await?;
+    }
+
+    // Владелец на момент оплаты, а не текущий владелец канала
+    let owner_telegram_id = match FeeLedger::find()
+        .filter(fee_ledger::Column::TransactionId.eq(tx.id))
+        .one(txn)
+        .await?
+    {
+        Some(entry) => entry.owner_telegram_id,
+        None => {
+            // Счета из API всегда создаются с записью о комиссии, сюда попадают только старые оплаты каналов
+            let channel = match tx.channel_id {
+                Some(channel_id) => Channel::find_by_id(channel_id).one(txn).await?,
+                None => None,
+            };
+            channel
+                .ok_or_else(|| sea_orm::DbErr::RecordNotFound(format!("owner of transaction {}", tx.id)))?
+                .owner_telegram_id
+        }
+    };
+    ledger::posting::post_payment_confirmation(txn, tx, owner_telegram_id, received).await?;
+    Ok(())
+}
+
+/// Closes an active transaction whose payment window is over and gives back the promo code
+/// use it took. Returns the current status.
+pub async fn expire_if_overdue(
+    db: &DatabaseConnection,
+    transaction_id: i64,
+) -> Result<Option<String>, sea_orm::DbErr> {
+    let Some(tx) = Transaction::find_by_id(transaction_id).one(db).await? else {
+        return Ok(None);
+    };
+    let overdue = tx.expires_at.is_some_and(|expires_at| expires_at < Utc::now());
+    if tx.status != "active" || !overdue {
+        return Ok(Some(tx.status));
+    }
+    let txn = db.begin().await?;
+    let expired = Transaction::update_many()
+        .col_expr(transaction::Column::Status, Expr::value("expired"))
+        .filter(transaction::Column::Id.eq(tx.id))
+        .filter(transaction::Column::Status.eq("active"))
+        .exec(&txn)
+        .await?;
+    // Параллельная проверка могла успеть первой: и промокод, и метрика — только свои
+    if expired.rows_affected > 0
+        && let Some(promo_code_id) = tx.promo_code_id
+    {
+        promo_code::release(&txn, promo_code_id).await?;
+    }
+    txn.commit().await?;
+    if expired.rows_affected > 0 {
+        metrics::counter!("payments_expired_total", "currency" => tx.currency).increment(1);
+    }
+    Ok(Some("expired".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::toncenter;
+    use sea_orm::prelude::Uuid;
+    use serde_json::json;
+
+    fn at(seconds: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(1760000000 + seconds, 0).unwrap()
+    }
+
+    /// Transaction 42 of the fixture: 12.5 USDT, open until `at(600)`.
+    fn transaction(status: &str) -> transaction::Model {
+        transaction::Model {
+            id: 42,
+            telegram_id: Some(279058397),
+            channel_id: Some(7),
+            chat_id: None,
+            price: Decimal::new(125, 1),
+            currency: "USDT".to_string(),
+            status: status.to_string(),
+            created_at: at(0),
+            completed_at: None,
+            transaction_data: json!({}),
+            wallet_address: String::new(),
+            message_id: None,
+            promo_code_id: None,
+            original_price: None,
+            platform_fee: Decimal::ZERO,
+            owner_amount: Decimal::ZERO,
+            expires_at: Some(at(600)),
+            refunded_amount: Decimal::ZERO,
+            public_id: Uuid::nil(),
+            description: None,
+            api_key_id: None,
+            correlation_id: Uuid::nil(),
+        }
+    }
+
+    fn judge_payment(tx: &transaction::Model, payment: &IncomingPayment) -> Option<Verdict> {
+        Some(judge(tx, &payment.hash, payment.amount(&tx.currency)?, payment.paid_at))
+    }
+
+    #[test]
+    fn usdt_notification_pays_its_transaction() {
+        let payments = toncenter::tests::fixture();
+        let payment = payments.iter().find(|p| p.transaction_id == Some(42)).unwrap();
+        assert_eq!(judge_payment(&transaction("active"), payment), Some(Verdict::Exact));
+    }
+
+    #[test]
+    fn ton_transfer_doesnt_pay_usdt_transaction() {
+        let payments = toncenter::tests::fixture();
+        let payment = payments.iter().find(|p| p.currency == "TON").unwrap();
+        assert_eq!(judge_payment(&transaction("active"), payment), None);
+    }
+
+    #[test]
+    fn compares_amount_with_price() {
+        let tx = transaction("active");
+        assert_eq!(judge(&tx, "h", Decimal::new(125, 1), at(1)), Verdict::Exact);
+        assert_eq!(judge(&tx, "h", Decimal::new(13, 0), at(1)), Verdict::Overpaid(Decimal::new(5, 1)));
+        assert_eq!(judge(&tx, "h", Decimal::new(12, 0), at(1)), Verdict::Underpaid);
+    }
+
+    #[test]
+    fn late_transfer_is_expired() {
+        let tx = transaction("active");
+        assert_eq!(judge(&tx, "h", Decimal::new(125, 1), at(600)), Verdict::Exact);
+        assert_eq!(judge(&tx, "h", Decimal::new(125, 1), at(601)), Verdict::Expired);
+        assert_eq!(judge(&transaction("expired"), "h", Decimal::new(125, 1), at(1)), Verdict::Expired);
+        assert_eq!(judge(&transaction("cancelled"), "h", Decimal::new(125, 1), at(1)), Verdict::Expired);
+    }
+
+    #[test]
+    fn completed_transaction_knows_its_own_transfer() {
+        let mut tx = transaction("completed");
+        tx.transaction_data = json!({ "chain_tx_hash": "paid" });
+        assert_eq!(judge(&tx, "paid", Decimal::new(125, 1), at(1)), Verdict::Applied);
+        assert_eq!(judge(&tx, "other", Decimal::new(125, 1), at(1)), Verdict::Duplicate);
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Postgres, run with TEST_DATABASE_URL=... cargo test -- --ignored"]
+    async fn expired_payment_gives_promo_code_use_back() {
+        let test_db = migration::testing::TestDatabase::migrated().await;
+        let db = &test_db.db;
+        // одна из двух попыток промокода ушла на платёж, который так и не оплатили
+        db.execute_unprepared(
+            "INSERT INTO users (telegram_id, username) VALUES (1, 'owner');
+             INSERT INTO channels (channel_id, owner_telegram_id, title, bot_added_at) VALUES (7, 1, 'Channel', now());
+             INSERT INTO promo_codes (id, code, discount_type, discount_value, max_uses, used_count)
+                 VALUES (3, 'SPRING', 'percent', 20, 2, 2);
+             INSERT INTO payment_transactions
+                 (id, telegram_id, channel_id, price, currency, status, wallet_address, promo_code_id, expires_at)
+                 VALUES (42, 1, 7, 10, 'USDT', 'active', 'gate', 3, now() - interval '1 minute');",
+        )
+        .await
+        .unwrap();
+
+        for _ in 0..2 {
+            assert_eq!(expire_if_overdue(db, 42).await.unwrap().as_deref(), Some("expired"));
+            // повторная проверка того же платежа попытку второй раз не возвращает
+            let promo = db::PromoCode::find_by_id(3).one(db).await.unwrap().unwrap();
+            assert_eq!(promo.used_count, 1);
+        }
+        test_db.drop().await;
+    }
+}
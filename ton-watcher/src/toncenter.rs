@@ -0,0 +1,269 @@
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use boc::Boc;
+use chrono::{DateTime, Utc};
+use sea_orm::prelude::Decimal;
+use serde::Deserialize;
+use ton_address::Address;
+
+/// How many of the latest gate transactions every scan looks at.
+const SCAN_LIMIT: u32 = 50;
+/// `transfer_notification`, what a jetton wallet sends its owner on an incoming transfer.
+const TRANSFER_NOTIFICATION: u64 = 0x7362d09c;
+
+#[derive(Debug, Deserialize)]
+struct TonTransaction {
+    utime: i64,
+    transaction_id: TonTransactionId,
+    in_msg: Option<InMsg>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TonTransactionId {
+    hash: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct InMsg {
+    value: String,
+    source: String,
+    message: Option<String>,
+    payload: Option<String>,
+    msg_data: Option<MsgData>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MsgData {
+    /// Base64 BOC of the message body, set for `msg.dataRaw`.
+    body: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TonApiResponse {
+    result: Vec<TonTransaction>,
+}
+
+/// A transfer to the gate address: TON sent to it directly, or USDT credited to its jetton
+/// wallet, which the gate learns about from the wallet's `transfer_notification`.
+#[derive(Debug, Clone)]
+pub struct IncomingPayment {
+    /// The Krypton transaction named in the comment, if any.
+    pub transaction_id: Option<i64>,
+    /// Hash of the on-chain transaction, unique per transfer.
+    pub hash: String,
+    /// Who paid: the sending wallet, for USDT the owner of the sending jetton wallet.
+    pub source: String,
+    /// `TON` or `USDT`.
+    pub currency: &'static str,
+    /// Raw amount in the smallest unit of the currency.
+    pub value: String,
+    pub paid_at: DateTime<Utc>,
+}
+
+impl IncomingPayment {
+    /// The amount in whole units of `currency`, `None` if the transfer is in another
+    /// currency or the value is malformed.
+    pub fn amount(&self, currency: &str) -> Option<Decimal> {
+        if currency != self.currency {
+            return None;
+        }
+        let raw: i64 = self.value.parse().ok()?;
+        let scale = match currency {
+            "USDT" => 6,
+            _ => 9,
+        };
+        Some(Decimal::new(raw, scale).normalize())
+    }
+}
+
+/// Latest incoming transfers to `address`. USDT is only taken from `usdt_jetton_wallet`, the
+/// gate's own USDT wallet: any contract can send a `transfer_notification`.
+pub async fn fetch_incoming(
+    api_key: &str,
+    address: &str,
+    usdt_jetton_wallet: &str,
+) -> Result<Vec<IncomingPayment>, reqwest::Error> {
+    let url = format!(
+        "https://toncenter.com/api/v2/getTransactions?address={}&limit={}&api_key={}",
+        address,
+        SCAN_LIMIT,
+        api_key
+    );
+    let data: TonApiResponse = reqwest::get(&url).await?.json().await?;
+    Ok(incoming_payments(data, usdt_jetton_wallet))
+}
+
+fn incoming_payments(data: TonApiResponse, usdt_jetton_wallet: &str) -> Vec<IncomingPayment> {
+    let usdt_jetton_wallet = raw_address(usdt_jetton_wallet);
+    data.result
+        .into_iter()
+        .filter_map(|tx| {
+            let in_msg = tx.in_msg?;
+            if in_msg.source.is_empty() {
+                return None;
+            }
+            let paid_at = DateTime::from_timestamp(tx.utime, 0)?;
+            let body = in_msg.msg_data.as_ref().and_then(|data| data.body.as_deref());
+            if let Some(notification) = body.and_then(JettonNotification::parse) {
+                // TON, приложенные к уведомлению, — это газ, а не оплата
+                if raw_address(&in_msg.source) != usdt_jetton_wallet {
+                    tracing::warn!("⚠️ Уведомление о жетонах не от USDT-кошелька шлюза: {}", in_msg.source);
+                    return None;
+                }
+                return Some(IncomingPayment {
+                    transaction_id: notification.comment.as_deref().and_then(transaction_id_from_comment),
+                    hash: tx.transaction_id.hash,
+                    source: notification.sender?,
+                    currency: "USDT",
+                    value: notification.amount.to_string(),
+                    paid_at,
+                });
+            }
+            let transaction_id = in_msg
+                .payload
+                .as_deref()
+                .and_then(decode_transaction_id_from_payload)
+                .or_else(|| in_msg.message.as_deref().and_then(transaction_id_from_comment));
+            Some(IncomingPayment {
+                transaction_id,
+                hash: tx.transaction_id.hash,
+                source: in_msg.source,
+                currency: "TON",
+                value: in_msg.value,
+                paid_at,
+            })
+        })
+        .collect()
+}
+
+/// `transfer_notification#7362d09c query_id:uint64 amount:(VarUInteger 16) sender:MsgAddress
+/// forward_payload:(Either Cell ^Cell)`.
+#[derive(Debug, PartialEq, Eq)]
+struct JettonNotification {
+    amount: u128,
+    /// Owner of the jetton wallet the transfer came from, raw `0:<hex>`.
+    sender: Option<String>,
+    /// Text comment of the forward payload, if it is one.
+    comment: Option<String>,
+}
+
+impl JettonNotification {
+    /// `None` if the body isn't a `transfer_notification`.
+    fn parse(body_base64: &str) -> Option<Self> {
+        let bytes = STANDARD.decode(body_base64).ok()?;
+        let boc = Boc::parse(&bytes).ok()?;
+        let mut body = boc.root();
+        if body.load_uint(32).ok()? != TRANSFER_NOTIFICATION {
+            return None;
+        }
+        body.load_uint(64).ok()?;
+        let amount = body.load_coins().ok()?;
+        let sender = body.load_address().ok()?;
+        let payload = match body.load_bit() {
+            Ok(true) => body.load_ref().ok(),
+            Ok(false) => Some(body),
+            Err(_) => None,
+        };
+        // Комментарий — op 0 и текст в формате snake
+        let comment = payload.and_then(|mut payload| {
+            if payload.remaining_bits() < 32 || payload.load_uint(32) != Ok(0) {
+                return None;
+            }
+            String::from_utf8(payload.load_snake_bytes().ok()?).ok()
+        });
+        Some(JettonNotification { amount, sender, comment })
+    }
+}
+
+/// toncenter reports user-friendly addresses, user_wallets keeps the raw `0:<hex>` form.
+pub fn raw_address(address: &str) -> Option<String> {
+    if address.contains(':') {
+        return Some(address.to_lowercase());
+    }
+    Address::from_base64(address, None)
+        .ok()
+        .map(|decoded| decoded.address.to_raw_address())
+}
+
+fn decode_transaction_id_from_payload(payload_base64: &str) -> Option<i64> {
+    let decoded = STANDARD.decode(payload_base64).ok()?;
+    let text = String::from_utf8(decoded).ok()?;
+    if let Some(tx_str) = text.strip_prefix("transaction_id=") {
+        tx_str.parse::<i64>().ok()
+    } else {
+        None
+    }
+}
+
+/// The comment the payment link puts into the transfer: "Krypton payment for transaction {id}".
+fn transaction_id_from_comment(comment: &str) -> Option<i64> {
+    comment
+        .trim()
+        .strip_prefix("Krypton payment for transaction ")?
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// The gate's USDT jetton wallet in the fixture.
+    const USDT_JETTON_WALLET: &str = "EQD17yS-IK1bXy8E9gg-X5iVLb7LnXkVdUHERnP67p_xVQ1K";
+    const PAYER: &str = "0:8d65fcf7d4880cd5224b36c33e43617cc519fc6514f79759f65fb571649dffab";
+
+    /// A `getTransactions` reply for the gate in toncenter's v2 format: USDT with the comment
+    /// inline and in a reference, the mainnet `transfer_notification` from tonlib-core's tests
+    /// (a transfer with a binary forward payload instead of a comment), a notification from a
+    /// foreign jetton wallet and a plain TON transfer.
+    pub(crate) fn fixture() -> Vec<IncomingPayment> {
+        let data: TonApiResponse = serde_json::from_str(include_str!("fixtures/get_transactions.json")).unwrap();
+        incoming_payments(data, USDT_JETTON_WALLET)
+    }
+
+    #[test]
+    fn reads_usdt_from_transfer_notifications() {
+        let payments = fixture();
+        let usdt: Vec<_> = payments.iter().filter(|p| p.currency == "USDT").collect();
+        assert_eq!(usdt.len(), 3);
+
+        assert_eq!(usdt[0].transaction_id, Some(42));
+        assert_eq!(usdt[0].source, PAYER);
+        assert_eq!(usdt[0].amount("USDT"), Some(Decimal::new(125, 1)));
+        assert_eq!(usdt[0].hash, "cJtVvT2g9ag4ElvQ7iDFv918q6FzkS1CgcroFreaIBs=");
+
+        // комментарий в отдельной ячейке
+        assert_eq!(usdt[1].transaction_id, Some(43));
+        assert_eq!(usdt[1].amount("USDT"), Some(Decimal::new(3, 0)));
+
+        assert_eq!(usdt[2].transaction_id, None);
+        assert_eq!(usdt[2].source, raw_address("EQAd8QRKoA5sKcug9bwK6vMdmhSAoAxr8vvABvC1TCeTude5").unwrap());
+        assert_eq!(usdt[2].amount("USDT"), Some(Decimal::new(20, 0)));
+    }
+
+    #[test]
+    fn ignores_notifications_from_other_jetton_wallets() {
+        let payments = fixture();
+        assert!(payments.iter().all(|p| p.hash != "QbY3z9nrPi9g9zT5ykTlwVWcb0gdSdbtaJHz6aCGrHg="));
+        assert_eq!(payments.len(), 4);
+    }
+
+    #[test]
+    fn reads_plain_ton_transfers() {
+        let payments = fixture();
+        let ton = payments.iter().find(|p| p.currency == "TON").unwrap();
+        assert_eq!(ton.transaction_id, Some(44));
+        assert_eq!(ton.amount("TON"), Some(Decimal::new(25, 1)));
+        // TON не оплачивают транзакцию в USDT
+        assert_eq!(ton.amount("USDT"), None);
+    }
+
+    #[test]
+    fn rejects_other_bodies() {
+        // обычный текстовый комментарий
+        let comment = "te6ccgEBAQEAKAAATAAAAABLcnlwdG9uIHBheW1lbnQgZm9yIHRyYW5zYWN0aW9uIDQy";
+        assert!(Boc::parse(&STANDARD.decode(comment).unwrap()).is_ok());
+        assert_eq!(JettonNotification::parse(comment), None);
+        assert_eq!(JettonNotification::parse("not a boc"), None);
+    }
+}
@@ -0,0 +1,102 @@
+mod payouts;
+mod refunds;
+mod signer;
+#[cfg(test)]
+mod testing;
+mod toncenter;
+
+use sea_orm::{Database, DatabaseConnection, DbErr};
+use signer::{DisabledSigner, HttpSigner, SignerError, WalletSigner};
+use std::time::Duration;
+use thiserror::Error;
+use toncenter::Toncenter;
+
+#[derive(Debug, Error)]
+pub enum TreasuryError {
+    #[error("Database error: {0}")]
+    Database(#[from] DbErr),
+
+    #[error("Ledger error: {0}")]
+    Ledger(#[from] ledger::LedgerError),
+
+    #[error("Signer error: {0}")]
+    Signer(#[from] SignerError),
+
+    #[error("Toncenter error: {0}")]
+    Toncenter(#[from] reqwest::Error),
+}
+
+const CYCLE_INTERVAL: Duration = Duration::from_secs(60);
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let (database, ton, health, log, signer_url, max_messages) = config::load_or_exit("treasury", |r| {
+        let max_messages: usize = r.or("wallet_signer.max_messages", 4);
+        if max_messages == 0 {
+            r.invalid("wallet_signer.max_messages", "must be at least 1");
+        }
+        (
+            config::DatabaseConfig::read(r),
+            config::TonConfig::read(r),
+            config::HealthConfig::read(r),
+            config::LogConfig::read(r),
+            r.optional::<String>("wallet_signer.url"),
+            max_messages,
+        )
+    });
+    ops::logging::init(log.json);
+    let toncenter = Toncenter::new(ton.api_key.clone());
+    let db: DatabaseConnection = Database::connect(database.url).await?;
+    if database.migrate {
+        tracing::info!("Applying pending migrations");
+        migration::up_on_boot(&db).await?;
+    }
+    let shutdown = ops::Shutdown::listen();
+    ops::metrics::install();
+    ops::health::spawn(
+        health.port,
+        ops::Health::new(
+            vec![ops::Check::Database(db.clone()), ops::Check::Indexer(ton.api_key.clone())],
+            shutdown.clone(),
+        ),
+    );
+
+    match signer_url {
+        Some(url) => {
+            run(&db, &HttpSigner::new(url, max_messages), &toncenter, &shutdown).await
+        }
+        None => {
+            tracing::warn!("⚠️ WALLET_SIGNER_URL не задан, выплаты и возвраты не будут подписываться");
+            run(&db, &DisabledSigner, &toncenter, &shutdown).await
+        }
+    }
+}
+
+async fn run<S: WalletSigner>(
+    db: &DatabaseConnection,
+    signer: &S,
+    toncenter: &Toncenter,
+    shutdown: &ops::Shutdown,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // Цикл не прерывается посередине: подписанная пачка должна успеть записаться в базу
+    while !shutdown.requested() {
+        if let Err(err) = refunds::track_refunds(db, toncenter).await {
+            tracing::error!("❌ Ошибка отслеживания возвратов: {}", err);
+        }
+        if let Err(err) = refunds::send_refunds(db, signer, toncenter).await {
+            tracing::error!("❌ Ошибка отправки возвратов: {}", err);
+        }
+        if let Err(err) = payouts::track_batches(db, toncenter).await {
+            tracing::error!("❌ Ошибка отслеживания выплат: {}", err);
+        }
+        if let Err(err) = payouts::create_batches(db, signer, toncenter).await {
+            tracing::error!("❌ Ошибка создания выплат: {}", err);
+        }
+        tokio::select! {
+            _ = tokio::time::sleep(CYCLE_INTERVAL) => {}
+            _ = shutdown.wait() => {}
+        }
+    }
+    tracing::info!("👋 treasury остановлен");
+    Ok(())
+}
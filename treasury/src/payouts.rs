@@ -0,0 +1,501 @@
+use crate::TreasuryError;
+use crate::signer::{MESSAGE_TTL, Transfer, WalletSigner};
+use crate::toncenter::{Chain, Outcome};
+use chrono::{Duration, Utc};
+use db::settings::PayoutSettings;
+use db::{Channel, Payout, PayoutBatch, PayoutBatchModel, PayoutModel, channel, payout, payout_batch};
+use ledger::AccountKind;
+use sea_orm::prelude::Decimal;
+use sea_orm::sea_query::Expr;
+use sea_orm::{
+    ActiveModelTrait, ActiveValue::Set, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter,
+    QueryOrder, QuerySelect, TransactionTrait,
+};
+
+/// How long a batch may stay `pending` while it is being signed. An older one was left
+/// behind by a process that stopped between saving the batch and saving its signature.
+const SIGNING_LEASE: Duration = Duration::minutes(5);
+
+/// Pays every owner whose available balance reached the threshold, `batch_size` owners
+/// per signed message.
+pub async fn create_batches<S: WalletSigner, C: Chain>(
+    db: &DatabaseConnection,
+    signer: &S,
+    chain: &C,
+) -> Result<(), TreasuryError> {
+    fail_abandoned_batches(db).await?;
+    let settings = PayoutSettings::load(db).await?;
+    let batch_size = settings.batch_size.min(signer.max_messages()).max(1);
+
+    for total in ledger::balance::totals(db, AccountKind::OwnerPayable).await? {
+        let currency = total.currency;
+        let mut transfers: Vec<(i64, Transfer)> = Vec::new();
+        for balance in
+            ledger::balance::balances_by_currency(db, AccountKind::OwnerPayable, &currency).await?
+        {
+            let Some(owner_telegram_id) = balance.telegram_id else {
+                continue;
+            };
+            let available = balance.amount - in_flight(db, owner_telegram_id, &currency).await?;
+            if available < settings.min_amount {
+                continue;
+            }
+            let Some(destination) = destination_address(db, owner_telegram_id).await? else {
+                tracing::warn!("⚠️ У владельца {} нет адреса для выплаты", owner_telegram_id);
+                continue;
+            };
+            transfers.push((
+                owner_telegram_id,
+                Transfer {
+                    destination,
+                    amount: available,
+                    currency: currency.clone(),
+                    comment: String::new(),
+                },
+            ));
+        }
+
+        for chunk in transfers.chunks(batch_size) {
+            create_batch(db, signer, chain, &currency, chunk).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Payouts already batched but not confirmed yet: the ledger is only debited on
+/// confirmation, so they have to be subtracted to avoid paying the same money twice.
+/// A payout stays pending until its message landed or can't land anymore.
+async fn in_flight(
+    db: &DatabaseConnection,
+    owner_telegram_id: i64,
+    currency: &str,
+) -> Result<Decimal, TreasuryError> {
+    let amount: Option<Decimal> = Payout::find()
+        .select_only()
+        .column_as(payout::Column::Amount.sum(), "amount")
+        .filter(payout::Column::OwnerTelegramId.eq(owner_telegram_id))
+        .filter(payout::Column::Currency.eq(currency))
+        .filter(payout::Column::Status.eq("pending"))
+        .into_tuple()
+        .one(db)
+        .await?
+        .flatten();
+    Ok(amount.unwrap_or_default())
+}
+
+/// The crypto address of the owner's most recently configured channel.
+async fn destination_address(
+    db: &DatabaseConnection,
+    owner_telegram_id: i64,
+) -> Result<Option<String>, TreasuryError> {
+    Ok(Channel::find()
+        .filter(channel::Column::OwnerTelegramId.eq(owner_telegram_id))
+        .filter(channel::Column::CryptoAddress.is_not_null())
+        .order_by_desc(channel::Column::LastCheckDate)
+        .one(db)
+        .await?
+        .and_then(|c| c.crypto_address))
+}
+
+async fn create_batch<S: WalletSigner, C: Chain>(
+    db: &DatabaseConnection,
+    signer: &S,
+    chain: &C,
+    currency: &str,
+    transfers: &[(i64, Transfer)],
+) -> Result<(), TreasuryError> {
+    let now = Utc::now();
+    let txn = db.begin().await?;
+    let batch = PayoutBatchModel {
+        status: Set("pending".to_string()),
+        currency: Set(currency.to_string()),
+        created_at: Set(now),
+        updated_at: Set(now),
+        ..Default::default()
+    }
+    .insert(&txn)
+    .await?;
+    let mut signed_transfers = Vec::with_capacity(transfers.len());
+    for (owner_telegram_id, transfer) in transfers {
+        let payout = PayoutModel {
+            batch_id: Set(batch.id),
+            owner_telegram_id: Set(*owner_telegram_id),
+            currency: Set(currency.to_string()),
+            amount: Set(transfer.amount),
+            destination_address: Set(transfer.destination.clone()),
+            status: Set("pending".to_string()),
+            created_at: Set(now),
+            ..Default::default()
+        }
+        .insert(&txn)
+        .await?;
+        signed_transfers.push(Transfer {
+            comment: format!("Krypton payout {}", payout.id),
+            ..transfer.clone()
+        });
+    }
+    txn.commit().await?;
+
+    let signed = match signer.sign(&signed_transfers, Utc::now() + MESSAGE_TTL).await {
+        Ok(signed) => signed,
+        Err(err) => {
+            tracing::error!("❌ Батч выплат {} не подписан: {}", batch.id, err);
+            fail_batch(db, batch.id, &err.to_string()).await?;
+            return Ok(());
+        }
+    };
+    // Подпись сохраняем, только пока батч ещё наш: просроченный батч уже отдан заново
+    let Some(batch) = PayoutBatch::update_many()
+        .col_expr(payout_batch::Column::Status, Expr::value("signed"))
+        .col_expr(payout_batch::Column::SignedBoc, Expr::value(signed.boc))
+        .col_expr(payout_batch::Column::MessageHash, Expr::value(signed.message_hash))
+        .col_expr(payout_batch::Column::ValidUntil, Expr::value(signed.valid_until))
+        .col_expr(payout_batch::Column::UpdatedAt, Expr::value(Utc::now()))
+        .filter(payout_batch::Column::Id.eq(batch.id))
+        .filter(payout_batch::Column::Status.eq("pending"))
+        .exec_with_returning(db)
+        .await?
+        .pop()
+    else {
+        tracing::warn!("⚠️ Батч выплат {} закрыт, пока подписывался: подпись выброшена", batch.id);
+        return Ok(());
+    };
+    broadcast(db, chain, batch).await
+}
+
+/// Fails batches stuck in `pending` past the signing lease, freeing their payouts for a
+/// new batch. Their message was never saved, so it was never broadcast and can't land.
+async fn fail_abandoned_batches(db: &DatabaseConnection) -> Result<(), TreasuryError> {
+    let txn = db.begin().await?;
+    let abandoned = PayoutBatch::update_many()
+        .col_expr(payout_batch::Column::Status, Expr::value("failed"))
+        .col_expr(payout_batch::Column::Error, Expr::value("abandoned before it was signed"))
+        .col_expr(payout_batch::Column::UpdatedAt, Expr::value(Utc::now()))
+        .filter(payout_batch::Column::Status.eq("pending"))
+        .filter(payout_batch::Column::UpdatedAt.lt(Utc::now() - SIGNING_LEASE))
+        .exec_with_returning(&txn)
+        .await?;
+    if abandoned.is_empty() {
+        return Ok(());
+    }
+    let ids: Vec<i64> = abandoned.iter().map(|batch| batch.id).collect();
+    tracing::warn!("⚠️ Брошенные до подписи батчи выплат закрыты: {:?}", ids);
+    Payout::update_many()
+        .col_expr(payout::Column::Status, Expr::value("failed"))
+        .filter(payout::Column::BatchId.is_in(ids))
+        .filter(payout::Column::Status.eq("pending"))
+        .exec(&txn)
+        .await?;
+    txn.commit().await?;
+    Ok(())
+}
+
+/// A failed broadcast leaves the batch `signed`: the message may still have reached the
+/// network, so it's retried until it is seen on chain or expires.
+async fn broadcast<C: Chain>(
+    db: &DatabaseConnection,
+    chain: &C,
+    batch: payout_batch::Model,
+) -> Result<(), TreasuryError> {
+    let Some(boc) = batch.signed_boc.clone() else {
+        return Ok(());
+    };
+    let id = batch.id;
+    let mut batch: PayoutBatchModel = batch.into();
+    match chain.send_boc(&boc).await {
+        Ok(()) => {
+            batch.status = Set("sent".to_string());
+            batch.error = Set(None);
+        }
+        Err(err) => {
+            tracing::warn!("⚠️ Батч выплат {} не отправлен: {}", id, err);
+            batch.error = Set(Some(err.to_string()));
+        }
+    }
+    batch.updated_at = Set(Utc::now());
+    batch.update(db).await?;
+    Ok(())
+}
+
+/// Follows signed and sent batches until their message lands or the chain is past its
+/// `valid_until`. Confirmation is what debits the owners in the ledger; only a message that
+/// can't land anymore fails the batch and frees its payouts for a new one.
+pub async fn track_batches<C: Chain>(db: &DatabaseConnection, chain: &C) -> Result<(), TreasuryError> {
+    let batches = PayoutBatch::find()
+        .filter(payout_batch::Column::Status.is_in(["signed", "sent"]))
+        .order_by_asc(payout_batch::Column::Id)
+        .all(db)
+        .await?;
+    for batch in batches {
+        let (Some(hash), Some(valid_until)) = (batch.message_hash.clone(), batch.valid_until) else {
+            continue;
+        };
+        match chain.outcome(&hash, valid_until).await? {
+            Outcome::Sent => {
+                confirm_batch(db, batch.id).await?;
+                tracing::info!("✅ Батч выплат {} подтверждён", batch.id);
+            }
+            Outcome::Rejected => {
+                tracing::warn!("⚠️ Кошелёк принял батч выплат {}, но не отправил переводы", batch.id);
+                fail_batch(db, batch.id, "the wallet processed the message without sending the transfers")
+                    .await?;
+            }
+            Outcome::Expired => fail_batch(db, batch.id, "expired before reaching the chain").await?,
+            Outcome::Pending if batch.status == "signed" => broadcast(db, chain, batch).await?,
+            Outcome::Pending => {}
+        }
+    }
+    Ok(())
+}
+
+async fn confirm_batch(db: &DatabaseConnection, batch_id: i64) -> Result<(), TreasuryError> {
+    let now = Utc::now();
+    let txn = db.begin().await?;
+    PayoutBatch::update_many()
+        .col_expr(payout_batch::Column::Status, Expr::value("confirmed"))
+        .col_expr(payout_batch::Column::ConfirmedAt, Expr::value(now))
+        .col_expr(payout_batch::Column::UpdatedAt, Expr::value(now))
+        .filter(payout_batch::Column::Id.eq(batch_id))
+        .exec(&txn)
+        .await?;
+    let payouts = Payout::find()
+        .filter(payout::Column::BatchId.eq(batch_id))
+        .filter(payout::Column::Status.eq("pending"))
+        .all(&txn)
+        .await?;
+    for payout in payouts {
+        ledger::posting::post_payout(
+            &txn,
+            payout.id,
+            payout.owner_telegram_id,
+            &payout.currency,
+            payout.amount,
+        )
+        .await?;
+        let mut payout: PayoutModel = payout.into();
+        payout.status = Set("confirmed".to_string());
+        payout.confirmed_at = Set(Some(now));
+        payout.update(&txn).await?;
+    }
+    txn.commit().await?;
+    Ok(())
+}
+
+async fn fail_batch(db: &DatabaseConnection, batch_id: i64, error: &str) -> Result<(), TreasuryError> {
+    let txn = db.begin().await?;
+    PayoutBatch::update_many()
+        .col_expr(payout_batch::Column::Status, Expr::value("failed"))
+        .col_expr(payout_batch::Column::Error, Expr::value(error))
+        .col_expr(payout_batch::Column::UpdatedAt, Expr::value(Utc::now()))
+        .filter(payout_batch::Column::Id.eq(batch_id))
+        .exec(&txn)
+        .await?;
+    Payout::update_many()
+        .col_expr(payout::Column::Status, Expr::value("failed"))
+        .filter(payout::Column::BatchId.eq(batch_id))
+        .filter(payout::Column::Status.eq("pending"))
+        .exec(&txn)
+        .await?;
+    txn.commit().await?;
+    Ok(())
+}
+
+/// The batch state machine against a real Postgres, with the chain and the signer faked.
+/// Ignored by default, see `migration::testing` for how to run them.
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{FakeChain, FakeSigner};
+    use ledger::{AccountKey, JournalKind};
+    use migration::testing::TestDatabase;
+    use sea_orm::ConnectionTrait;
+
+    const OWNER: i64 = 1;
+
+    /// An owner Krypton owes 25 USDT, with a payout address.
+    async fn seed(db: &DatabaseConnection) {
+        db.execute_unprepared(
+            "INSERT INTO users (telegram_id, username) VALUES (1, 'owner');
+             INSERT INTO channels (channel_id, owner_telegram_id, title, bot_added_at, crypto_address)
+                 VALUES (10, 1, 'Channel', now(), 'UQowner');",
+        )
+        .await
+        .unwrap();
+        let txn = db.begin().await.unwrap();
+        ledger::posting::post(
+            &txn,
+            JournalKind::PaymentConfirmed,
+            1,
+            "Payment for channel 10".to_string(),
+            &[
+                (AccountKey::gateway("USDT"), Decimal::new(25, 0)),
+                (AccountKey::owner_payable(OWNER, "USDT"), Decimal::new(-25, 0)),
+            ],
+        )
+        .await
+        .unwrap();
+        txn.commit().await.unwrap();
+    }
+
+    async fn batches(db: &DatabaseConnection) -> Vec<(payout_batch::Model, Vec<payout::Model>)> {
+        PayoutBatch::find()
+            .find_with_related(Payout)
+            .order_by_asc(payout_batch::Column::Id)
+            .all(db)
+            .await
+            .unwrap()
+    }
+
+    async fn owed(db: &DatabaseConnection) -> Decimal {
+        ledger::balance::balance(db, &AccountKey::owner_payable(OWNER, "USDT")).await.unwrap()
+    }
+
+    async fn setup() -> (TestDatabase, FakeSigner, FakeChain) {
+        let test_db = TestDatabase::migrated().await;
+        seed(&test_db.db).await;
+        (test_db, FakeSigner::new(), FakeChain::new())
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Postgres, run with TEST_DATABASE_URL=... cargo test -- --ignored"]
+    async fn batch_waits_for_the_chain_not_the_clock() {
+        let (test_db, signer, chain) = setup().await;
+        let db = &test_db.db;
+        create_batches(db, &signer, &chain).await.unwrap();
+        let created = batches(db).await;
+        assert_eq!(created.len(), 1);
+        let (batch, payouts) = &created[0];
+        assert_eq!(batch.status, "sent");
+        assert!(batch.valid_until.is_some());
+        assert_eq!(payouts[0].amount, Decimal::new(25, 0));
+        assert_eq!(chain.sent.lock().unwrap().len(), 1);
+
+        // Прошёл час по нашим часам, но сеть ещё не дошла до valid_until
+        db.execute_unprepared("UPDATE payout_batches SET created_at = now() - interval '1 hour'")
+            .await
+            .unwrap();
+        track_batches(db, &chain).await.unwrap();
+        create_batches(db, &signer, &chain).await.unwrap();
+        let tracked = batches(db).await;
+        assert_eq!(tracked.len(), 1, "the owner isn't paid twice while the message may land");
+        assert_eq!(tracked[0].0.status, "sent");
+        assert_eq!(tracked[0].1[0].status, "pending");
+        test_db.drop().await;
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Postgres, run with TEST_DATABASE_URL=... cargo test -- --ignored"]
+    async fn landed_batch_debits_the_owner() {
+        let (test_db, signer, chain) = setup().await;
+        let db = &test_db.db;
+        create_batches(db, &signer, &chain).await.unwrap();
+        chain.answer(Outcome::Sent);
+        track_batches(db, &chain).await.unwrap();
+
+        let (batch, payouts) = batches(db).await.remove(0);
+        assert_eq!(batch.status, "confirmed");
+        assert_eq!(payouts[0].status, "confirmed");
+        assert_eq!(owed(db).await, Decimal::ZERO);
+
+        // повторная проверка ничего не проводит второй раз
+        track_batches(db, &chain).await.unwrap();
+        create_batches(db, &signer, &chain).await.unwrap();
+        assert_eq!(batches(db).await.len(), 1);
+        assert_eq!(owed(db).await, Decimal::ZERO);
+        test_db.drop().await;
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Postgres, run with TEST_DATABASE_URL=... cargo test -- --ignored"]
+    async fn expired_batch_is_paid_again() {
+        let (test_db, signer, chain) = setup().await;
+        let db = &test_db.db;
+        create_batches(db, &signer, &chain).await.unwrap();
+        chain.answer(Outcome::Expired);
+        track_batches(db, &chain).await.unwrap();
+
+        let (batch, payouts) = batches(db).await.remove(0);
+        assert_eq!(batch.status, "failed");
+        assert_eq!(payouts[0].status, "failed");
+        assert_eq!(owed(db).await, Decimal::new(25, 0));
+
+        chain.answer(Outcome::Pending);
+        create_batches(db, &signer, &chain).await.unwrap();
+        let all = batches(db).await;
+        assert_eq!(all.len(), 2);
+        assert_eq!(all[1].0.status, "sent");
+        assert_eq!(all[1].1[0].amount, Decimal::new(25, 0));
+        test_db.drop().await;
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Postgres, run with TEST_DATABASE_URL=... cargo test -- --ignored"]
+    async fn rejected_batch_fails_without_debit() {
+        let (test_db, signer, chain) = setup().await;
+        let db = &test_db.db;
+        create_batches(db, &signer, &chain).await.unwrap();
+        chain.answer(Outcome::Rejected);
+        track_batches(db, &chain).await.unwrap();
+
+        let (batch, payouts) = batches(db).await.remove(0);
+        assert_eq!(batch.status, "failed");
+        assert_eq!(payouts[0].status, "failed");
+        assert_eq!(owed(db).await, Decimal::new(25, 0));
+        test_db.drop().await;
+    }
+
+    /// A batch saved with its payouts, whose process stopped before the signature was saved.
+    async fn interrupted_batch(db: &DatabaseConnection) {
+        db.execute_unprepared(
+            "WITH batch AS (
+                 INSERT INTO payout_batches (status, currency, created_at, updated_at)
+                     VALUES ('pending', 'USDT', now(), now())
+                     RETURNING id
+             )
+             INSERT INTO payouts (batch_id, owner_telegram_id, currency, amount, destination_address, status, created_at)
+                 SELECT id, 1, 'USDT', 25, 'UQowner', 'pending', now() FROM batch;",
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Postgres, run with TEST_DATABASE_URL=... cargo test -- --ignored"]
+    async fn interrupted_batch_is_paid_again_after_the_lease() {
+        let (test_db, signer, chain) = setup().await;
+        let db = &test_db.db;
+        interrupted_batch(db).await;
+
+        // батч может ещё подписываться: выплату не дублируем
+        create_batches(db, &signer, &chain).await.unwrap();
+        let all = batches(db).await;
+        assert_eq!(all.len(), 1);
+        assert_eq!(all[0].0.status, "pending");
+
+        db.execute_unprepared("UPDATE payout_batches SET updated_at = now() - interval '6 minutes'")
+            .await
+            .unwrap();
+        create_batches(db, &signer, &chain).await.unwrap();
+        let all = batches(db).await;
+        assert_eq!(all.len(), 2);
+        assert_eq!(all[0].0.status, "failed");
+        assert_eq!(all[0].1[0].status, "failed");
+        assert_eq!(all[1].0.status, "sent");
+        assert_eq!(all[1].1[0].amount, Decimal::new(25, 0));
+        assert_eq!(owed(db).await, Decimal::new(25, 0), "nothing is debited before confirmation");
+        test_db.drop().await;
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Postgres, run with TEST_DATABASE_URL=... cargo test -- --ignored"]
+    async fn batch_without_valid_until_is_left_alone() {
+        let (test_db, signer, chain) = setup().await;
+        let db = &test_db.db;
+        create_batches(db, &signer, &chain).await.unwrap();
+        db.execute_unprepared("UPDATE payout_batches SET valid_until = NULL").await.unwrap();
+        chain.answer(Outcome::Expired);
+        track_batches(db, &chain).await.unwrap();
+        assert_eq!(batches(db).await[0].0.status, "sent");
+        test_db.drop().await;
+    }
+}
@@ -0,0 +1,233 @@
+use crate::TreasuryError;
+use crate::signer::{MESSAGE_TTL, Transfer, WalletSigner};
+use crate::toncenter::{Chain, Outcome};
+use chrono::Utc;
+use db::{Refund, RefundModel, Transaction, refund, transaction};
+use sea_orm::sea_query::Expr;
+use sea_orm::{
+    ActiveModelTrait, ActiveValue::Set, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter,
+    QueryOrder, TransactionTrait,
+};
+
+/// Signs and sends every approved refund back to the address the money came from.
+/// A refund that can't be signed stays `approved` and is retried on the next cycle.
+pub async fn send_refunds<S: WalletSigner, C: Chain>(
+    db: &DatabaseConnection,
+    signer: &S,
+    chain: &C,
+) -> Result<(), TreasuryError> {
+    let refunds = Refund::find()
+        .filter(refund::Column::Status.eq("approved"))
+        .order_by_asc(refund::Column::Id)
+        .all(db)
+        .await?;
+    for refund in refunds {
+        let transfer = Transfer {
+            destination: refund.destination_address.clone(),
+            amount: refund.amount,
+            currency: refund.currency.clone(),
+            comment: format!(
+                "Krypton refund {} for transaction {}",
+                refund.id, refund.transaction_id
+            ),
+        };
+        let id = refund.id;
+        let mut refund: RefundModel = refund.into();
+        refund.updated_at = Set(Utc::now());
+        match signer.sign(&[transfer], Utc::now() + MESSAGE_TTL).await {
+            Ok(signed) => {
+                refund.status = Set("signed".to_string());
+                refund.signed_boc = Set(Some(signed.boc));
+                refund.message_hash = Set(Some(signed.message_hash));
+                refund.valid_until = Set(Some(signed.valid_until));
+                refund.error = Set(None);
+                let refund = refund.update(db).await?;
+                broadcast(db, chain, refund).await?;
+            }
+            Err(err) => {
+                tracing::error!("❌ Возврат {} не подписан: {}", id, err);
+                refund.error = Set(Some(err.to_string()));
+                refund.update(db).await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn broadcast<C: Chain>(
+    db: &DatabaseConnection,
+    chain: &C,
+    refund: refund::Model,
+) -> Result<(), TreasuryError> {
+    let Some(boc) = refund.signed_boc.clone() else {
+        return Ok(());
+    };
+    let id = refund.id;
+    let mut refund: RefundModel = refund.into();
+    match chain.send_boc(&boc).await {
+        Ok(()) => {
+            refund.status = Set("sent".to_string());
+            refund.error = Set(None);
+        }
+        Err(err) => {
+            tracing::warn!("⚠️ Возврат {} не отправлен: {}", id, err);
+            refund.error = Set(Some(err.to_string()));
+        }
+    }
+    refund.update(db).await?;
+    Ok(())
+}
+
+/// Follows signed and sent refunds until their message lands or the chain is past its
+/// `valid_until`. Confirmation debits the refunds account and is added to the original
+/// transaction's `refunded_amount`; a message that can't land anymore puts the refund back
+/// to `approved` to be signed again.
+pub async fn track_refunds<C: Chain>(db: &DatabaseConnection, chain: &C) -> Result<(), TreasuryError> {
+    let refunds = Refund::find()
+        .filter(refund::Column::Status.is_in(["signed", "sent"]))
+        .order_by_asc(refund::Column::Id)
+        .all(db)
+        .await?;
+    for refund in refunds {
+        let (Some(hash), Some(valid_until)) = (refund.message_hash.clone(), refund.valid_until) else {
+            continue;
+        };
+        let error = match chain.outcome(&hash, valid_until).await? {
+            Outcome::Sent => {
+                confirm_refund(db, refund.id).await?;
+                tracing::info!("✅ Возврат {} подтверждён", refund.id);
+                continue;
+            }
+            Outcome::Rejected => "the wallet processed the message without sending the transfer",
+            Outcome::Expired => "expired before reaching the chain",
+            Outcome::Pending => {
+                if refund.status == "signed" {
+                    broadcast(db, chain, refund).await?;
+                }
+                continue;
+            }
+        };
+        tracing::warn!("⚠️ Возврат {} не дошёл, подпишем заново: {}", refund.id, error);
+        let mut refund: RefundModel = refund.into();
+        refund.status = Set("approved".to_string());
+        refund.signed_boc = Set(None);
+        refund.message_hash = Set(None);
+        refund.valid_until = Set(None);
+        refund.error = Set(Some(error.to_string()));
+        refund.updated_at = Set(Utc::now());
+        refund.update(db).await?;
+    }
+    Ok(())
+}
+
+async fn confirm_refund(db: &DatabaseConnection, refund_id: i64) -> Result<(), TreasuryError> {
+    let now = Utc::now();
+    let txn = db.begin().await?;
+    let Some(refund) = Refund::find_by_id(refund_id).one(&txn).await? else {
+        return Ok(());
+    };
+    if refund.status == "confirmed" {
+        return Ok(());
+    }
+    let mut model: RefundModel = refund.clone().into();
+    model.status = Set("confirmed".to_string());
+    model.confirmed_at = Set(Some(now));
+    model.updated_at = Set(now);
+    model.update(&txn).await?;
+    Transaction::update_many()
+        .col_expr(
+            transaction::Column::RefundedAmount,
+            Expr::col(transaction::Column::RefundedAmount).add(refund.amount),
+        )
+        .filter(transaction::Column::Id.eq(refund.transaction_id))
+        .exec(&txn)
+        .await?;
+    ledger::posting::post_refund_sent(&txn, refund.id, &refund.currency, refund.amount).await?;
+    txn.commit().await?;
+    Ok(())
+}
+
+/// Refunds against a real Postgres, with the chain and the signer faked.
+/// Ignored by default, see `migration::testing` for how to run them.
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{FakeChain, FakeSigner};
+    use migration::testing::TestDatabase;
+    use sea_orm::ConnectionTrait;
+    use sea_orm::prelude::Decimal;
+
+    async fn setup() -> (TestDatabase, FakeSigner, FakeChain) {
+        let test_db = TestDatabase::migrated().await;
+        test_db
+            .db
+            .execute_unprepared(
+                "INSERT INTO payment_transactions (id, price, currency, status, wallet_address)
+                     VALUES (1, 10, 'USDT', 'expired', 'UQgate');
+                 INSERT INTO refunds (transaction_id, reason, amount, currency, destination_address,
+                                      source_tx_hash, status)
+                     VALUES (1, 'expired', 10, 'USDT', 'UQpayer', 'tx-1', 'approved');",
+            )
+            .await
+            .unwrap();
+        (test_db, FakeSigner::new(), FakeChain::new())
+    }
+
+    async fn refund(db: &DatabaseConnection) -> refund::Model {
+        Refund::find().one(db).await.unwrap().unwrap()
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Postgres, run with TEST_DATABASE_URL=... cargo test -- --ignored"]
+    async fn refund_waits_for_the_chain_not_the_clock() {
+        let (test_db, signer, chain) = setup().await;
+        let db = &test_db.db;
+        send_refunds(db, &signer, &chain).await.unwrap();
+        let sent = refund(db).await;
+        assert_eq!(sent.status, "sent");
+        assert_eq!(sent.message_hash.as_deref(), Some("hash-0"));
+        assert!(sent.valid_until.is_some());
+
+        db.execute_unprepared("UPDATE refunds SET updated_at = now() - interval '1 hour'")
+            .await
+            .unwrap();
+        track_refunds(db, &chain).await.unwrap();
+        send_refunds(db, &signer, &chain).await.unwrap();
+        assert_eq!(refund(db).await.message_hash.as_deref(), Some("hash-0"), "not signed again");
+        test_db.drop().await;
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Postgres, run with TEST_DATABASE_URL=... cargo test -- --ignored"]
+    async fn expired_refund_is_signed_again() {
+        let (test_db, signer, chain) = setup().await;
+        let db = &test_db.db;
+        send_refunds(db, &signer, &chain).await.unwrap();
+        chain.answer(Outcome::Expired);
+        track_refunds(db, &chain).await.unwrap();
+        let expired = refund(db).await;
+        assert_eq!(expired.status, "approved");
+        assert_eq!(expired.valid_until, None);
+
+        chain.answer(Outcome::Pending);
+        send_refunds(db, &signer, &chain).await.unwrap();
+        assert_eq!(refund(db).await.message_hash.as_deref(), Some("hash-1"));
+        test_db.drop().await;
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Postgres, run with TEST_DATABASE_URL=... cargo test -- --ignored"]
+    async fn landed_refund_is_confirmed_once() {
+        let (test_db, signer, chain) = setup().await;
+        let db = &test_db.db;
+        send_refunds(db, &signer, &chain).await.unwrap();
+        chain.answer(Outcome::Sent);
+        track_refunds(db, &chain).await.unwrap();
+        track_refunds(db, &chain).await.unwrap();
+
+        assert_eq!(refund(db).await.status, "confirmed");
+        let tx = Transaction::find_by_id(1).one(db).await.unwrap().unwrap();
+        assert_eq!(tx.refunded_amount, Decimal::new(10, 0));
+        test_db.drop().await;
+    }
+}
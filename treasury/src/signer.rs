@@ -0,0 +1,133 @@
+use chrono::{DateTime, Duration, Utc};
+use sea_orm::prelude::Decimal;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use thiserror::Error;
+
+/// One outgoing message from the gate wallet.
+#[derive(Clone, Debug)]
+pub struct Transfer {
+    pub destination: String,
+    pub amount: Decimal,
+    pub currency: String,
+    pub comment: String,
+}
+
+/// How long a signed message stays valid: the network drops it after `valid_until`,
+/// so a message that didn't land by then never will.
+pub const MESSAGE_TTL: Duration = Duration::minutes(15);
+
+/// External message ready to be broadcast. `message_hash` identifies it on chain.
+#[derive(Clone, Debug, Deserialize)]
+pub struct SignedTransfer {
+    pub boc: String,
+    pub message_hash: String,
+    /// The `valid_until` the signer put into the message, unix seconds on the wire.
+    #[serde(with = "chrono::serde::ts_seconds")]
+    pub valid_until: DateTime<Utc>,
+}
+
+#[derive(Debug, Error)]
+pub enum SignerError {
+    #[error("Signer request failed: {0}")]
+    Request(#[from] reqwest::Error),
+
+    #[error("Signer rejected the transfer: {0}")]
+    Rejected(String),
+
+    #[error("Wallet signer is not configured")]
+    Disabled,
+}
+
+/// Produces signed transfers from the gate wallet. The private key never lives in Krypton
+/// services: implementations delegate to whatever holds it (remote signer, KMS, ...).
+pub trait WalletSigner: Send + Sync {
+    /// How many transfers fit into one signed message (4 for wallet v4, 255 for v5).
+    fn max_messages(&self) -> usize;
+
+    /// Signs `transfers` into one message that the wallet accepts until `valid_until`.
+    fn sign(
+        &self,
+        transfers: &[Transfer],
+        valid_until: DateTime<Utc>,
+    ) -> impl Future<Output = Result<SignedTransfer, SignerError>> + Send;
+}
+
+#[derive(Serialize)]
+struct SignRequest<'a> {
+    transfers: Vec<TransferRequest<'a>>,
+    #[serde(with = "chrono::serde::ts_seconds")]
+    valid_until: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+struct TransferRequest<'a> {
+    destination: &'a str,
+    amount: String,
+    currency: &'a str,
+    comment: &'a str,
+}
+
+/// Signer service reachable over HTTP: `POST {url}/sign` with the transfers and
+/// `valid_until`, answers with `{"boc": "...", "message_hash": "...", "valid_until": ...}`.
+pub struct HttpSigner {
+    client: reqwest::Client,
+    url: String,
+    max_messages: usize,
+}
+
+impl HttpSigner {
+    pub fn new(url: String, max_messages: usize) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url,
+            max_messages,
+        }
+    }
+}
+
+impl WalletSigner for HttpSigner {
+    fn max_messages(&self) -> usize {
+        self.max_messages
+    }
+
+    async fn sign(&self, transfers: &[Transfer], valid_until: DateTime<Utc>) -> Result<SignedTransfer, SignerError> {
+        let request = SignRequest {
+            valid_until,
+            transfers: transfers
+                .iter()
+                .map(|t| TransferRequest {
+                    destination: &t.destination,
+                    amount: t.amount.to_string(),
+                    currency: &t.currency,
+                    comment: &t.comment,
+                })
+                .collect(),
+        };
+        let response = self
+            .client
+            .post(format!("{}/sign", self.url))
+            .json(&request)
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(SignerError::Rejected(format!("{}: {}", status, body)));
+        }
+        Ok(response.json().await?)
+    }
+}
+
+/// Used when no signer is configured: nothing leaves the gate wallet.
+pub struct DisabledSigner;
+
+impl WalletSigner for DisabledSigner {
+    fn max_messages(&self) -> usize {
+        1
+    }
+
+    async fn sign(&self, _transfers: &[Transfer], _valid_until: DateTime<Utc>) -> Result<SignedTransfer, SignerError> {
+        Err(SignerError::Disabled)
+    }
+}
@@ -0,0 +1,63 @@
+//! Stand-ins for the wallet signer and the chain in tests.
+
+use crate::signer::{SignedTransfer, SignerError, Transfer, WalletSigner};
+use crate::toncenter::{Chain, Outcome};
+use chrono::{DateTime, Utc};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Signs anything, with message hashes `hash-0`, `hash-1`, ...
+pub struct FakeSigner {
+    signed: AtomicUsize,
+}
+
+impl FakeSigner {
+    pub fn new() -> Self {
+        Self { signed: AtomicUsize::new(0) }
+    }
+}
+
+impl WalletSigner for FakeSigner {
+    fn max_messages(&self) -> usize {
+        4
+    }
+
+    async fn sign(&self, _transfers: &[Transfer], valid_until: DateTime<Utc>) -> Result<SignedTransfer, SignerError> {
+        let n = self.signed.fetch_add(1, Ordering::SeqCst);
+        Ok(SignedTransfer {
+            boc: format!("boc-{}", n),
+            message_hash: format!("hash-{}", n),
+            valid_until,
+        })
+    }
+}
+
+/// Answers every lookup with `outcome` and remembers what was broadcast.
+pub struct FakeChain {
+    outcome: Mutex<Outcome>,
+    pub sent: Mutex<Vec<String>>,
+}
+
+impl FakeChain {
+    pub fn new() -> Self {
+        Self {
+            outcome: Mutex::new(Outcome::Pending),
+            sent: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn answer(&self, outcome: Outcome) {
+        *self.outcome.lock().unwrap() = outcome;
+    }
+}
+
+impl Chain for FakeChain {
+    async fn send_boc(&self, boc: &str) -> Result<(), reqwest::Error> {
+        self.sent.lock().unwrap().push(boc.to_string());
+        Ok(())
+    }
+
+    async fn outcome(&self, _message_hash: &str, _valid_until: DateTime<Utc>) -> Result<Outcome, reqwest::Error> {
+        Ok(*self.outcome.lock().unwrap())
+    }
+}
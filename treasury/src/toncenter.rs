@@ -0,0 +1,224 @@
+use chrono::{DateTime, Duration, Utc};
+use serde::Deserialize;
+use serde_json::Value;
+use std::future::Future;
+
+/// How long the indexer may lag behind the chain. A message that isn't indexed this long
+/// after it stopped being valid was never accepted.
+const INDEX_LAG: Duration = Duration::minutes(2);
+
+/// What became of a signed external message.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    /// The wallet processed the message and sent its transfers.
+    Sent,
+    /// The wallet processed the message but sent nothing, e.g. for lack of balance.
+    /// The message can't be accepted again.
+    Rejected,
+    /// The chain is past the message's `valid_until` and it never landed.
+    Expired,
+    /// It may still land: keep broadcasting and checking.
+    Pending,
+}
+
+impl Outcome {
+    /// `landed` tells whether the wallet transaction that processed the message sent the
+    /// transfers, if the indexer has that transaction. `chain_time` is the time of the latest
+    /// masterchain block, when known.
+    pub fn decide(landed: Option<bool>, valid_until: DateTime<Utc>, chain_time: Option<DateTime<Utc>>) -> Self {
+        match landed {
+            Some(true) => Outcome::Sent,
+            Some(false) => Outcome::Rejected,
+            None if chain_time.is_some_and(|chain_time| chain_time > valid_until + INDEX_LAG) => Outcome::Expired,
+            None => Outcome::Pending,
+        }
+    }
+}
+
+/// Where signed messages go and where their fate is read from.
+pub trait Chain: Send + Sync {
+    fn send_boc(&self, boc: &str) -> impl Future<Output = Result<(), reqwest::Error>> + Send;
+
+    /// Looks the message up by its hash and, if it isn't there, compares `valid_until`
+    /// with the time of the latest masterchain block, never with the local clock.
+    fn outcome(
+        &self,
+        message_hash: &str,
+        valid_until: DateTime<Utc>,
+    ) -> impl Future<Output = Result<Outcome, reqwest::Error>> + Send;
+}
+
+/// Minimal toncenter client: broadcasting signed messages over v2 and finding them
+/// with the v3 indexer.
+pub struct Toncenter {
+    client: reqwest::Client,
+    base_url: String,
+    api_key: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TransactionsResponse {
+    transactions: Vec<IndexedTransaction>,
+}
+
+#[derive(Debug, Deserialize)]
+struct IndexedTransaction {
+    description: TransactionDescription,
+}
+
+#[derive(Debug, Deserialize)]
+struct TransactionDescription {
+    aborted: bool,
+    action: Option<ActionPhase>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ActionPhase {
+    success: bool,
+}
+
+impl TransactionsResponse {
+    /// Whether the transaction that took the message in also sent its transfers.
+    fn landed(&self) -> Option<bool> {
+        let tx = self.transactions.first()?;
+        let description = &tx.description;
+        Some(!description.aborted && description.action.as_ref().is_some_and(|action| action.success))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct MasterchainInfo {
+    last: Block,
+}
+
+#[derive(Debug, Deserialize)]
+struct Block {
+    // v3 отдаёт числа uint32 то строкой, то числом
+    gen_utime: Value,
+}
+
+impl Block {
+    fn time(&self) -> Option<DateTime<Utc>> {
+        let seconds = match &self.gen_utime {
+            Value::String(seconds) => seconds.parse().ok()?,
+            seconds => seconds.as_i64()?,
+        };
+        DateTime::from_timestamp(seconds, 0)
+    }
+}
+
+impl Toncenter {
+    pub fn new(api_key: String) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            base_url: "https://toncenter.com/api".to_string(),
+            api_key,
+        }
+    }
+
+    async fn transactions_by_message(&self, message_hash: &str) -> Result<TransactionsResponse, reqwest::Error> {
+        self.client
+            .get(format!("{}/v3/transactionsByMessage", self.base_url))
+            .query(&[("msg_hash", message_hash), ("direction", "in"), ("api_key", &self.api_key)])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await
+    }
+
+    async fn chain_time(&self) -> Result<Option<DateTime<Utc>>, reqwest::Error> {
+        let info: MasterchainInfo = self
+            .client
+            .get(format!("{}/v3/masterchainInfo", self.base_url))
+            .query(&[("api_key", &self.api_key)])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(info.last.time())
+    }
+}
+
+impl Chain for Toncenter {
+    async fn send_boc(&self, boc: &str) -> Result<(), reqwest::Error> {
+        self.client
+            .post(format!("{}/v2/sendBoc", self.base_url))
+            .query(&[("api_key", &self.api_key)])
+            .json(&serde_json::json!({ "boc": boc }))
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    async fn outcome(&self, message_hash: &str, valid_until: DateTime<Utc>) -> Result<Outcome, reqwest::Error> {
+        let landed = self.transactions_by_message(message_hash).await?.landed();
+        // Время сети спрашиваем после поиска: раз сообщения не было раньше, не будет и позже
+        let chain_time = match landed {
+            Some(_) => None,
+            None => self.chain_time().await?,
+        };
+        Ok(Outcome::decide(landed, valid_until, chain_time))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(seconds: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(1760000000 + seconds, 0).unwrap()
+    }
+
+    #[test]
+    fn waits_for_the_chain_to_pass_valid_until() {
+        let valid_until = at(900);
+        assert_eq!(Outcome::decide(None, valid_until, Some(at(0))), Outcome::Pending);
+        assert_eq!(Outcome::decide(None, valid_until, Some(at(900))), Outcome::Pending);
+        // индексатор может ещё не знать о сообщении, принятом в последний момент
+        assert_eq!(Outcome::decide(None, valid_until, Some(at(900 + 120))), Outcome::Pending);
+        assert_eq!(Outcome::decide(None, valid_until, Some(at(900 + 121))), Outcome::Expired);
+        // без времени сети ничего не считаем просроченным
+        assert_eq!(Outcome::decide(None, valid_until, None), Outcome::Pending);
+    }
+
+    #[test]
+    fn landed_message_is_final_whatever_the_time() {
+        assert_eq!(Outcome::decide(Some(true), at(900), Some(at(5000))), Outcome::Sent);
+        assert_eq!(Outcome::decide(Some(false), at(900), None), Outcome::Rejected);
+    }
+
+    #[test]
+    fn reads_indexed_transactions() {
+        let sent: TransactionsResponse = serde_json::from_str(
+            r#"{"transactions": [{"hash": "x", "description": {"type": "ord", "aborted": false,
+                "compute_ph": {"success": true}, "action": {"success": true, "result_code": 0}}}],
+                "address_book": {}}"#,
+        )
+        .unwrap();
+        assert_eq!(sent.landed(), Some(true));
+
+        let no_balance: TransactionsResponse = serde_json::from_str(
+            r#"{"transactions": [{"hash": "x", "description": {"type": "ord", "aborted": true,
+                "compute_ph": {"success": true}, "action": {"success": false, "result_code": 37}}}],
+                "address_book": {}}"#,
+        )
+        .unwrap();
+        assert_eq!(no_balance.landed(), Some(false));
+
+        let missing: TransactionsResponse =
+            serde_json::from_str(r#"{"transactions": [], "address_book": {}}"#).unwrap();
+        assert_eq!(missing.landed(), None);
+    }
+
+    #[test]
+    fn reads_masterchain_time() {
+        let info: MasterchainInfo =
+            serde_json::from_str(r#"{"last": {"seqno": 1, "gen_utime": "1760000900"}, "first": {}}"#).unwrap();
+        assert_eq!(info.last.time(), Some(at(900)));
+        let info: MasterchainInfo = serde_json::from_str(r#"{"last": {"gen_utime": 1760000900}}"#).unwrap();
+        assert_eq!(info.last.time(), Some(at(900)));
+    }
+}
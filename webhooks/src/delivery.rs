@@ -0,0 +1,290 @@
+use chrono::{DateTime, Utc};
+use db::{WebhookDelivery, WebhookDeliveryModel, WebhookEndpoint, webhook_delivery, webhook_endpoint};
+use sea_orm::sea_query::{Expr, LockBehavior, LockType, Order, Query};
+use sea_orm::{
+    ActiveModelTrait, ActiveValue::Set, ColumnTrait, DatabaseConnection, DbErr, EntityTrait,
+    QueryFilter,
+};
+use serde_json::json;
+use std::time::Duration;
+
+use crate::signature::{EVENT_ID_HEADER, EVENT_TYPE_HEADER, SIGNATURE_HEADER, sign};
+use crate::target;
+
+/// After this many failed attempts a delivery is given up and stays `failed` until replayed.
+pub const MAX_ATTEMPTS: i32 = 10;
+/// Delay before the first retry, doubled for every next one.
+const RETRY_BASE: Duration = Duration::from_secs(30);
+const RETRY_MAX: Duration = Duration::from_secs(6 * 60 * 60);
+/// How many deliveries one pass sends.
+const BATCH_SIZE: u64 = 50;
+/// A claimed delivery is hidden from other workers for this long, well over the time
+/// a whole batch can take. If the worker dies meanwhile, the delivery comes back after it.
+const CLAIM_LEASE: Duration = Duration::from_secs(15 * 60);
+
+/// Why an attempt didn't count as delivered.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Failure {
+    /// HTTP status, if the endpoint answered at all.
+    pub status: Option<u16>,
+    pub error: String,
+}
+
+/// Delay before the attempt that follows `attempts` failed ones.
+pub fn backoff(attempts: i32) -> Duration {
+    let exponent = attempts.clamp(1, 16) as u32 - 1;
+    RETRY_BASE.saturating_mul(1 << exponent).min(RETRY_MAX)
+}
+
+/// JSON body of a delivery. It's built from the stored row, so retries and replays send
+/// the same document.
+pub fn body(delivery: &webhook_delivery::Model) -> Vec<u8> {
+    serde_json::to_vec(&json!({
+        "id": delivery.event_id,
+        "type": delivery.event_type,
+        "created_at": delivery.created_at,
+        "data": delivery.payload,
+    }))
+    .unwrap()
+}
+
+/// One signed POST of `delivery` to `url`. Any 2xx answer is a success.
+pub async fn send(
+    client: &reqwest::Client,
+    url: &str,
+    secret: &str,
+    delivery: &webhook_delivery::Model,
+) -> Result<u16, Failure> {
+    // Подпись ставится в момент отправки: пачка может рассылаться дольше окна проверки у получателя
+    send_signed_at(client, url, secret, delivery, Utc::now()).await
+}
+
+/// [`send`] with the signature timestamp set to `signed_at`.
+async fn send_signed_at(
+    client: &reqwest::Client,
+    url: &str,
+    secret: &str,
+    delivery: &webhook_delivery::Model,
+    signed_at: DateTime<Utc>,
+) -> Result<u16, Failure> {
+    let body = body(delivery);
+    let response = client
+        .post(url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header(SIGNATURE_HEADER, sign(secret, signed_at.timestamp(), &body))
+        .header(EVENT_ID_HEADER, delivery.event_id.to_string())
+        .header(EVENT_TYPE_HEADER, &delivery.event_type)
+        .body(body)
+        .send()
+        .await
+        .map_err(|err| Failure {
+            status: None,
+            error: err.to_string(),
+        })?;
+    let status = response.status();
+    if status.is_success() {
+        Ok(status.as_u16())
+    } else {
+        Err(Failure {
+            status: Some(status.as_u16()),
+            error: format!("endpoint answered {}", status),
+        })
+    }
+}
+
+/// Claims up to [`BATCH_SIZE`] due deliveries by moving their next attempt a lease ahead.
+/// Rows another worker is claiming at the same moment are skipped, not waited for, so
+/// every delivery goes to exactly one worker.
+async fn claim_due(db: &DatabaseConnection, now: DateTime<Utc>) -> Result<Vec<webhook_delivery::Model>, DbErr> {
+    let due = Query::select()
+        .column(webhook_delivery::Column::Id)
+        .from(WebhookDelivery)
+        .and_where(webhook_delivery::Column::Status.eq("pending"))
+        .and_where(webhook_delivery::Column::NextAttemptAt.lte(now))
+        .order_by(webhook_delivery::Column::NextAttemptAt, Order::Asc)
+        .limit(BATCH_SIZE)
+        .lock_with_behavior(LockType::Update, LockBehavior::SkipLocked)
+        .to_owned();
+    WebhookDelivery::update_many()
+        .col_expr(webhook_delivery::Column::NextAttemptAt, Expr::value(now + CLAIM_LEASE))
+        .filter(webhook_delivery::Column::Id.in_subquery(due))
+        .exec_with_returning(db)
+        .await
+}
+
+/// Sends the deliveries whose time has come and records the outcome of every attempt.
+/// Returns how many were attempted.
+pub async fn deliver_due(db: &DatabaseConnection, client: &reqwest::Client) -> Result<usize, DbErr> {
+    let now = Utc::now();
+    let due = claim_due(db, now).await?;
+    let endpoints = WebhookEndpoint::find()
+        .filter(webhook_endpoint::Column::Id.is_in(due.iter().map(|d| d.endpoint_id)))
+        .all(db)
+        .await?;
+    let count = due.len();
+    for delivery in due {
+        let endpoint = endpoints.iter().find(|e| e.id == delivery.endpoint_id);
+        let outcome = match endpoint {
+            Some(endpoint) if endpoint.is_active => match target::parse(&endpoint.url) {
+                Ok(_) => send(client, &endpoint.url, &endpoint.secret, &delivery).await,
+                Err(error) => Err(Failure {
+                    status: None,
+                    error: format!("endpoint URL {}", error),
+                }),
+            },
+            _ => Err(Failure {
+                status: None,
+                error: "endpoint is disabled".to_string(),
+            }),
+        };
+        record(db, delivery, outcome).await?;
+    }
+    Ok(count)
+}
+
+async fn record(
+    db: &DatabaseConnection,
+    delivery: webhook_delivery::Model,
+    outcome: Result<u16, Failure>,
+) -> Result<(), DbErr> {
+    let now = Utc::now();
+    let attempts = delivery.attempts + 1;
+    let id = delivery.id;
+    let mut model: WebhookDeliveryModel = delivery.into();
+    model.attempts = Set(attempts);
+    match outcome {
+        Ok(status) => {
+            model.status = Set("delivered".to_string());
+            model.response_status = Set(Some(status as i32));
+            model.error = Set(None);
+            model.delivered_at = Set(Some(now));
+        }
+        Err(failure) => {
+            tracing::error!("❌ Вебхук {} не доставлен (попытка {}): {}", id, attempts, failure.error);
+            model.response_status = Set(failure.status.map(i32::from));
+            model.error = Set(Some(failure.error));
+            if attempts >= MAX_ATTEMPTS {
+                model.status = Set("failed".to_string());
+            } else {
+                model.next_attempt_at = Set(now + backoff(attempts));
+            }
+        }
+    }
+    model.update(db).await?;
+    Ok(())
+}
+
+/// Queues a delivery again with a fresh set of attempts, whatever happened to it before.
+/// The event id stays the same, so receivers can tell a replay from a new event.
+pub async fn replay(db: &DatabaseConnection, delivery_id: i64) -> Result<Option<webhook_delivery::Model>, DbErr> {
+    let Some(delivery) = WebhookDelivery::find_by_id(delivery_id).one(db).await? else {
+        return Ok(None);
+    };
+    let mut model: WebhookDeliveryModel = delivery.into();
+    model.status = Set("pending".to_string());
+    model.attempts = Set(0);
+    model.next_attempt_at = Set(Utc::now());
+    model.error = Set(None);
+    model.delivered_at = Set(None);
+    model.update(db).await.map(Some)
+}
+
+/// Endpoint of a delivery, to check who may see or replay it.
+pub async fn endpoint_of(
+    db: &DatabaseConnection,
+    delivery: &webhook_delivery::Model,
+) -> Result<Option<webhook_endpoint::Model>, DbErr> {
+    WebhookEndpoint::find_by_id(delivery.endpoint_id).one(db).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signature::verify;
+    use axum::{Router, body::Bytes, http::HeaderMap, http::StatusCode, routing::post};
+    use sea_orm::prelude::Uuid;
+    use tokio::sync::mpsc;
+
+    fn delivery() -> webhook_delivery::Model {
+        webhook_delivery::Model {
+            id: 1,
+            endpoint_id: 1,
+            event_id: Uuid::nil(),
+            event_type: "payment.confirmed".to_string(),
+            payload: json!({"transaction_id": "00000000-0000-0000-0000-000000000000"}),
+            status: "pending".to_string(),
+            attempts: 0,
+            next_attempt_at: Utc::now(),
+            response_status: None,
+            error: None,
+            created_at: DateTime::from_timestamp(1760000000, 0).unwrap(),
+            delivered_at: None,
+        }
+    }
+
+    /// Local stand-in for an owner's endpoint: answers `status` and hands over what it got.
+    async fn stand_in(status: StatusCode) -> (String, mpsc::UnboundedReceiver<(HeaderMap, Bytes)>) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let app = Router::new().route(
+            "/hook",
+            post(move |headers: HeaderMap, body: Bytes| {
+                let sender = sender.clone();
+                async move {
+                    sender.send((headers, body)).unwrap();
+                    status
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (url, receiver)
+    }
+
+    #[tokio::test]
+    async fn delivers_signed_payload() {
+        let (url, mut received) = stand_in(StatusCode::NO_CONTENT).await;
+        let signed_at = DateTime::from_timestamp(1760000100, 0).unwrap();
+
+        let result = send_signed_at(&reqwest::Client::new(), &url, "whsec_test", &delivery(), signed_at).await;
+
+        assert_eq!(result, Ok(204));
+        let (headers, body) = received.recv().await.unwrap();
+        let signature = headers[SIGNATURE_HEADER].to_str().unwrap();
+        assert_eq!(verify("whsec_test", signature, &body), Some(1760000100));
+        assert_eq!(headers[EVENT_TYPE_HEADER], "payment.confirmed");
+        assert_eq!(headers[EVENT_ID_HEADER], Uuid::nil().to_string());
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["type"], "payment.confirmed");
+        assert_eq!(body["data"], delivery().payload);
+    }
+
+    #[tokio::test]
+    async fn error_status_is_a_failure() {
+        let (url, _received) = stand_in(StatusCode::INTERNAL_SERVER_ERROR).await;
+
+        let result = send(&reqwest::Client::new(), &url, "whsec_test", &delivery()).await;
+
+        assert_eq!(result.unwrap_err().status, Some(500));
+    }
+
+    #[tokio::test]
+    async fn unreachable_endpoint_is_a_failure() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        drop(listener);
+
+        let result = send(&reqwest::Client::new(), &url, "whsec_test", &delivery()).await;
+
+        assert_eq!(result.unwrap_err().status, None);
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        assert_eq!(backoff(1), Duration::from_secs(30));
+        assert_eq!(backoff(2), Duration::from_secs(60));
+        assert_eq!(backoff(5), Duration::from_secs(480));
+        assert_eq!(backoff(MAX_ATTEMPTS), Duration::from_secs(15360));
+        assert_eq!(backoff(20), Duration::from_secs(6 * 60 * 60));
+    }
+}
@@ -0,0 +1,73 @@
+use db::{WebhookDeliveryModel, WebhookEndpoint, webhook_endpoint};
+use sea_orm::{ActiveModelTrait, ActiveValue::Set, ColumnTrait, ConnectionTrait, DbErr, EntityTrait, QueryFilter};
+use serde_json::Value;
+
+/// What a webhook can be sent about. The string forms are the `type` of the payload and
+/// what owners subscribe to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WebhookEvent {
+    /// A transaction was paid. Reference: the transaction.
+    PaymentConfirmed,
+    /// A payment bought a subscription period.
+    SubscriptionActivated,
+    /// A subscription period ended without a renewal.
+    SubscriptionExpired,
+    /// The bot removed a user whose subscription ended from the channel.
+    MemberRemoved,
+}
+
+impl WebhookEvent {
+    pub const ALL: [WebhookEvent; 4] = [
+        WebhookEvent::PaymentConfirmed,
+        WebhookEvent::SubscriptionActivated,
+        WebhookEvent::SubscriptionExpired,
+        WebhookEvent::MemberRemoved,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WebhookEvent::PaymentConfirmed => "payment.confirmed",
+            WebhookEvent::SubscriptionActivated => "subscription.activated",
+            WebhookEvent::SubscriptionExpired => "subscription.expired",
+            WebhookEvent::MemberRemoved => "member.removed",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|event| event.as_str() == value)
+    }
+}
+
+fn is_subscribed(endpoint: &webhook_endpoint::Model, event: WebhookEvent) -> bool {
+    match endpoint.events.as_array() {
+        Some(events) if !events.is_empty() => events.iter().any(|e| e.as_str() == Some(event.as_str())),
+        _ => true,
+    }
+}
+
+/// Queues `event` for every active endpoint of the channel subscribed to it. Call it inside
+/// the database transaction that makes the change, so the event is sent if and only if
+/// the change is committed.
+pub async fn enqueue<C: ConnectionTrait>(
+    db: &C,
+    channel_id: i64,
+    event: WebhookEvent,
+    data: Value,
+) -> Result<(), DbErr> {
+    let endpoints = WebhookEndpoint::find()
+        .filter(webhook_endpoint::Column::ChannelId.eq(channel_id))
+        .filter(webhook_endpoint::Column::IsActive.eq(true))
+        .all(db)
+        .await?;
+    for endpoint in endpoints.into_iter().filter(|endpoint| is_subscribed(endpoint, event)) {
+        WebhookDeliveryModel {
+            endpoint_id: Set(endpoint.id),
+            event_type: Set(event.as_str().to_string()),
+            payload: Set(data.clone()),
+            ..Default::default()
+        }
+        .insert(db)
+        .await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,10 @@
+//! Outbound webhooks: events are queued into `webhook_deliveries` in the same database
+//! transaction as the change they describe, and the `webhooks` binary delivers them with
+//! retries.
+
+pub mod delivery;
+pub mod event;
+pub mod signature;
+pub mod target;
+
+pub use event::{WebhookEvent, enqueue};
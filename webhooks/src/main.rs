@@ -0,0 +1,47 @@
+use sea_orm::{Database, DatabaseConnection};
+use std::time::Duration;
+
+/// How often the queue is checked when there's nothing to send.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+/// Owners' endpoints get this long to answer.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let (database, health, log) = config::load_or_exit("webhooks", |r| {
+        (
+            config::DatabaseConfig::read(r),
+            config::HealthConfig::read(r),
+            config::LogConfig::read(r),
+        )
+    });
+    ops::logging::init(log.json);
+    let db: DatabaseConnection = Database::connect(database.url).await?;
+    if database.migrate {
+        tracing::info!("Applying pending migrations");
+        migration::up_on_boot(&db).await?;
+    }
+    let shutdown = ops::Shutdown::listen();
+    ops::metrics::install();
+    ops::health::spawn(
+        health.port,
+        ops::Health::new(vec![ops::Check::Database(db.clone())], shutdown.clone()),
+    );
+    let client = webhooks::target::client(REQUEST_TIMEOUT)?;
+
+    // Пачка отправляется целиком, иначе доставки останутся без записи о попытке
+    while !shutdown.requested() {
+        match webhooks::delivery::deliver_due(&db, &client).await {
+            // Очередь не пуста — сразу берём следующую пачку
+            Ok(count) if count > 0 => continue,
+            Ok(_) => {}
+            Err(err) => tracing::error!("❌ Не удалось отправить вебхуки: {}", err),
+        }
+        tokio::select! {
+            _ = tokio::time::sleep(POLL_INTERVAL) => {}
+            _ = shutdown.wait() => {}
+        }
+    }
+    tracing::info!("👋 webhooks остановлен");
+    Ok(())
+}
@@ -0,0 +1,152 @@
+//! Where webhooks may be sent. Owners choose the URL, so without these checks the worker
+//! would POST into our own network on their behalf: only https URLs on public addresses
+//! are accepted, redirects are never followed, and names are resolved to public addresses
+//! only, so a record changed after the check can't point the worker inside either.
+
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::{Url, redirect};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+
+/// Whether `ip` is reachable from the internet, as opposed to loopback, private,
+/// link-local and other special-purpose ranges.
+pub fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_v4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(mapped) => is_public_v4(mapped),
+            None => is_public_v6(ip),
+        },
+    }
+}
+
+fn is_public_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        || a == 0
+        // 100.64.0.0/10, адреса за NAT провайдера
+        || (a == 100 && (64..128).contains(&b))
+        // 198.18.0.0/15, сети для тестов производительности
+        || (a == 198 && (b == 18 || b == 19))
+        // 240.0.0.0/4, зарезервировано
+        || a >= 240)
+}
+
+fn is_public_v6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_multicast()
+        // fc00::/7, уникальные локальные адреса
+        || (first & 0xfe00) == 0xfc00
+        // fe80::/10, локальные адреса канала
+        || (first & 0xffc0) == 0xfe80
+        // 2001:db8::/32, документация
+        || (first == 0x2001 && ip.segments()[1] == 0x0db8))
+}
+
+/// Parses an endpoint URL and checks what can be checked without DNS: https, a host,
+/// and a public address if the host is an IP literal.
+pub fn parse(url: &str) -> Result<Url, String> {
+    let url = Url::parse(url).map_err(|_| "not a valid URL".to_string())?;
+    if url.scheme() != "https" {
+        return Err("must be https".to_string());
+    }
+    match url.host() {
+        None => Err("has no host".to_string()),
+        Some(url::Host::Ipv4(ip)) if !is_public(ip.into()) => Err("points to a private address".to_string()),
+        Some(url::Host::Ipv6(ip)) if !is_public(ip.into()) => Err("points to a private address".to_string()),
+        Some(_) => Ok(url),
+    }
+}
+
+/// [`parse`], then resolves the host and refuses it if any of its addresses isn't public.
+/// The worker checks again on every delivery, this gives owners the error up front.
+pub async fn check(url: &str) -> Result<Url, String> {
+    let url = parse(url)?;
+    if let Some(url::Host::Domain(host)) = url.host() {
+        let port = url.port_or_known_default().unwrap_or(443);
+        let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, port))
+            .await
+            .map_err(|_| "host can't be resolved".to_string())?
+            .collect();
+        if addrs.is_empty() || addrs.iter().any(|addr| !is_public(addr.ip())) {
+            return Err("points to a private address".to_string());
+        }
+    }
+    Ok(url)
+}
+
+/// Resolver of the delivery client: hands reqwest only the public addresses of a name.
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((name.as_str(), 0))
+                .await?
+                .filter(|addr| is_public(addr.ip()))
+                .collect();
+            if addrs.is_empty() {
+                return Err(format!("{} has no public address", name.as_str()).into());
+            }
+            let addrs: Addrs = Box::new(addrs.into_iter());
+            Ok(addrs)
+        })
+    }
+}
+
+/// HTTP client for deliveries: public addresses only and no redirects.
+pub fn client(timeout: Duration) -> reqwest::Result<reqwest::Client> {
+    reqwest::Client::builder()
+        .timeout(timeout)
+        .redirect(redirect::Policy::none())
+        .dns_resolver(std::sync::Arc::new(PublicResolver))
+        .build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn private_ranges_are_not_public() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:10.0.0.1",
+        ] {
+            assert!(!is_public(ip.parse().unwrap()), "{}", ip);
+        }
+        assert!(is_public("93.184.216.34".parse().unwrap()));
+        assert!(is_public("2606:4700::1111".parse().unwrap()));
+    }
+
+    #[test]
+    fn parse_refuses_non_https_and_private_literals() {
+        assert!(parse("https://example.com/hook").is_ok());
+        assert_eq!(parse("http://example.com/hook").unwrap_err(), "must be https");
+        assert_eq!(parse("https://127.0.0.1/hook").unwrap_err(), "points to a private address");
+        assert_eq!(parse("https://[::1]/hook").unwrap_err(), "points to a private address");
+        assert_eq!(parse("https://169.254.169.254/").unwrap_err(), "points to a private address");
+    }
+
+    #[tokio::test]
+    async fn check_refuses_names_of_private_addresses() {
+        assert_eq!(check("https://localhost/hook").await.unwrap_err(), "points to a private address");
+    }
+}
@@ -0,0 +1,67 @@
+//! Delivery against a real Postgres: workers running side by side never pick up the same
+//! delivery. Ignored by default, see `migration::testing` for how to run it.
+
+use db::{WebhookDelivery, webhook_delivery};
+use migration::testing::TestDatabase;
+use sea_orm::{ConnectionTrait, DatabaseConnection, EntityTrait, QueryOrder};
+
+async fn seed(db: &DatabaseConnection, url: &str, is_active: bool, deliveries: usize) {
+    db.execute_unprepared(&format!(
+        "INSERT INTO users (telegram_id, username) VALUES (1, 'owner') ON CONFLICT DO NOTHING;
+         INSERT INTO channels (channel_id, owner_telegram_id, title, bot_added_at)
+             VALUES (10, 1, 'Channel', now()) ON CONFLICT DO NOTHING;
+         INSERT INTO webhook_endpoints (channel_id, url, secret, is_active)
+             VALUES (10, '{}', 'whsec_test', {});
+         INSERT INTO webhook_deliveries (endpoint_id, event_id, event_type, payload)
+             SELECT currval('webhook_endpoints_id_seq'), gen_random_uuid(), 'payment.confirmed', '{{}}'
+             FROM generate_series(1, {});",
+        url, is_active, deliveries
+    ))
+    .await
+    .unwrap();
+}
+
+async fn attempts(db: &DatabaseConnection) -> Vec<webhook_delivery::Model> {
+    WebhookDelivery::find()
+        .order_by_asc(webhook_delivery::Column::Id)
+        .all(db)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+#[ignore = "needs Postgres, run with TEST_DATABASE_URL=... cargo test -- --ignored"]
+async fn parallel_workers_attempt_each_delivery_once() {
+    let test_db = TestDatabase::migrated().await;
+    let db = &test_db.db;
+    seed(db, "https://example.com/hook", false, 40).await;
+    let client = reqwest::Client::new();
+
+    let (first, second) = tokio::join!(
+        webhooks::delivery::deliver_due(db, &client),
+        webhooks::delivery::deliver_due(db, &client),
+    );
+    let third = webhooks::delivery::deliver_due(db, &client).await.unwrap();
+
+    assert_eq!(first.unwrap() + second.unwrap(), 40);
+    assert_eq!(third, 0, "attempted deliveries wait for their retry");
+    let deliveries = attempts(db).await;
+    assert!(deliveries.iter().all(|d| d.attempts == 1 && d.status == "pending"));
+    test_db.drop().await;
+}
+
+#[tokio::test]
+#[ignore = "needs Postgres, run with TEST_DATABASE_URL=... cargo test -- --ignored"]
+async fn private_endpoints_are_not_called() {
+    let test_db = TestDatabase::migrated().await;
+    let db = &test_db.db;
+    seed(db, "https://127.0.0.1/hook", true, 1).await;
+
+    let count = webhooks::delivery::deliver_due(db, &reqwest::Client::new()).await.unwrap();
+
+    assert_eq!(count, 1);
+    let delivery = attempts(db).await.remove(0);
+    assert_eq!(delivery.response_status, None);
+    assert_eq!(delivery.error.as_deref(), Some("endpoint URL points to a private address"));
+    test_db.drop().await;
+}